@@ -0,0 +1,26 @@
+-- Parsed token instructions and account states written by --postgres-url.
+-- The Vixen stream doesn't carry the slot or transaction signature yet, so
+-- both are null for now.
+CREATE TABLE instructions (
+    id BIGSERIAL PRIMARY KEY,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    slot BIGINT,
+    signature TEXT,
+    program TEXT NOT NULL,
+    instruction_type TEXT NOT NULL,
+    payload JSONB NOT NULL
+);
+
+CREATE INDEX instructions_program_type ON instructions (program, instruction_type);
+
+CREATE TABLE account_states (
+    id BIGSERIAL PRIMARY KEY,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    slot BIGINT,
+    signature TEXT,
+    program TEXT NOT NULL,
+    state_type TEXT NOT NULL,
+    payload JSONB NOT NULL
+);
+
+CREATE INDEX account_states_program_type ON account_states (program, state_type);
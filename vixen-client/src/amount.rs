@@ -0,0 +1,136 @@
+use std::{fmt, str::FromStr};
+
+// A token amount as a user writes it, e.g. `1.5` or `1_000`, independent of
+// any mint. Converting to base units needs the mint's decimals, which keeps
+// every amount flag interpreted the same way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UiAmount {
+    mantissa: u128,
+    scale: u32,
+}
+
+impl UiAmount {
+    pub fn from_base_units(amount: u64, decimals: u8) -> Self {
+        Self {
+            mantissa: amount as u128,
+            scale: decimals as u32,
+        }
+    }
+
+    pub fn to_base_units(self, decimals: u8) -> Result<u64, String> {
+        let decimals = decimals as u32;
+        let base = if self.scale > decimals {
+            let divisor = 10u128.pow(self.scale - decimals);
+            if !self.mantissa.is_multiple_of(divisor) {
+                return Err(format!(
+                    "{} has more than {} decimal place(s)",
+                    self, decimals
+                ));
+            }
+            self.mantissa / divisor
+        } else {
+            10u128
+                .checked_pow(decimals - self.scale)
+                .and_then(|factor| self.mantissa.checked_mul(factor))
+                .ok_or_else(|| format!("{} is too large", self))?
+        };
+        u64::try_from(base).map_err(|_| format!("{} is too large", self))
+    }
+}
+
+impl FromStr for UiAmount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cleaned: String = s.chars().filter(|&c| c != '_').collect();
+        let (whole, fraction) = cleaned.split_once('.').unwrap_or((&cleaned, ""));
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty()) || !is_digits(whole) || !is_digits(fraction) {
+            return Err(format!(
+                "invalid amount {:?}, expected e.g. 1.5 or 1_000",
+                s
+            ));
+        }
+        let mantissa = format!("{}{}", whole, fraction)
+            .parse::<u128>()
+            .map_err(|_| format!("amount {:?} is too large", s))?;
+        Ok(Self {
+            mantissa,
+            scale: fraction.len() as u32,
+        })
+    }
+}
+
+impl fmt::Display for UiAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = format!(
+            "{:0>width$}",
+            self.mantissa,
+            width = self.scale as usize + 1
+        );
+        let (whole, fraction) = digits.split_at(digits.len() - self.scale as usize);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            f.write_str(whole)
+        } else {
+            write!(f, "{}.{}", whole, fraction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> UiAmount {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_to_base_units() {
+        assert_eq!(parse("1.5").to_base_units(6), Ok(1_500_000));
+        assert_eq!(parse("1_000").to_base_units(6), Ok(1_000_000_000));
+        assert_eq!(parse("0.000001").to_base_units(6), Ok(1));
+        assert_eq!(parse(".25").to_base_units(2), Ok(25));
+        assert_eq!(parse("7").to_base_units(0), Ok(7));
+        assert_eq!(parse("1.500").to_base_units(1), Ok(15));
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        for s in ["", ".", "abc", "1.2.3", "-1", "1e6", "1,000"] {
+            assert!(s.parse::<UiAmount>().is_err(), "{:?} should not parse", s);
+        }
+    }
+
+    #[test]
+    fn rejects_excess_precision_and_overflow() {
+        assert!(parse("1.0000001").to_base_units(6).is_err());
+        assert!(parse("18446744073709551616").to_base_units(0).is_err());
+        assert!(parse("20000000000000").to_base_units(9).is_err());
+    }
+
+    #[test]
+    fn round_trips_through_base_units() {
+        for (s, decimals) in [
+            ("1.5", 6),
+            ("1000", 6),
+            ("0.000001", 6),
+            ("42", 0),
+            ("3.14", 9),
+        ] {
+            let base = parse(s).to_base_units(decimals).unwrap();
+            assert_eq!(UiAmount::from_base_units(base, decimals).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn formats_like_the_token_program() {
+        for (amount, decimals) in [(10_000_000_000, 6), (1, 6), (123_456_789, 9), (5, 0)] {
+            assert_eq!(
+                UiAmount::from_base_units(amount, decimals).to_string(),
+                spl_token_2022::amount_to_ui_amount_string_trimmed(amount, decimals)
+            );
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::{collections::VecDeque, ops::Range, time::Instant};
+
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
+use solana_sdk::{
+    instruction::Instruction,
+    message::Message,
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+};
+use tracing::warn;
+
+use crate::{
+    error::{bail, Error, Result},
+    token::{send_instructions, SendOptions},
+};
+
+// Instructions that have to land together, e.g. a transfer and its memo,
+// with the keypairs besides the payer that sign them
+pub struct BatchItem<'a> {
+    pub instructions: Vec<Instruction>,
+    pub signers: Vec<&'a Keypair>,
+}
+
+// The instructions of a run of items, after `prefix`, and everyone signing
+// them once
+pub fn batch_of<'a>(
+    prefix: &[Instruction],
+    items: &[BatchItem<'a>],
+) -> (Vec<Instruction>, Vec<&'a Keypair>) {
+    let mut instructions = prefix.to_vec();
+    let mut signers: Vec<&Keypair> = vec![];
+    for item in items {
+        instructions.extend_from_slice(&item.instructions);
+        for signer in &item.signers {
+            if !signers.iter().any(|s| s.pubkey() == signer.pubkey()) {
+                signers.push(signer);
+            }
+        }
+    }
+    (instructions, signers)
+}
+
+// What the transaction would take on the wire: its signatures behind their
+// one byte length, then the message
+fn transaction_size(payer: &Pubkey, instructions: &[Instruction]) -> usize {
+    let message = Message::new(instructions, Some(payer));
+    1 + usize::from(message.header.num_required_signatures) * 64 + message.serialize().len()
+}
+
+// Split the items, in order, into runs that each fit in one transaction of at
+// most `max_items` of them, every transaction starting with `prefix`. Fails
+// when an item doesn't fit in a transaction even on its own.
+pub fn pack(
+    payer: &Pubkey,
+    prefix: &[Instruction],
+    items: &[BatchItem<'_>],
+    max_items: usize,
+) -> Result<Vec<Range<usize>>> {
+    let mut batches = vec![];
+    let mut start = 0;
+    while start < items.len() {
+        let mut end = start;
+        while end < items.len() && end - start < max_items.max(1) {
+            let (instructions, _) = batch_of(prefix, &items[start..=end]);
+            if transaction_size(payer, &instructions) > PACKET_DATA_SIZE {
+                break;
+            }
+            end += 1;
+        }
+        if end == start {
+            bail!(
+                workload,
+                "Instructions for a single transaction take more than the {} bytes a transaction \
+                 can hold",
+                PACKET_DATA_SIZE
+            );
+        }
+        batches.push(start..end);
+        start = end;
+    }
+    Ok(batches)
+}
+
+// The transaction itself was rejected, so it can't have landed and smaller
+// ones may still go through. Anything else, like a confirmation that timed
+// out, could have landed and mustn't be sent again.
+fn rejected(err: &Error) -> bool {
+    matches!(err, Error::Workload(_))
+        || err
+            .downcast_ref::<ClientError>()
+            .is_some_and(|e| e.get_transaction_error().is_some())
+}
+
+// Send the items in as few transactions as fit, each confirmed before the
+// next is sent. A batch that's rejected, e.g. for running over the compute
+// limit, is split in two and each half sent again, down to single items.
+// Returns each item's signature and when the transaction carrying it was
+// sent.
+pub async fn send_batched(
+    client: &RpcClient,
+    payer: &Keypair,
+    items: &[BatchItem<'_>],
+    send_opts: SendOptions,
+) -> Result<Vec<(Signature, Instant)>> {
+    let mut sent = Vec::with_capacity(items.len());
+    let mut queue: VecDeque<_> = pack(&payer.pubkey(), &[], items, usize::MAX)?.into();
+    while let Some(batch) = queue.pop_front() {
+        let (instructions, signers) = batch_of(&[], &items[batch.clone()]);
+        let sent_at = Instant::now();
+        match send_instructions(client, payer, &signers, &instructions, send_opts).await {
+            Ok(signature) => sent.extend(batch.map(|_| (signature, sent_at))),
+            Err(e) if batch.len() > 1 && rejected(&e) => {
+                warn!(
+                    "Batch of {} item(s) rejected, splitting it: {:#}",
+                    batch.len(),
+                    e
+                );
+                let middle = batch.start + batch.len() / 2;
+                queue.push_front(middle..batch.end);
+                queue.push_front(batch.start..middle);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::system_instruction;
+
+    use super::*;
+
+    fn transfer(payer: &Pubkey) -> BatchItem<'static> {
+        BatchItem {
+            instructions: vec![system_instruction::transfer(
+                payer,
+                &Pubkey::new_unique(),
+                1,
+            )],
+            signers: vec![],
+        }
+    }
+
+    #[test]
+    fn packs_as_many_items_as_fit() {
+        let payer = Pubkey::new_unique();
+        let items: Vec<_> = (0..60).map(|_| transfer(&payer)).collect();
+        let batches = pack(&payer, &[], &items, usize::MAX).unwrap();
+        assert!(batches.len() > 1, "{:?}", batches);
+        assert_eq!(batches.first().unwrap().start, 0);
+        assert_eq!(batches.last().unwrap().end, items.len());
+        for batch in &batches {
+            let (instructions, _) = batch_of(&[], &items[batch.clone()]);
+            assert!(transaction_size(&payer, &instructions) <= PACKET_DATA_SIZE);
+        }
+        // Each batch was only cut short by the next item not fitting
+        let (instructions, _) = batch_of(&[], &items[batches[0].start..=batches[0].end]);
+        assert!(transaction_size(&payer, &instructions) > PACKET_DATA_SIZE);
+
+        let capped = pack(&payer, &[], &items[..5], 2).unwrap();
+        assert_eq!(capped, [0..2, 2..4, 4..5]);
+    }
+
+    #[test]
+    fn fails_on_an_item_too_large_for_a_transaction() {
+        let payer = Pubkey::new_unique();
+        let item = BatchItem {
+            instructions: vec![Instruction::new_with_bytes(
+                Pubkey::new_unique(),
+                &[0; PACKET_DATA_SIZE],
+                vec![],
+            )],
+            signers: vec![],
+        };
+        assert!(pack(&payer, &[], &[item], usize::MAX).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+use std::time::{Duration, Instant};
+
+use futures::future::{join_all, try_join_all};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    nonce_utils::nonblocking::{data_from_account, get_account_with_commitment},
+};
+use solana_sdk::{
+    hash::Hash, nonce::State as NonceState, pubkey::Pubkey, signature::Keypair, signer::Signer,
+    system_instruction, transaction::Transaction,
+};
+use tracing::{info, warn};
+
+use crate::{
+    error::{Context as _, Error, Result},
+    token::{rent_exempt_balance, send_and_confirm, send_instructions, SendOptions},
+    workload::{transfer_instructions, Workload},
+};
+
+#[derive(Clone, Copy)]
+pub struct BenchConfig {
+    pub mints: usize,
+    // Token accounts created for each mint
+    pub accounts: usize,
+    // Transfers packed into each transaction
+    pub batch: usize,
+    // Durable nonce accounts, each with one transaction in flight at a time
+    pub nonces: usize,
+    pub duration: Duration,
+}
+
+// Everything the benchmark sends from, created up front by
+// `Workload::prepare_bench` so the transfers start at full rate
+pub struct Bench {
+    pub(crate) client: RpcClient,
+    pub(crate) payer: Keypair,
+    pub(crate) workload: Workload,
+    pub(crate) config: BenchConfig,
+    // Each mint with its token accounts, all owned by the payer
+    pub(crate) mints: Vec<(Pubkey, Vec<Pubkey>)>,
+    pub(crate) nonces: Vec<Pubkey>,
+}
+
+#[derive(Default)]
+pub struct BenchRun {
+    pub transactions: u64,
+    pub transfers: u64,
+    pub failed: u64,
+    pub elapsed: Duration,
+}
+
+impl BenchRun {
+    pub fn log(&self) {
+        let secs = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        info!(
+            "Bench traffic: {} transfer(s) in {} transaction(s) confirmed in {:.1}s, {} failed",
+            self.transfers, self.transactions, secs, self.failed
+        );
+        info!(
+            "  sustained: {:.1} transfer(s)/s, {:.1} transaction(s)/s",
+            self.transfers as f64 / secs,
+            self.transactions as f64 / secs
+        );
+    }
+}
+
+// Create the nonce accounts the transfers are sent with, authorized to the
+// payer
+pub(crate) async fn create_nonce_accounts(
+    client: &RpcClient,
+    payer: &Keypair,
+    count: usize,
+    send_opts: SendOptions,
+) -> Result<Vec<Pubkey>> {
+    let rent = rent_exempt_balance(client, NonceState::size(), send_opts).await?;
+    let creations = (0..count).map(|_| async move {
+        let nonce = Keypair::new();
+        let instructions = system_instruction::create_nonce_account(
+            &payer.pubkey(),
+            &nonce.pubkey(),
+            &payer.pubkey(),
+            rent,
+        );
+        send_instructions(client, payer, &[&nonce], &instructions, send_opts).await?;
+        Ok::<_, Error>(nonce.pubkey())
+    });
+    try_join_all(creations).await
+}
+
+impl Bench {
+    // Every nonce account sends batches of transfers round one mint's
+    // accounts, the next as soon as the last is confirmed, until the duration
+    // is up or shutdown. A transaction carrying a durable nonce never expires,
+    // so a slow confirmation only holds back its own lane.
+    pub async fn run(&self) -> Result<BenchRun> {
+        info!(
+            "Sending batches of {} transfer(s) from {} nonce account(s) round {} mint(s) for {:?}",
+            self.config.batch,
+            self.nonces.len(),
+            self.mints.len(),
+            self.config.duration
+        );
+        let start = Instant::now();
+        let lanes = self
+            .nonces
+            .iter()
+            .enumerate()
+            .map(|(lane, nonce)| self.run_lane(lane, nonce, start));
+        let mut run = BenchRun::default();
+        for lane in join_all(lanes).await {
+            let lane = lane?;
+            run.transactions += lane.transactions;
+            run.transfers += lane.transfers;
+            run.failed += lane.failed;
+        }
+        run.elapsed = start.elapsed();
+        Ok(run)
+    }
+
+    async fn run_lane(&self, lane: usize, nonce: &Pubkey, start: Instant) -> Result<BenchRun> {
+        let (mint, accounts) = &self.mints[lane % self.mints.len()];
+        let send_opts = self.workload.send_opts();
+        let shutdown = self.workload.shutdown();
+        let mut run = BenchRun::default();
+        let mut blockhash = fetch_nonce(&self.client, nonce, None, send_opts).await?;
+        // Lanes sharing a mint start at different accounts
+        let mut next = lane / self.mints.len() * self.config.batch;
+        while start.elapsed() < self.config.duration && !shutdown.is_cancelled() {
+            let mut instructions = vec![system_instruction::advance_nonce_account(
+                nonce,
+                &self.payer.pubkey(),
+            )];
+            // Untagged, a memo for each transfer would crowd the batch out
+            // of the transaction
+            for _ in 0..self.config.batch {
+                let source = &accounts[next % accounts.len()];
+                let destination = &accounts[(next + 1) % accounts.len()];
+                instructions.extend(transfer_instructions(
+                    &self.workload,
+                    mint,
+                    source,
+                    destination,
+                    &self.payer.pubkey(),
+                    None,
+                )?);
+                next += 1;
+            }
+            let tx = Transaction::new_signed_with_payer(
+                &instructions,
+                Some(&self.payer.pubkey()),
+                &[&self.payer],
+                blockhash,
+            );
+            let sent = tokio::select! {
+                sent = send_and_confirm(&self.client, &tx, send_opts) => sent,
+                _ = shutdown.cancelled() => break,
+            };
+            self.workload.record_sent();
+            match sent {
+                Ok(_) => {
+                    run.transactions += 1;
+                    run.transfers += self.config.batch as u64;
+                }
+                Err(e) => {
+                    run.failed += 1;
+                    warn!("Bench transaction on nonce {} failed: {:#}", nonce, e);
+                }
+            }
+            blockhash = fetch_nonce(&self.client, nonce, Some(blockhash), send_opts).await?;
+        }
+        Ok(run)
+    }
+}
+
+// The nonce account's current blockhash. Given the one just used, waits for
+// the node to have seen it advance, and settles for what's there after the
+// usual read attempts, a failed transaction may not have advanced it.
+async fn fetch_nonce(
+    client: &RpcClient,
+    nonce: &Pubkey,
+    used: Option<Hash>,
+    send_opts: SendOptions,
+) -> Result<Hash> {
+    let mut attempt = 1;
+    loop {
+        let account = get_account_with_commitment(client, nonce, client.commitment())
+            .await
+            .context(Error::Rpc, || {
+                format!("Unable to read nonce account {}", nonce)
+            })?;
+        let blockhash = data_from_account(&account)
+            .context(Error::Rpc, || format!("Invalid nonce account {}", nonce))?
+            .blockhash();
+        if Some(blockhash) != used || attempt >= send_opts.read_attempts {
+            return Ok(blockhash);
+        }
+        tokio::time::sleep(send_opts.confirm_interval).await;
+        attempt += 1;
+    }
+}
@@ -0,0 +1,244 @@
+use std::time::{Duration, Instant};
+
+use color_eyre::{eyre::bail, Result};
+use cpu_time::ThreadTime;
+use futures::{
+    stream::{select_all, SelectAll},
+    StreamExt as _,
+};
+use solana_sdk::pubkey::Pubkey;
+use tracing::{error, info, info_span, warn, Instrument};
+use yellowstone_vixen_proto::{
+    stream::SubscribeRequest,
+    tonic::{codec::Streaming, Status},
+};
+
+use svmkit_vixen_demo::{
+    bench::BenchConfig,
+    subscriber::{DecoderRegistry, ParsedUpdate, VixenSubscriber},
+    workload::Workload,
+};
+
+#[derive(clap::Args)]
+pub struct BenchmarkArgs {
+    /// How long to consume the stream for
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "60s")]
+    duration: Duration,
+
+    /// Window used to measure the peak message rate
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "1s")]
+    window: Duration,
+
+    /// Run the mint / transfer workflow alongside the benchmark to generate traffic
+    #[arg(long)]
+    with_load: bool,
+}
+
+#[derive(clap::Args)]
+pub struct BenchArgs {
+    /// How long to send transfers and consume the stream for
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "60s")]
+    duration: Duration,
+
+    /// Window used to measure the peak message rate
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "1s")]
+    window: Duration,
+
+    /// Mints to create before the transfers start
+    #[arg(long, default_value_t = 4)]
+    mints: usize,
+
+    /// Token accounts to create for each mint
+    #[arg(long, default_value_t = 8)]
+    accounts: usize,
+
+    /// Transfers packed into each transaction
+    #[arg(long, default_value_t = 8)]
+    batch: usize,
+
+    /// Durable nonce accounts to send from, each with one transaction in
+    /// flight at a time
+    #[arg(long, default_value_t = 16)]
+    nonces: usize,
+}
+
+#[derive(Default)]
+struct BenchmarkReport {
+    messages: u64,
+    bytes: u64,
+    undecoded: u64,
+    peak_rate: f64,
+    decode_cpu: Duration,
+    elapsed: Duration,
+}
+
+impl BenchmarkReport {
+    fn log(&self) {
+        let secs = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        let per_message = if self.messages > 0 {
+            Duration::from_secs_f64(self.decode_cpu.as_secs_f64() / self.messages as f64)
+        } else {
+            Duration::ZERO
+        };
+        info!(
+            "Benchmark report: {} message(s) ({} bytes, {} undecoded) in {:.1}s",
+            self.messages, self.bytes, self.undecoded, secs
+        );
+        info!(
+            "  sustained: {:.1} msg/s, peak: {:.1} msg/s",
+            self.messages as f64 / secs,
+            self.peak_rate
+        );
+        info!(
+            "  decode CPU time: {:?} total, {:?} per message",
+            self.decode_cpu, per_message
+        );
+    }
+}
+
+type UpdateStream = SelectAll<Streaming<yellowstone_vixen_proto::stream::SubscribeUpdate>>;
+
+// Subscribe to every program, their updates counting towards the same totals
+async fn subscribe(subscriber: &VixenSubscriber, programs: &[Pubkey]) -> Result<UpdateStream> {
+    let mut client = subscriber.connect().await?;
+    let mut streams = vec![];
+    for program in programs {
+        let req = SubscribeRequest {
+            program: program.to_string(),
+        };
+        streams.push(client.subscribe(req).await?.into_inner());
+    }
+    Ok(select_all(streams))
+}
+
+// Consume the stream as fast as possible, doing nothing but decoding, and
+// report the throughput we were able to sustain
+pub async fn run(
+    args: BenchmarkArgs,
+    subscriber: &VixenSubscriber,
+    programs: &[Pubkey],
+    workload: Workload,
+) -> Result<()> {
+    let mut stream = subscribe(subscriber, programs).await?;
+    info!(
+        "Benchmark subscribed, consuming for {:?} with a {:?} peak window",
+        args.duration, args.window
+    );
+
+    if args.with_load {
+        tokio::spawn(async move {
+            let span = info_span!("Mint Token");
+            // Already subscribed, so there's nothing to wait for
+            let stream_ready = std::future::ready(Ok(()));
+            if let Err(e) = workload.run_configured(stream_ready).instrument(span).await {
+                error!("Error airdropping or minting token: {:#}", e);
+            }
+        });
+    }
+
+    measure(&mut stream, args.duration, args.window)
+        .await?
+        .log();
+    Ok(())
+}
+
+// Create the bench's mints, accounts and nonce accounts, then measure the
+// stream while every nonce account sends batches of transfers as fast as
+// they confirm. The updates from the setup are read and dropped, so only
+// the transfers count.
+pub async fn run_bench(
+    args: BenchArgs,
+    subscriber: &VixenSubscriber,
+    programs: &[Pubkey],
+    workload: Workload,
+) -> Result<()> {
+    let mut stream = subscribe(subscriber, programs).await?;
+    let config = BenchConfig {
+        mints: args.mints,
+        accounts: args.accounts,
+        batch: args.batch,
+        nonces: args.nonces,
+        duration: args.duration,
+    };
+    let prepare = workload.prepare_bench(config);
+    tokio::pin!(prepare);
+    let bench = loop {
+        tokio::select! {
+            bench = &mut prepare => break bench?,
+            update = stream.next() => match update {
+                Some(update) => drop(update?),
+                None => bail!("Stream ended before the bench started"),
+            },
+        }
+    };
+    info!(
+        "Bench set up, consuming for {:?} with a {:?} peak window",
+        args.duration, args.window
+    );
+    let (run, report) = tokio::join!(
+        bench.run().instrument(info_span!("bench")),
+        measure(&mut stream, args.duration, args.window)
+    );
+    report?.log();
+    run?.log();
+    Ok(())
+}
+
+async fn measure(
+    stream: &mut UpdateStream,
+    duration: Duration,
+    window: Duration,
+) -> std::result::Result<BenchmarkReport, Status> {
+    let decoders = DecoderRegistry::default();
+    let mut report = BenchmarkReport::default();
+    let start = Instant::now();
+    let deadline = tokio::time::sleep(duration);
+    tokio::pin!(deadline);
+    let mut window_start = start;
+    let mut window_messages = 0;
+    loop {
+        let update = tokio::select! {
+            update = stream.next() => update.transpose()?,
+            _ = &mut deadline => break,
+        };
+        let Some(update) = update else {
+            warn!("Stream ended before the benchmark finished");
+            break;
+        };
+
+        let now = Instant::now();
+        let window_elapsed = now.duration_since(window_start);
+        if window_elapsed >= window {
+            let rate = window_messages as f64 / window_elapsed.as_secs_f64();
+            report.peak_rate = report.peak_rate.max(rate);
+            window_start = now;
+            window_messages = 0;
+        }
+        window_messages += 1;
+        report.messages += 1;
+
+        let Some(any) = update.parsed else {
+            report.undecoded += 1;
+            continue;
+        };
+        report.bytes += any.value.len() as u64;
+        let cpu_start = ThreadTime::now();
+        let decoded = matches!(
+            decoders.decode(&any),
+            Ok(ParsedUpdate::Instruction(_)
+                | ParsedUpdate::State(_)
+                | ParsedUpdate::LegacyInstruction(_)
+                | ParsedUpdate::LegacyState(_))
+        );
+        report.decode_cpu += cpu_start.elapsed();
+        if !decoded {
+            report.undecoded += 1;
+        }
+    }
+    report.elapsed = start.elapsed();
+    // Without a single complete window the sustained rate is the best estimate
+    if report.peak_rate == 0.0 {
+        report.peak_rate = report.messages as f64 / report.elapsed.as_secs_f64();
+    }
+    Ok(report)
+}
@@ -0,0 +1,210 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use metrics::{counter, histogram};
+use rand::Rng as _;
+use solana_client::{
+    client_error::ClientErrorKind, nonblocking::rpc_client::RpcClient, rpc_request::RpcError,
+};
+use solana_sdk::{signature::Signature, transaction::Transaction};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use yellowstone_vixen_proto::stream::SubscribeRequest;
+
+use crate::subscriber::VixenSubscriber;
+
+// Not a base58 pubkey, so no server can make sense of it
+const MALFORMED_PROGRAM: &str = "chaos-not-a-program";
+
+// How long a malformed subscription may go unanswered before it counts as
+// accepted
+const MALFORMED_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The faults --chaos injects, to show the demo recovering from them
+#[derive(Clone, Copy, Debug)]
+pub struct ChaosOptions {
+    // Chance of each transaction being delayed, and of it being sent twice
+    pub rate: f64,
+    pub max_delay: Duration,
+    // How often the stream is dropped and a malformed subscription sent
+    pub interval: Duration,
+}
+
+impl Default for ChaosOptions {
+    fn default() -> Self {
+        Self {
+            rate: 0.2,
+            max_delay: Duration::from_secs(2),
+            interval: Duration::from_secs(30),
+        }
+    }
+}
+
+// Every fault injected so far, shared by all sends since SendOptions is
+// copied into each of them
+static DELAYED: AtomicU64 = AtomicU64::new(0);
+static RESENT: AtomicU64 = AtomicU64::new(0);
+static RESENDS_REJECTED: AtomicU64 = AtomicU64::new(0);
+
+impl ChaosOptions {
+    // Before a transaction is sent, maybe hold it back
+    pub async fn delay(&self) {
+        let delay = {
+            let mut rng = rand::thread_rng();
+            rng.gen_bool(self.rate)
+                .then(|| self.max_delay.mul_f64(rng.gen_range(0.0..=1.0)))
+        };
+        if let Some(delay) = delay {
+            DELAYED.fetch_add(1, Ordering::Relaxed);
+            counter!("vixen_chaos_faults_total", "fault" => "delay").increment(1);
+            info!("Chaos: delaying a transaction by {:?}", delay);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    // Once a transaction went out, maybe send it again, which the node should
+    // either dedupe or refuse as already processed
+    pub async fn resend(&self, client: &RpcClient, tx: &Transaction, signature: &Signature) {
+        if !rand::thread_rng().gen_bool(self.rate) {
+            return;
+        }
+        RESENT.fetch_add(1, Ordering::Relaxed);
+        counter!("vixen_chaos_faults_total", "fault" => "duplicate").increment(1);
+        match client.send_transaction(tx).await {
+            Ok(_) => info!("Chaos: sent transaction {} a second time", signature),
+            Err(e) => {
+                if is_already_processed(e.kind()) {
+                    RESENDS_REJECTED.fetch_add(1, Ordering::Relaxed);
+                }
+                info!(
+                    "Chaos: sent transaction {} a second time, the node refused it: {}",
+                    signature, e
+                );
+            }
+        }
+    }
+}
+
+fn is_already_processed(kind: &ClientErrorKind) -> bool {
+    match kind {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { message, .. }) => {
+            message.contains("already been processed")
+        }
+        _ => kind.to_string().contains("AlreadyProcessed"),
+    }
+}
+
+// How long each dropped subscription took to connect again. Clones share it,
+// one goes to each subscription.
+#[derive(Clone, Default)]
+pub struct Recoveries(Arc<Mutex<RecoveryState>>);
+
+#[derive(Default)]
+struct RecoveryState {
+    drops: u64,
+    recovered: Vec<Duration>,
+}
+
+impl Recoveries {
+    pub fn dropped(&self) {
+        counter!("vixen_chaos_faults_total", "fault" => "disconnect").increment(1);
+        self.0.lock().unwrap().drops += 1;
+    }
+
+    pub fn recovered(&self, after: Duration) {
+        histogram!("vixen_chaos_recovery_seconds").record(after);
+        self.0.lock().unwrap().recovered.push(after);
+    }
+}
+
+// Every interval until shutdown, subscribe with a program that isn't one and
+// check the server turns it down, while the real subscriptions carry on
+pub async fn malformed_subscriptions(
+    subscriber: VixenSubscriber,
+    interval: Duration,
+    shutdown: CancellationToken,
+) -> MalformedReport {
+    let mut report = MalformedReport::default();
+    let mut ticks = tokio::time::interval(interval);
+    ticks.tick().await;
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            _ = shutdown.cancelled() => return report,
+        }
+        report.sent += 1;
+        counter!("vixen_chaos_faults_total", "fault" => "malformed_subscription").increment(1);
+        let attempt = async {
+            let mut client = subscriber.connect().await.map_err(|e| e.to_string())?;
+            let request = SubscribeRequest {
+                program: MALFORMED_PROGRAM.to_string(),
+            };
+            let mut stream = client
+                .subscribe(request)
+                .await
+                .map_err(|status| status.message().to_string())?
+                .into_inner();
+            // Some servers only refuse it once the stream starts
+            stream
+                .message()
+                .await
+                .map_err(|status| status.message().to_string())
+        };
+        match tokio::time::timeout(MALFORMED_TIMEOUT, attempt).await {
+            Ok(Err(reason)) => {
+                report.rejected += 1;
+                info!("Chaos: malformed subscription rejected: {}", reason);
+            }
+            _ => warn!(
+                "Chaos: the server accepted a subscription to {:?}",
+                MALFORMED_PROGRAM
+            ),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MalformedReport {
+    pub sent: u64,
+    pub rejected: u64,
+}
+
+// Logs how the demo came through the faults injected into it, the stream's
+// only when it ran
+pub fn log_report(stream: Option<(&Recoveries, &MalformedReport)>) {
+    info!(
+        "Chaos: delayed {} transaction(s) and sent {} twice, {} of the duplicates refused as already processed",
+        DELAYED.load(Ordering::Relaxed),
+        RESENT.load(Ordering::Relaxed),
+        RESENDS_REJECTED.load(Ordering::Relaxed)
+    );
+    let Some((recoveries, malformed)) = stream else {
+        return;
+    };
+    let state = recoveries.0.lock().unwrap();
+    let mut recovered = state.recovered.clone();
+    recovered.sort();
+    match (recovered.get(recovered.len() / 2), recovered.last()) {
+        (Some(median), Some(max)) => info!(
+            "Chaos: dropped the stream {} time(s), it reconnected {} time(s) after a median {:?} and at most {:?}",
+            state.drops,
+            recovered.len(),
+            median,
+            max
+        ),
+        _ if state.drops > 0 => warn!(
+            "Chaos: dropped the stream {} time(s), it never reconnected",
+            state.drops
+        ),
+        _ => info!("Chaos: the stream was never dropped"),
+    }
+    info!(
+        "Chaos: sent {} malformed subscription(s), {} rejected by the server",
+        malformed.sent, malformed.rejected
+    );
+}
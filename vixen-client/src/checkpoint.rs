@@ -0,0 +1,203 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    io::{ErrorKind, Write as _},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use yellowstone_vixen_proto::{parser::TokenAccountProto, prost::Message};
+
+// Bumped when the file's layout changes
+const CHECKPOINT_VERSION: u32 = 1;
+
+// The last state of every token account the log has pinned to its account,
+// shared by the log's AccountStates and the Checkpointer saving it
+pub type TrackedAccounts = Arc<Mutex<HashMap<Pubkey, TokenAccountProto>>>;
+
+// What survives a restart, so an account's first update after one is still
+// logged as what changed rather than in full. The stream carries no slot, so
+// there's no position in it to keep.
+#[derive(Default)]
+pub struct Checkpoint {
+    pub accounts: HashMap<Pubkey, TokenAccountProto>,
+}
+
+// Where the checkpoint is kept, for other backends to plug in. A save
+// replaces the last one whole: interrupted, it leaves the previous one.
+pub trait CheckpointStore: Send + Sync {
+    // An empty checkpoint when nothing has been saved yet
+    fn load(&self) -> Result<Checkpoint>;
+
+    fn save(&self, checkpoint: &Checkpoint) -> Result<()>;
+}
+
+// A JSON file, written next to itself and renamed over the last one
+pub struct FileCheckpointStore {
+    path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CheckpointFile {
+    version: u32,
+    // Each account's last state, base64 of its protobuf encoding
+    accounts: BTreeMap<String, String>,
+}
+
+impl FileCheckpointStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn load(&self) -> Result<Checkpoint> {
+        let json = match fs::read(&self.path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Checkpoint::default()),
+            Err(e) => {
+                return Err(e)
+                    .wrap_err_with(|| format!("Unable to read checkpoint {}", self.path.display()))
+            }
+        };
+        let file: CheckpointFile = serde_json::from_slice(&json)
+            .wrap_err_with(|| format!("Invalid checkpoint {}", self.path.display()))?;
+        if file.version != CHECKPOINT_VERSION {
+            bail!(
+                "Checkpoint {} is version {}, this client reads version {}",
+                self.path.display(),
+                file.version,
+                CHECKPOINT_VERSION
+            );
+        }
+        let mut accounts = HashMap::with_capacity(file.accounts.len());
+        for (pubkey, state) in file.accounts {
+            let state = BASE64
+                .decode(state)
+                .ok()
+                .and_then(|bytes| TokenAccountProto::decode(bytes.as_slice()).ok());
+            match (pubkey.parse(), state) {
+                (Ok(pubkey), Some(state)) => {
+                    accounts.insert(pubkey, state);
+                }
+                _ => bail!(
+                    "Invalid account {} in checkpoint {}",
+                    pubkey,
+                    self.path.display()
+                ),
+            }
+        }
+        Ok(Checkpoint { accounts })
+    }
+
+    fn save(&self, checkpoint: &Checkpoint) -> Result<()> {
+        let file = CheckpointFile {
+            version: CHECKPOINT_VERSION,
+            accounts: checkpoint
+                .accounts
+                .iter()
+                .map(|(pubkey, state)| (pubkey.to_string(), BASE64.encode(state.encode_to_vec())))
+                .collect(),
+        };
+        let mut partial = self.path.clone().into_os_string();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let res = (|| {
+            let mut out = File::create(&partial)?;
+            out.write_all(&serde_json::to_vec(&file)?)?;
+            out.sync_all()?;
+            fs::rename(&partial, &self.path)
+        })();
+        res.wrap_err_with(|| format!("Unable to write checkpoint {}", self.path.display()))
+    }
+}
+
+// Saves the tracked accounts every interval and once more at shutdown
+pub struct Checkpointer {
+    store: Box<dyn CheckpointStore>,
+    accounts: TrackedAccounts,
+    interval: Duration,
+}
+
+impl Checkpointer {
+    // With the accounts the store last saved, for the log to diff against
+    pub fn restore(store: Box<dyn CheckpointStore>, interval: Duration) -> Result<Self> {
+        let checkpoint = store.load()?;
+        if !checkpoint.accounts.is_empty() {
+            info!(
+                "Restored {} token account(s) from the checkpoint",
+                checkpoint.accounts.len()
+            );
+        }
+        Ok(Self {
+            store,
+            accounts: Arc::new(Mutex::new(checkpoint.accounts)),
+            interval,
+        })
+    }
+
+    pub fn accounts(&self) -> TrackedAccounts {
+        self.accounts.clone()
+    }
+
+    pub async fn run(self, shutdown: CancellationToken) {
+        let mut ticks = tokio::time::interval(self.interval);
+        ticks.tick().await;
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => self.save(),
+                _ = shutdown.cancelled() => break,
+            }
+        }
+        self.save();
+    }
+
+    fn save(&self) {
+        let checkpoint = Checkpoint {
+            accounts: self.accounts.lock().unwrap().clone(),
+        };
+        if let Err(e) = self.store.save(&checkpoint) {
+            warn!("Unable to save the checkpoint: {:#}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_back_what_was_saved() {
+        let path = std::env::temp_dir().join(format!("vixen-checkpoint-{}", std::process::id()));
+        let store = FileCheckpointStore::new(path.clone());
+        assert!(store.load().unwrap().accounts.is_empty());
+
+        let pubkey = Pubkey::new_unique();
+        let state = TokenAccountProto {
+            mint: Pubkey::new_unique().to_string(),
+            owner: Pubkey::new_unique().to_string(),
+            amount: 42,
+            ..TokenAccountProto::default()
+        };
+        let checkpoint = Checkpoint {
+            accounts: HashMap::from([(pubkey, state.clone())]),
+        };
+        store.save(&checkpoint).unwrap();
+        // Saved again over the first
+        store.save(&checkpoint).unwrap();
+        let loaded = store.load().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.accounts.len(), 1);
+        assert_eq!(loaded.accounts[&pubkey], state);
+    }
+}
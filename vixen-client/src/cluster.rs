@@ -0,0 +1,50 @@
+use std::fmt;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+
+use crate::error::Result;
+
+const MAINNET_GENESIS_HASH: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
+const DEVNET_GENESIS_HASH: &str = "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG";
+const TESTNET_GENESIS_HASH: &str = "4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cluster {
+    Mainnet,
+    Devnet,
+    Testnet,
+    Unknown,
+}
+
+impl Cluster {
+    pub fn from_genesis_hash(hash: &str) -> Self {
+        match hash {
+            MAINNET_GENESIS_HASH => Cluster::Mainnet,
+            DEVNET_GENESIS_HASH => Cluster::Devnet,
+            TESTNET_GENESIS_HASH => Cluster::Testnet,
+            _ => Cluster::Unknown,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Cluster::Mainnet => "mainnet",
+            Cluster::Devnet => "devnet",
+            Cluster::Testnet => "testnet",
+            Cluster::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Detect which cluster the RPC node belongs to. Nothing is cached here: the
+// binary detects it once and hands it to the workload in `SendOptions`.
+pub async fn detect_cluster(rpc_client: &RpcClient) -> Result<Cluster> {
+    let genesis_hash = rpc_client.get_genesis_hash().await?;
+    Ok(Cluster::from_genesis_hash(&genesis_hash.to_string()))
+}
@@ -0,0 +1,91 @@
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use serde::{de::Error as _, Deserialize, Deserializer};
+use solana_sdk::pubkey::Pubkey;
+use svmkit_vixen_demo::{amount::UiAmount, program::parse_program};
+
+// Demo parameters loaded with `--config <path>`, or from vixen-client.toml in
+// the working directory when that exists, a TOML file such as
+//
+//     grpc-url = "http://localhost:9000"
+//     rpc-url = "http://localhost:8899"
+//     program = ["token-2022", "spl-token"]
+//     decimals = 6
+//     mint-amount = "10000"
+//     transfer-amount = "1000"
+//     burn-amount = "100"
+//     airdrop-amount = "2"
+//
+// Every key is optional and named after its command line flag. Each value is
+// taken from the flag, or its environment variable, when given, otherwise from
+// this file, otherwise from the built-in default. `program` takes one program
+// or a list, like repeating --program. Amounts are strings so "1.5" isn't
+// rounded through a float.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub grpc_url: Option<String>,
+    pub rpc_url: Option<String>,
+    #[serde(deserialize_with = "programs")]
+    pub program: Vec<Pubkey>,
+    pub decimals: Option<u8>,
+    #[serde(deserialize_with = "parsed")]
+    pub mint_amount: Option<UiAmount>,
+    #[serde(deserialize_with = "parsed")]
+    pub transfer_amount: Option<UiAmount>,
+    #[serde(deserialize_with = "parsed")]
+    pub burn_amount: Option<UiAmount>,
+    // In SOL
+    #[serde(deserialize_with = "parsed")]
+    pub airdrop_amount: Option<UiAmount>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Unable to read config file {}", path.display()))?;
+        let config: Self = toml::from_str(&text)
+            .wrap_err_with(|| format!("Invalid config file {}", path.display()))?;
+        if let Some(decimals) = config.decimals.filter(|&d| d > 9) {
+            bail!(
+                "Invalid config file {}: decimals is {}, at most 9 are supported",
+                path.display(),
+                decimals
+            );
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn programs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Pubkey>, D::Error> {
+    let programs = match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(program) => vec![program],
+        OneOrMany::Many(programs) => programs,
+    };
+    programs
+        .iter()
+        .map(|arg| parse_program(arg).map_err(D::Error::custom))
+        .collect()
+}
+
+fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(D::Error::custom))
+        .transpose()
+}
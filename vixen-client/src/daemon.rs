@@ -0,0 +1,45 @@
+use std::ffi::OsString;
+
+use clap::{ArgAction, CommandFactory as _};
+use color_eyre::{eyre::bail, Result};
+
+use crate::Opts;
+
+// Listened on for /healthz when VIXEN_HTTP_ADDR doesn't say otherwise
+pub const DEFAULT_HTTP_ADDR: &str = "0.0.0.0:8080";
+
+// In a compose file the environment is the one place to configure the client
+// from, so with --daemon every flag is read from VIXEN_ and the flag's name,
+// e.g. VIXEN_GRPC_URL or VIXEN_RAW_ACCOUNT_FALLBACK=true, or from the
+// variable it already has, like SOLANA_RPC_URL. Builds those into a command
+// line for Opts to parse as usual, so they're validated like the flags.
+pub fn args_from_env() -> Result<Vec<OsString>> {
+    if std::env::args_os().skip(1).any(|arg| arg != "--daemon") {
+        bail!(
+            "--daemon takes its configuration from the environment only, as VIXEN_<FLAG> variables"
+        );
+    }
+    let mut args = vec![OsString::from(env!("CARGO_PKG_NAME")), "--daemon".into()];
+    for arg in Opts::command().get_arguments() {
+        // Those with a variable of their own are read by clap itself
+        let Some(long) = arg.get_long().filter(|_| arg.get_env().is_none()) else {
+            continue;
+        };
+        let var = format!("VIXEN_{}", long.replace('-', "_").to_uppercase());
+        let Some(value) = std::env::var_os(&var) else {
+            continue;
+        };
+        if matches!(arg.get_action(), ArgAction::SetTrue) {
+            match value.to_str().map(str::to_lowercase).as_deref() {
+                Some("1" | "true" | "yes" | "on") => args.push(format!("--{}", long).into()),
+                Some("" | "0" | "false" | "no" | "off") => {}
+                _ => bail!("{} is a switch, set it to true or false", var),
+            }
+            continue;
+        }
+        let mut flag = OsString::from(format!("--{}=", long));
+        flag.push(value);
+        args.push(flag);
+    }
+    Ok(args)
+}
@@ -0,0 +1,74 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use color_eyre::Result;
+use serde_json::json;
+use tracing::warn;
+use yellowstone_vixen_proto::prost_types::Any;
+
+// Payloads that failed to decode as the type they claim, each written as raw
+// bytes for e.g. `protoc --decode_raw`, with a line in `index.ndjson` giving
+// its type URL and the decode error
+pub struct DeadLetters {
+    dir: PathBuf,
+    index: File,
+    written: u64,
+}
+
+impl DeadLetters {
+    pub fn new(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("index.ndjson"))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            index,
+            written: 0,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    // Failing to write one is logged rather than ending the stream
+    pub fn write(&mut self, any: &Any, error: &str) {
+        if let Err(e) = self.try_write(any, error) {
+            warn!(
+                "Unable to write dead letter to {}: {}",
+                self.dir.display(),
+                e
+            );
+        }
+    }
+
+    fn try_write(&mut self, any: &Any, error: &str) -> Result<()> {
+        let received_at = SystemTime::now();
+        let millis = received_at
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_millis();
+        let type_name = any.type_url.rsplit('/').next().unwrap_or_default();
+        let file = format!("{}-{}-{}.bin", millis, self.written, type_name);
+        fs::write(self.dir.join(&file), &any.value)?;
+        let entry = json!({
+            "received_at": humantime::format_rfc3339_millis(received_at).to_string(),
+            "type_url": any.type_url,
+            "error": error,
+            "file": file,
+            "bytes": any.value.len(),
+        });
+        writeln!(self.index, "{}", entry)?;
+        self.written += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt::{self, Display};
+
+use solana_client::client_error::ClientError;
+use solana_sdk::{program_error::ProgramError, signer::SignerError};
+use thiserror::Error;
+use yellowstone_vixen_proto::prost::DecodeError;
+
+use crate::subscriber::SubscriptionFailure;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+pub type Source = Box<dyn std::error::Error + Send + Sync>;
+
+// What went wrong, by kind, so a caller can retry a transport error and give
+// up on a config error without picking the message apart. Each one displays
+// as the error it wraps, which is either the underlying error itself or a
+// message of the library's own with that error as its source.
+#[derive(Debug, Error)]
+pub enum Error {
+    // Connecting to the Vixen server, subscribing, or the stream failing
+    #[error(transparent)]
+    Transport(Source),
+    // A payload, proto or record that couldn't be decoded or encoded
+    #[error(transparent)]
+    Decode(Source),
+    // A validator RPC call that failed, or came back other than expected
+    #[error(transparent)]
+    Rpc(Source),
+    // A workload transaction that couldn't be built or didn't do what it
+    // should have, or a workload stopped by shutdown
+    #[error(transparent)]
+    Workload(Source),
+    // Bad input: a scenario, wallet, keypair file or amount
+    #[error(transparent)]
+    Config(Source),
+}
+
+// A message of the library's own, with what led to it when there's more.
+// `{:#}` follows it with the whole chain of sources, the way eyre does.
+#[derive(Debug, Error)]
+struct Message {
+    message: String,
+    #[source]
+    source: Option<Source>,
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if f.alternate() {
+            let mut source = self.source.as_deref().map(|e| e as &dyn std::error::Error);
+            while let Some(e) = source {
+                write!(f, ": {}", e)?;
+                source = e.source();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn message(message: impl Display) -> Source {
+    Box::new(Message {
+        message: message.to_string(),
+        source: None,
+    })
+}
+
+impl Error {
+    pub fn transport(msg: impl Display) -> Self {
+        Error::Transport(message(msg))
+    }
+
+    pub fn decode(msg: impl Display) -> Self {
+        Error::Decode(message(msg))
+    }
+
+    pub fn rpc(msg: impl Display) -> Self {
+        Error::Rpc(message(msg))
+    }
+
+    pub fn workload(msg: impl Display) -> Self {
+        Error::Workload(message(msg))
+    }
+
+    pub fn config(msg: impl Display) -> Self {
+        Error::Config(message(msg))
+    }
+
+    // The error this one wraps, when it's an `E`, e.g. the ClientError
+    // behind an Rpc error
+    pub fn downcast_ref<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        let (Error::Transport(inner)
+        | Error::Decode(inner)
+        | Error::Rpc(inner)
+        | Error::Workload(inner)
+        | Error::Config(inner)) = self;
+        inner.downcast_ref()
+    }
+
+    // Whether trying again could help, as opposed to an error that will
+    // keep happening until something is changed
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Error::Transport(_) | Error::Rpc(_))
+    }
+}
+
+// Wraps an error in a message saying what was being done, keeping the error
+// as its source, e.g. `.context(Error::Config, || format!("Invalid wallet
+// {}", path.display()))`. A library error keeps its kind whichever is asked
+// for, an error from elsewhere takes the one given.
+pub trait Context<T> {
+    fn context<M: Display>(
+        self,
+        kind: fn(Source) -> Error,
+        message: impl FnOnce() -> M,
+    ) -> Result<T>;
+}
+
+impl<T, E: Into<Source>> Context<T> for Result<T, E> {
+    fn context<M: Display>(
+        self,
+        kind: fn(Source) -> Error,
+        message: impl FnOnce() -> M,
+    ) -> Result<T> {
+        self.map_err(|source| {
+            let source = source.into();
+            let kind = match source.downcast_ref::<Error>() {
+                Some(Error::Transport(_)) => Error::Transport,
+                Some(Error::Decode(_)) => Error::Decode,
+                Some(Error::Rpc(_)) => Error::Rpc,
+                Some(Error::Workload(_)) => Error::Workload,
+                Some(Error::Config(_)) => Error::Config,
+                None => kind,
+            };
+            kind(Box::new(Message {
+                message: message().to_string(),
+                source: Some(source),
+            }))
+        })
+    }
+}
+
+// Return early with a library error of the given kind, e.g.
+// `bail!(config, "repeat must be at least 1")`
+macro_rules! bail {
+    ($kind:ident, $($arg:tt)+) => {
+        return Err($crate::error::Error::$kind(format!($($arg)+)))
+    };
+}
+pub(crate) use bail;
+
+impl From<ClientError> for Error {
+    fn from(e: ClientError) -> Self {
+        Error::Rpc(Box::new(e))
+    }
+}
+
+impl From<SubscriptionFailure> for Error {
+    fn from(e: SubscriptionFailure) -> Self {
+        Error::Transport(Box::new(e))
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        Error::Decode(Box::new(e))
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Decode(Box::new(e))
+    }
+}
+
+// From the token program's instruction builders
+impl From<ProgramError> for Error {
+    fn from(e: ProgramError) -> Self {
+        Error::Workload(Box::new(e))
+    }
+}
+
+impl From<SignerError> for Error {
+    fn from(e: SignerError) -> Self {
+        Error::Workload(Box::new(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_keeps_the_kind_and_the_chain() {
+        let io: std::result::Result<(), _> = Err(std::io::Error::other("disk full"));
+        let err = io
+            .context(Error::Config, || "Unable to write wallet w.json")
+            .unwrap_err();
+        assert!(matches!(err, Error::Config(_)));
+        assert_eq!(err.to_string(), "Unable to write wallet w.json");
+        assert_eq!(
+            format!("{:#}", err),
+            "Unable to write wallet w.json: disk full"
+        );
+
+        let rpc: Result<()> = Err(Error::rpc("Account not found"));
+        let err = rpc
+            .context(Error::Workload, || "Transaction failed after 3 attempt(s)")
+            .unwrap_err();
+        assert!(err.is_transient());
+        assert_eq!(
+            format!("{:#}", err),
+            "Transaction failed after 3 attempt(s): Account not found"
+        );
+    }
+}
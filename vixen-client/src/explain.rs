@@ -0,0 +1,71 @@
+use solana_sdk::{
+    program_utils::limited_deserialize, pubkey::Pubkey, system_instruction::SystemInstruction,
+    system_program, transaction::Transaction,
+};
+use spl_token_2022::instruction::TokenInstruction;
+use tracing::info;
+
+use crate::program::{is_token_program, program_name};
+
+// Log a human-readable breakdown of every instruction in the transaction
+pub fn explain_transaction(tx: &Transaction) {
+    let message = &tx.message;
+    info!(
+        "Transaction with {} instruction(s), {} signature(s) required",
+        message.instructions.len(),
+        message.header.num_required_signatures
+    );
+    for (i, ix) in message.instructions.iter().enumerate() {
+        let program_id = message.account_keys[ix.program_id_index as usize];
+        info!(
+            "  #{} {} ({}): {}",
+            i,
+            program_name(&program_id),
+            program_id,
+            instruction_name(&program_id, &ix.data)
+        );
+        for &account_index in &ix.accounts {
+            let index = account_index as usize;
+            info!(
+                "      {} [{}]",
+                message.account_keys[index],
+                account_roles(
+                    message.is_signer(index),
+                    message.is_maybe_writable(index, None)
+                )
+            );
+        }
+    }
+}
+
+fn instruction_name(program_id: &Pubkey, data: &[u8]) -> String {
+    let debug = if *program_id == system_program::id() {
+        limited_deserialize::<SystemInstruction>(data)
+            .ok()
+            .map(|ix| format!("{:?}", ix))
+    } else if is_token_program(program_id) {
+        TokenInstruction::unpack(data)
+            .ok()
+            .map(|ix| format!("{:?}", ix))
+    } else {
+        None
+    };
+    // Only keep the variant name, the accounts listing already covers the interesting bits
+    match debug {
+        Some(debug) => debug
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        None => format!("<{} bytes of instruction data>", data.len()),
+    }
+}
+
+fn account_roles(is_signer: bool, is_writable: bool) -> &'static str {
+    match (is_signer, is_writable) {
+        (true, true) => "signer, writable",
+        (true, false) => "signer",
+        (false, true) => "writable",
+        (false, false) => "readonly",
+    }
+}
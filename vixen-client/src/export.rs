@@ -0,0 +1,550 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use color_eyre::{eyre::bail, Result};
+use solana_sdk::pubkey::Pubkey;
+use tracing::{info, warn};
+use yellowstone_vixen_proto::parser::token_program_ix_proto::IxOneof as TokenIx;
+
+use svmkit_vixen_demo::subscriber::ParsedUpdate;
+
+use crate::sink::MessageHandler;
+
+// How often the current file is checked against --export-rotate-after when
+// no transfers arrive
+const ROTATE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// Which kind of file --export-transfers writes
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row
+    Csv,
+    /// Apache Parquet, needs the `parquet` cargo feature
+    Parquet,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+const COLUMNS: [&str; 9] = [
+    "received_at",
+    "program",
+    "source",
+    "destination",
+    "mint",
+    "amount",
+    "decimals",
+    "slot",
+    "signature",
+];
+
+// A transfer_checked instruction from either token program. The stream carries
+// no slot or signature, so those columns stay empty until it does.
+struct TransferRow {
+    received_at: SystemTime,
+    program: String,
+    source: String,
+    destination: String,
+    mint: String,
+    amount: u64,
+    decimals: u8,
+    slot: Option<u64>,
+    signature: Option<String>,
+}
+
+impl TransferRow {
+    fn from_update(program: &Pubkey, update: &ParsedUpdate) -> Option<Self> {
+        let Some(TokenIx::TransferChecked(transfer)) = update.token_instruction() else {
+            return None;
+        };
+        let accounts = transfer.accounts.as_ref()?;
+        let data = transfer.data.as_ref()?;
+        Some(Self {
+            received_at: SystemTime::now(),
+            program: program.to_string(),
+            source: accounts.source.clone(),
+            destination: accounts.destination.clone(),
+            mint: accounts.mint.clone(),
+            amount: data.amount,
+            decimals: u8::try_from(data.decimals).ok()?,
+            slot: None,
+            signature: None,
+        })
+    }
+}
+
+// One open export file, renamed from its `.partial` name once finished so a
+// reader never picks up a file that's still being written
+trait TransferFile: Send {
+    fn write(&mut self, row: TransferRow) -> Result<()>;
+
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+// Every value is an address, a number or a timestamp, so nothing needs quoting
+struct CsvFile(BufWriter<File>);
+
+impl CsvFile {
+    fn create(file: File) -> Result<Self> {
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{}", COLUMNS.join(","))?;
+        Ok(Self(writer))
+    }
+}
+
+impl TransferFile for CsvFile {
+    fn write(&mut self, row: TransferRow) -> Result<()> {
+        writeln!(
+            self.0,
+            "{},{},{},{},{},{},{},{},{}",
+            humantime::format_rfc3339_millis(row.received_at),
+            row.program,
+            row.source,
+            row.destination,
+            row.mint,
+            row.amount,
+            row.decimals,
+            row.slot.map(|slot| slot.to_string()).unwrap_or_default(),
+            row.signature.unwrap_or_default()
+        )?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.0.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_file {
+    use std::{fs::File, sync::Arc, time::UNIX_EPOCH};
+
+    use color_eyre::Result;
+    use parquet::{
+        basic::Compression,
+        data_type::{ByteArray, ByteArrayType, Int32Type, Int64Type},
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    };
+
+    use super::{TransferFile, TransferRow};
+
+    const SCHEMA: &str = "message transfer {
+        REQUIRED INT64 received_at (TIMESTAMP(MILLIS, true));
+        REQUIRED BYTE_ARRAY program (STRING);
+        REQUIRED BYTE_ARRAY source (STRING);
+        REQUIRED BYTE_ARRAY destination (STRING);
+        REQUIRED BYTE_ARRAY mint (STRING);
+        REQUIRED INT64 amount (INTEGER(64, false));
+        REQUIRED INT32 decimals (INTEGER(8, false));
+        OPTIONAL INT64 slot (INTEGER(64, false));
+        OPTIONAL BYTE_ARRAY signature (STRING);
+    }";
+
+    // Rows are buffered and written a row group at a time, the file is only
+    // readable once its footer goes out in finish()
+    const ROW_GROUP_SIZE: usize = 10_000;
+
+    pub struct ParquetFile {
+        writer: SerializedFileWriter<File>,
+        rows: Vec<TransferRow>,
+    }
+
+    impl ParquetFile {
+        pub fn create(file: File) -> Result<Self> {
+            let properties = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            let writer = SerializedFileWriter::new(
+                file,
+                Arc::new(parse_message_type(SCHEMA)?),
+                Arc::new(properties),
+            )?;
+            Ok(Self {
+                writer,
+                rows: Vec::with_capacity(ROW_GROUP_SIZE),
+            })
+        }
+
+        fn write_row_group(&mut self) -> Result<()> {
+            if self.rows.is_empty() {
+                return Ok(());
+            }
+            let rows = std::mem::take(&mut self.rows);
+            let strings = |column: fn(&TransferRow) -> &str| -> Vec<ByteArray> {
+                rows.iter().map(|row| column(row).into()).collect()
+            };
+            let mut group = self.writer.next_row_group()?;
+            let mut index = 0;
+            while let Some(mut column) = group.next_column()? {
+                match index {
+                    0 => {
+                        let millis: Vec<i64> = rows
+                            .iter()
+                            .map(|row| {
+                                let since = row.received_at.duration_since(UNIX_EPOCH);
+                                since.map_or(0, |since| since.as_millis() as i64)
+                            })
+                            .collect();
+                        column
+                            .typed::<Int64Type>()
+                            .write_batch(&millis, None, None)?;
+                    }
+                    1..=4 => {
+                        let values = match index {
+                            1 => strings(|row| &row.program),
+                            2 => strings(|row| &row.source),
+                            3 => strings(|row| &row.destination),
+                            _ => strings(|row| &row.mint),
+                        };
+                        column
+                            .typed::<ByteArrayType>()
+                            .write_batch(&values, None, None)?;
+                    }
+                    // Parquet keeps unsigned integers in the signed physical
+                    // type, the logical type says how to read them back
+                    5 => {
+                        let amounts: Vec<i64> = rows.iter().map(|row| row.amount as i64).collect();
+                        column
+                            .typed::<Int64Type>()
+                            .write_batch(&amounts, None, None)?;
+                    }
+                    6 => {
+                        let decimals: Vec<i32> =
+                            rows.iter().map(|row| row.decimals.into()).collect();
+                        column
+                            .typed::<Int32Type>()
+                            .write_batch(&decimals, None, None)?;
+                    }
+                    7 => {
+                        let levels: Vec<i16> =
+                            rows.iter().map(|row| row.slot.is_some().into()).collect();
+                        let slots: Vec<i64> = rows
+                            .iter()
+                            .filter_map(|row| row.slot)
+                            .map(|slot| slot as i64)
+                            .collect();
+                        column
+                            .typed::<Int64Type>()
+                            .write_batch(&slots, Some(&levels), None)?;
+                    }
+                    _ => {
+                        let levels: Vec<i16> = rows
+                            .iter()
+                            .map(|row| row.signature.is_some().into())
+                            .collect();
+                        let signatures: Vec<ByteArray> = rows
+                            .iter()
+                            .filter_map(|row| row.signature.as_deref())
+                            .map(ByteArray::from)
+                            .collect();
+                        column.typed::<ByteArrayType>().write_batch(
+                            &signatures,
+                            Some(&levels),
+                            None,
+                        )?;
+                    }
+                }
+                column.close()?;
+                index += 1;
+            }
+            group.close()?;
+            Ok(())
+        }
+    }
+
+    impl TransferFile for ParquetFile {
+        fn write(&mut self, row: TransferRow) -> Result<()> {
+            self.rows.push(row);
+            if self.rows.len() >= ROW_GROUP_SIZE {
+                self.write_row_group()?;
+            }
+            Ok(())
+        }
+
+        fn finish(mut self: Box<Self>) -> Result<()> {
+            self.write_row_group()?;
+            self.writer.close()?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn create_parquet(file: File) -> Result<Box<dyn TransferFile>> {
+    Ok(Box::new(parquet_file::ParquetFile::create(file)?))
+}
+
+#[cfg(not(feature = "parquet"))]
+fn create_parquet(_: File) -> Result<Box<dyn TransferFile>> {
+    bail!("--export-format parquet requires vixen-client to be built with the `parquet` feature")
+}
+
+struct OpenFile {
+    file: Box<dyn TransferFile>,
+    path: PathBuf,
+    opened: Instant,
+    rows: u64,
+}
+
+struct ExportState {
+    dir: PathBuf,
+    format: ExportFormat,
+    rotate_after: Duration,
+    current: Option<OpenFile>,
+    // Files opened so far, part of the name so two started in the same
+    // millisecond don't collide
+    opened: u64,
+}
+
+impl ExportState {
+    fn open(&mut self) -> Result<()> {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        self.opened += 1;
+        let path = self.dir.join(format!(
+            "transfers-{}-{:06}.{}",
+            started,
+            self.opened,
+            self.format.extension()
+        ));
+        // Never truncate an earlier export, e.g. one from a previous run
+        if path.exists() {
+            bail!("Export file {} already exists", path.display());
+        }
+        let partial = File::create_new(partial_path(&path))?;
+        let file: Box<dyn TransferFile> = match self.format {
+            ExportFormat::Csv => Box::new(CsvFile::create(partial)?),
+            ExportFormat::Parquet => create_parquet(partial)?,
+        };
+        self.current = Some(OpenFile {
+            file,
+            path,
+            opened: Instant::now(),
+            rows: 0,
+        });
+        Ok(())
+    }
+
+    fn finish_current(&mut self) -> Result<()> {
+        let Some(OpenFile {
+            file, path, rows, ..
+        }) = self.current.take()
+        else {
+            return Ok(());
+        };
+        file.finish()?;
+        fs::rename(partial_path(&path), &path)?;
+        info!("Exported {} transfer(s) to {}", rows, path.display());
+        Ok(())
+    }
+
+    // Each file covers one window of `rotate_after`, quiet ones included
+    fn rotate_if_due(&mut self) -> Result<()> {
+        let due = self
+            .current
+            .as_ref()
+            .is_some_and(|current| current.opened.elapsed() >= self.rotate_after);
+        if due {
+            self.finish_current()?;
+            self.open()?;
+        }
+        Ok(())
+    }
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    partial.into()
+}
+
+// Writes every transfer_checked instruction on the stream to a file in `dir`,
+// starting a new one every `rotate_after` whether or not transfers arrive.
+// Files are named by when they were started, in milliseconds since the epoch,
+// and a sequence number, so they sort in order.
+pub struct TransferExporter {
+    state: Arc<Mutex<ExportState>>,
+}
+
+impl TransferExporter {
+    // Opens the first file up front so a bad directory or a missing feature
+    // fails at startup rather than on the first transfer
+    pub fn new(dir: &Path, format: ExportFormat, rotate_after: Duration) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let mut state = ExportState {
+            dir: dir.to_path_buf(),
+            format,
+            rotate_after,
+            current: None,
+            opened: 0,
+        };
+        state.open()?;
+        let state = Arc::new(Mutex::new(state));
+        // Rotate on a timer too, so a quiet stream still finishes its file
+        let rotated = Arc::downgrade(&state);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ROTATE_CHECK_INTERVAL.min(rotate_after));
+            loop {
+                interval.tick().await;
+                let Some(state) = rotated.upgrade() else {
+                    break;
+                };
+                let res = state.lock().unwrap().rotate_if_due();
+                if let Err(e) = res {
+                    warn!("Unable to rotate the transfer export: {}", e);
+                }
+            }
+        });
+        Ok(Self { state })
+    }
+}
+
+#[async_trait]
+impl MessageHandler for TransferExporter {
+    fn name(&self) -> &str {
+        "export"
+    }
+
+    async fn handle(&mut self, program: &Pubkey, update: &ParsedUpdate) -> Result<()> {
+        let Some(row) = TransferRow::from_update(program, update) else {
+            return Ok(());
+        };
+        let mut state = self.state.lock().unwrap();
+        state.rotate_if_due()?;
+        // Only missing after a rotation failed to open the next file
+        if state.current.is_none() {
+            state.open()?;
+        }
+        let current = state.current.as_mut().unwrap();
+        current.file.write(row)?;
+        current.rows += 1;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.state.lock().unwrap().finish_current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yellowstone_vixen_proto::parser::{
+        TokenProgramIxProto, TransferCheckedAccountsProto, TransferCheckedDataProto,
+        TransferCheckedIxProto,
+    };
+
+    use super::*;
+
+    fn transfer(amount: u64) -> ParsedUpdate {
+        ParsedUpdate::LegacyInstruction(TokenProgramIxProto {
+            ix_oneof: Some(TokenIx::TransferChecked(TransferCheckedIxProto {
+                accounts: Some(TransferCheckedAccountsProto {
+                    source: Pubkey::new_unique().to_string(),
+                    mint: Pubkey::new_unique().to_string(),
+                    destination: Pubkey::new_unique().to_string(),
+                    ..Default::default()
+                }),
+                data: Some(TransferCheckedDataProto {
+                    amount,
+                    decimals: 6,
+                }),
+            })),
+        })
+    }
+
+    // Exports two transfers and one update that isn't, returning the file
+    async fn export(format: ExportFormat) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "vixen-export-{}-{}",
+            format.extension(),
+            std::process::id()
+        ));
+        fs::remove_dir_all(&dir).ok();
+        let program = Pubkey::new_unique();
+        let mut exporter = TransferExporter::new(&dir, format, Duration::from_secs(3600)).unwrap();
+        exporter.handle(&program, &transfer(10)).await.unwrap();
+        exporter
+            .handle(
+                &program,
+                &ParsedUpdate::LegacyInstruction(Default::default()),
+            )
+            .await
+            .unwrap();
+        exporter.handle(&program, &transfer(20)).await.unwrap();
+        exporter.flush().await.unwrap();
+        let files: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1, "{:?}", files);
+        assert_eq!(
+            files[0].extension().unwrap().to_str(),
+            Some(format.extension())
+        );
+        files[0].clone()
+    }
+
+    #[tokio::test]
+    async fn exports_transfers_as_csv() {
+        let path = export(ExportFormat::Csv).await;
+        let csv = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], COLUMNS.join(","));
+        assert!(lines[2].ends_with(",20,6,,"), "{}", lines[2]);
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[tokio::test]
+    async fn rotates_while_the_stream_is_quiet() {
+        let dir = std::env::temp_dir().join(format!("vixen-export-rotate-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        let mut exporter =
+            TransferExporter::new(&dir, ExportFormat::Csv, Duration::from_millis(20)).unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        exporter.flush().await.unwrap();
+        let names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert!(names.len() > 1, "{:?}", names);
+        assert!(
+            names.iter().all(|name| name.ends_with(".csv")),
+            "{:?}",
+            names
+        );
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn exports_transfers_as_parquet() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let path = export(ExportFormat::Parquet).await;
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 2);
+        assert_eq!(
+            metadata.file_metadata().schema_descr().num_columns(),
+            COLUMNS.len()
+        );
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
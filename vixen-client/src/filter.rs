@@ -0,0 +1,200 @@
+use std::collections::BTreeSet;
+
+use color_eyre::{eyre::bail, Result};
+use prost_reflect::{DynamicMessage, Kind, MessageDescriptor, Value};
+use solana_sdk::pubkey::Pubkey;
+use svmkit_vixen_demo::{json::descriptor_pool, subscriber::ParsedUpdate};
+use yellowstone_vixen_proto::{parser::TokenExtensionProgramIxProto, prost::Name};
+
+// The outer oneof picks the program or extension, the inner one the
+// instruction itself, e.g. `token_program_ix` then `transfer_checked`
+const VARIANT_DEPTH: usize = 2;
+
+// Instruction variants to keep, by their proto field name at either level of
+// the oneof, so `transfer_checked` keeps just that instruction while
+// `transfer_fee_ix` keeps everything from the transfer fee extension
+pub struct IxFilter {
+    variants: BTreeSet<String>,
+}
+
+impl IxFilter {
+    // `flag` names the option the variants came from, for the error
+    pub fn new(flag: &str, names: &[String]) -> Result<Self> {
+        let known = known_variants();
+        let unknown: Vec<_> = names.iter().filter(|n| !known.contains(*n)).collect();
+        if !unknown.is_empty() {
+            let known: Vec<_> = known.into_iter().collect();
+            bail!(
+                "Unknown {} variant(s) {:?}, valid names are: {}",
+                flag,
+                unknown,
+                known.join(", ")
+            );
+        }
+        Ok(Self {
+            variants: names.iter().cloned().collect(),
+        })
+    }
+
+    // Legacy token program instructions are matched by their name alone,
+    // e.g. `transfer_checked`, as they have no outer oneof
+    pub fn matches(&self, update: &ParsedUpdate) -> bool {
+        // Anything we can't inspect is kept rather than silently dropped
+        let Some(Ok(message)) = update.to_dynamic() else {
+            return true;
+        };
+        let mut names = vec![];
+        set_variants(&message, VARIANT_DEPTH, &mut names);
+        names.iter().any(|name| self.variants.contains(name))
+    }
+}
+
+// Keeps updates with an owner or authority field naming one of the given
+// keys, i.e. those touching accounts they control. The subscription can only
+// be scoped to a program, so this runs on the client.
+pub struct OwnerFilter {
+    owners: BTreeSet<String>,
+}
+
+impl OwnerFilter {
+    pub fn new(owners: &[Pubkey]) -> Self {
+        Self {
+            owners: owners.iter().map(Pubkey::to_string).collect(),
+        }
+    }
+
+    pub fn matches(&self, update: &ParsedUpdate) -> bool {
+        matches_keys(update, &|field, key| {
+            let is_owner_field = field.contains("owner") || field.contains("authority");
+            is_owner_field && self.owners.contains(key)
+        })
+    }
+}
+
+// Keeps updates naming one of the given accounts in any field, e.g. as the
+// source or destination of a transfer, or as the mint of a token account.
+// State updates don't carry the address of their own account, so they only
+// match through the keys they hold.
+pub struct AccountFilter {
+    accounts: BTreeSet<String>,
+}
+
+impl AccountFilter {
+    pub fn new(accounts: &[Pubkey]) -> Self {
+        Self {
+            accounts: accounts.iter().map(Pubkey::to_string).collect(),
+        }
+    }
+
+    pub fn matches(&self, update: &ParsedUpdate) -> bool {
+        matches_keys(update, &|_, key| self.accounts.contains(key))
+    }
+}
+
+// Every client-side filter an update has to pass before reaching the sinks
+#[derive(Default)]
+pub struct UpdateFilters {
+    pub ix: Option<IxFilter>,
+    // Drop state and unknown updates, for --only-ix
+    pub instructions_only: bool,
+    pub owner: Option<OwnerFilter>,
+    pub account: Option<AccountFilter>,
+}
+
+impl UpdateFilters {
+    pub fn matches(&self, update: &ParsedUpdate) -> bool {
+        if self.instructions_only && !update.is_instruction() {
+            return false;
+        }
+        if let Some(filter) = &self.ix {
+            if update.is_instruction() && !filter.matches(update) {
+                return false;
+            }
+        }
+        self.owner
+            .as_ref()
+            .is_none_or(|filter| filter.matches(update))
+            && self
+                .account
+                .as_ref()
+                .is_none_or(|filter| filter.matches(update))
+    }
+}
+
+// Anything we can't inspect is kept rather than silently dropped
+fn matches_keys(update: &ParsedUpdate, matches: &dyn Fn(&str, &str) -> bool) -> bool {
+    let Some(Ok(message)) = update.to_dynamic() else {
+        return true;
+    };
+    names_key(&message, matches)
+}
+
+// Whether any string field, however deeply nested, satisfies `matches` given
+// its field name and value
+fn names_key(message: &DynamicMessage, matches: &dyn Fn(&str, &str) -> bool) -> bool {
+    message.fields().any(|(field, value)| {
+        let check = |value: &Value| match value {
+            Value::String(key) => matches(field.name(), key),
+            Value::Message(inner) => names_key(inner, matches),
+            _ => false,
+        };
+        match value {
+            Value::List(items) => items.iter().any(check),
+            value => check(value),
+        }
+    })
+}
+
+// The oneof variants an update is, outermost first, e.g.
+// `token_program_ix.transfer_checked` or `extended_token_account`
+pub fn variant_name(update: &ParsedUpdate) -> Option<String> {
+    let message = update.to_dynamic()?.ok()?;
+    let depth = if update.is_instruction() {
+        VARIANT_DEPTH
+    } else {
+        1
+    };
+    let mut names = vec![];
+    set_variants(&message, depth, &mut names);
+    (!names.is_empty()).then(|| names.join("."))
+}
+
+fn known_variants() -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    if let Some(descriptor) =
+        descriptor_pool().get_message_by_name(&TokenExtensionProgramIxProto::full_name())
+    {
+        oneof_variants(&descriptor, VARIANT_DEPTH, &mut names);
+    }
+    names
+}
+
+fn oneof_variants(descriptor: &MessageDescriptor, depth: usize, names: &mut BTreeSet<String>) {
+    if depth == 0 {
+        return;
+    }
+    for field in descriptor.fields() {
+        if field.containing_oneof().is_none() {
+            continue;
+        }
+        names.insert(field.name().to_string());
+        if let Kind::Message(inner) = field.kind() {
+            oneof_variants(&inner, depth - 1, names);
+        }
+    }
+}
+
+fn set_variants(message: &DynamicMessage, depth: usize, names: &mut Vec<String>) {
+    if depth == 0 {
+        return;
+    }
+    for (field, value) in message.fields() {
+        if field.containing_oneof().is_none() {
+            continue;
+        }
+        names.push(field.name().to_string());
+        if let Value::Message(inner) = value {
+            set_variants(inner, depth - 1, names);
+        }
+    }
+}
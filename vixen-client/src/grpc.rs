@@ -0,0 +1,106 @@
+use std::{path::Path, time::Duration};
+
+use color_eyre::{eyre::bail, Result};
+use yellowstone_vixen_proto::tonic::{
+    metadata::AsciiMetadataValue,
+    service::Interceptor,
+    transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity},
+    Request, Status,
+};
+
+use svmkit_vixen_demo::subscriber::VixenSubscriber;
+
+// How to reach and authenticate with the gRPC server
+#[derive(Clone, Copy)]
+pub struct GrpcOptions<'a> {
+    // Connect over TLS even when the URL says http://
+    pub tls: bool,
+    pub ca_cert: Option<&'a Path>,
+    pub domain_name: Option<&'a str>,
+    // PEM certificate and key presented to servers that require mutual TLS
+    pub client_identity: Option<(&'a Path, &'a Path)>,
+    pub x_token: Option<&'a str>,
+    pub connect_timeout: Duration,
+    // None to never ping
+    pub keepalive_interval: Option<Duration>,
+    pub keepalive_timeout: Duration,
+    pub tcp_nodelay: bool,
+}
+
+// Sent on every request, as Yellowstone and hardened Vixen deployments
+// expect in an `x-token` header
+struct AuthToken(AsciiMetadataValue);
+
+impl Interceptor for AuthToken {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        request.metadata_mut().insert("x-token", self.0.clone());
+        Ok(request)
+    }
+}
+
+// Plaintext for http:// addresses like the local server, TLS for https://
+// (or with `tls`) with the system roots plus an optional extra CA. Every URL
+// after the first is a fallback, reached with the same options.
+pub fn grpc_subscriber(urls: &[&str], options: GrpcOptions) -> Result<VixenSubscriber> {
+    let Some((primary, fallbacks)) = urls.split_first() else {
+        bail!("--grpc-url needs at least one URL");
+    };
+    let token = match options.x_token {
+        Some(token) => match token.parse() {
+            Ok(token) => Some(AuthToken(token)),
+            Err(_) => bail!("--x-token must be printable ASCII"),
+        },
+        None => None,
+    };
+    let fallbacks = fallbacks
+        .iter()
+        .map(|url| grpc_endpoint(url, options))
+        .collect::<Result<Vec<_>>>()?;
+    let subscriber =
+        VixenSubscriber::new(grpc_endpoint(primary, options)?).with_fallback_endpoints(fallbacks);
+    Ok(match token {
+        Some(token) => subscriber.with_interceptor(token),
+        None => subscriber,
+    })
+}
+
+fn grpc_endpoint(url: &str, options: GrpcOptions) -> Result<Endpoint> {
+    // tonic only uses TLS for https:// URIs
+    let url = match url.strip_prefix("http://") {
+        Some(rest) if options.tls => format!("https://{}", rest),
+        _ => url.to_string(),
+    };
+    let mut endpoint = Channel::from_shared(url)?
+        .connect_timeout(options.connect_timeout)
+        .tcp_nodelay(options.tcp_nodelay);
+    if let Some(interval) = options.keepalive_interval {
+        endpoint = endpoint
+            .http2_keep_alive_interval(interval)
+            .keep_alive_timeout(options.keepalive_timeout);
+    }
+    if endpoint.uri().scheme_str() != Some("https") {
+        if options.ca_cert.is_some()
+            || options.domain_name.is_some()
+            || options.client_identity.is_some()
+        {
+            bail!(
+                "--ca-cert, --tls-domain-name and --client-cert need --tls or an https:// gRPC URL"
+            );
+        }
+        return Ok(endpoint);
+    }
+    let mut tls = ClientTlsConfig::new().with_native_roots();
+    if let Some(path) = options.ca_cert {
+        tls = tls.ca_certificate(Certificate::from_pem(std::fs::read(path)?));
+    }
+    if let Some(domain_name) = options.domain_name {
+        tls = tls.domain_name(domain_name);
+    }
+    if let Some((cert, key)) = options.client_identity {
+        tls = tls.identity(Identity::from_pem(
+            std::fs::read(cert)?,
+            std::fs::read(key)?,
+        ));
+    }
+    Ok(endpoint.tls_config(tls)?)
+}
@@ -0,0 +1,72 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use solana_sdk::pubkey::Pubkey;
+
+struct HealthState {
+    started: Instant,
+    // Whether each program's subscription is currently connected
+    connected: BTreeMap<String, bool>,
+    last_update: Option<(Instant, SystemTime)>,
+}
+
+// The stream's current state as the subscriptions see it, for the /healthz
+// and /readyz probes
+#[derive(Clone)]
+pub struct StreamHealth(Arc<Mutex<HealthState>>);
+
+impl StreamHealth {
+    // Every program starts out disconnected, so readiness waits for all of
+    // them
+    pub fn new(programs: &[Pubkey]) -> Self {
+        Self(Arc::new(Mutex::new(HealthState {
+            started: Instant::now(),
+            connected: programs.iter().map(|p| (p.to_string(), false)).collect(),
+            last_update: None,
+        })))
+    }
+
+    pub fn connected(&self, program: &Pubkey, connected: bool) {
+        self.0
+            .lock()
+            .unwrap()
+            .connected
+            .insert(program.to_string(), connected);
+    }
+
+    pub fn updated(&self) {
+        self.0.lock().unwrap().last_update = Some((Instant::now(), SystemTime::now()));
+    }
+
+    pub fn snapshot(&self) -> HealthSnapshot {
+        let state = self.0.lock().unwrap();
+        let (silent_for, last_update_at) = match state.last_update {
+            Some((at, wall)) => (
+                at.elapsed(),
+                Some(humantime::format_rfc3339_millis(wall).to_string()),
+            ),
+            None => (state.started.elapsed(), None),
+        };
+        HealthSnapshot {
+            connected: state.connected.clone(),
+            last_update_at,
+            silent_for,
+        }
+    }
+}
+
+pub struct HealthSnapshot {
+    pub connected: BTreeMap<String, bool>,
+    pub last_update_at: Option<String>,
+    // Since the last update, or since startup before the first one
+    pub silent_for: Duration,
+}
+
+impl HealthSnapshot {
+    pub fn all_connected(&self) -> bool {
+        self.connected.values().all(|connected| *connected)
+    }
+}
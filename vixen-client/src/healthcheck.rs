@@ -0,0 +1,76 @@
+use std::time::{Duration, Instant};
+
+use color_eyre::{
+    eyre::{bail, Report},
+    Result,
+};
+use solana_client::{client_error::ClientErrorKind, nonblocking::rpc_client::RpcClient};
+use svmkit_vixen_demo::subscriber::VixenSubscriber;
+
+use crate::redact_url;
+
+// Long enough for a remote server, short enough not to stall a CI step
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Check that the Vixen server accepts a connection and the validator answers
+// RPC, printing one line per endpoint so scripts can gate on the exit status
+pub async fn run(subscriber: &VixenSubscriber, rpc_client: &RpcClient) -> Result<()> {
+    let start = Instant::now();
+    let connect = subscriber.connect();
+    let grpc = match tokio::time::timeout(CONNECT_TIMEOUT, connect).await {
+        Ok(Ok(_)) => Ok("connected".to_string()),
+        // tonic's own message is just "transport error", the cause is deeper down
+        Ok(Err(e)) => {
+            let summary = e.to_string();
+            Err(format!("{}: {}", summary, Report::new(e).root_cause()))
+        }
+        Err(_) => Err(format!("no connection after {:?}", CONNECT_TIMEOUT)),
+    };
+    let grpc_ok = report(
+        "Vixen gRPC",
+        &redact_url(&subscriber.endpoint().uri().to_string()),
+        start.elapsed(),
+        grpc,
+    );
+
+    let start = Instant::now();
+    let rpc = rpc_client
+        .get_version()
+        .await
+        .map(|version| format!("solana-core {}", version.solana_core))
+        .map_err(|e| match e.kind {
+            // reqwest names the full URL, credentials and all, in its message
+            ClientErrorKind::Reqwest(e) => e.without_url().to_string(),
+            kind => kind.to_string(),
+        });
+    let rpc_ok = report(
+        "Validator RPC",
+        &redact_url(&rpc_client.url()),
+        start.elapsed(),
+        rpc,
+    );
+
+    if !(grpc_ok && rpc_ok) {
+        bail!("Healthcheck failed");
+    }
+    Ok(())
+}
+
+fn report(
+    name: &str,
+    // Already redacted, the output often ends up in CI logs
+    target: &str,
+    latency: Duration,
+    result: Result<String, String>,
+) -> bool {
+    match result {
+        Ok(detail) => {
+            println!("OK   {} {} in {:?}: {}", name, target, latency, detail);
+            true
+        }
+        Err(e) => {
+            println!("FAIL {} {} after {:?}: {}", name, target, latency, e);
+            false
+        }
+    }
+}
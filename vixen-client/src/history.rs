@@ -0,0 +1,85 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use yellowstone_vixen_proto::tonic;
+
+// Enough to see a flapping connection without growing without bound
+const MAX_EVENTS: usize = 64;
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionOutcome {
+    Connected,
+    ConnectFailed,
+    SubscribeFailed,
+    StreamEnded,
+    StreamError,
+    // By --chaos
+    Dropped,
+    // By --resubscribe-stale-after
+    Stale,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConnectionEvent {
+    pub at: String,
+    pub program: String,
+    pub attempt: u32,
+    pub outcome: ConnectionOutcome,
+    pub status_code: Option<String>,
+    pub reason: Option<String>,
+    pub backoff_ms: u64,
+}
+
+impl ConnectionEvent {
+    pub fn new(program: &Pubkey, attempt: u32, outcome: ConnectionOutcome) -> Self {
+        Self {
+            at: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            program: program.to_string(),
+            attempt,
+            outcome,
+            status_code: None,
+            reason: None,
+            backoff_ms: 0,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: impl ToString) -> Self {
+        self.reason = Some(reason.to_string());
+        self
+    }
+
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff_ms = backoff.as_millis() as u64;
+        self
+    }
+
+    pub fn with_status(mut self, status: &tonic::Status) -> Self {
+        self.status_code = Some(format!("{:?}", status.code()));
+        self.with_reason(status.message())
+    }
+}
+
+// Bounded record of connection attempts and stream terminations, shared
+// between the stream task and the HTTP status endpoint
+#[derive(Clone, Default)]
+pub struct ConnectionHistory(Arc<Mutex<VecDeque<ConnectionEvent>>>);
+
+impl ConnectionHistory {
+    pub fn record(&self, event: ConnectionEvent) {
+        let mut events = self.0.lock().unwrap();
+        if events.len() == MAX_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    pub fn snapshot(&self) -> Vec<ConnectionEvent> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+}
@@ -0,0 +1,179 @@
+use std::{collections::BTreeMap, net::SocketAddr, str::FromStr as _, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use color_eyre::Result;
+use metrics_exporter_prometheus::PrometheusHandle;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use svmkit_vixen_demo::mints::{MintAggregator, MintStats, TransferRecord};
+use tokio::net::TcpListener;
+use tracing::info;
+
+use crate::{
+    health::StreamHealth,
+    history::{ConnectionEvent, ConnectionHistory},
+};
+
+// Well inside the timeout a Kubernetes probe gives the whole request
+const RPC_HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+
+// State shared with the HTTP status endpoints
+#[derive(Clone)]
+pub struct AppState {
+    pub history: ConnectionHistory,
+    pub health: StreamHealth,
+    pub mints: MintAggregator,
+    pub rpc_client: Arc<RpcClient>,
+    // How long the stream may go without an update before /healthz fails
+    pub stale_after: Duration,
+}
+
+pub async fn serve(addr: SocketAddr, state: AppState) -> Result<()> {
+    let app = Router::new()
+        .route("/reconnects", get(reconnects))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/mints", get(mints))
+        .route("/mints/:pubkey", get(mint))
+        .route("/accounts/:pubkey", get(account))
+        .route("/transfers", get(transfers))
+        .with_state(state);
+    let listener = TcpListener::bind(addr).await?;
+    info!("Serving HTTP status endpoints on {}", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn reconnects(State(state): State<AppState>) -> Json<Vec<ConnectionEvent>> {
+    Json(state.history.snapshot())
+}
+
+// Fails once the stream has been silent for longer than `stale_after`, the
+// one thing restarting the client could fix. A lost gRPC connection is
+// already retried by the client and the validator RPC is only needed by the
+// workload, so both are reported without failing the check.
+async fn healthz(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    let snapshot = state.health.snapshot();
+    let rpc = match tokio::time::timeout(RPC_HEALTH_TIMEOUT, state.rpc_client.get_health()).await {
+        Ok(Ok(())) => json!({ "reachable": true, "error": null }),
+        Ok(Err(e)) => json!({ "reachable": false, "error": e.to_string() }),
+        Err(_) => json!({
+            "reachable": false,
+            "error": format!("no answer after {:?}", RPC_HEALTH_TIMEOUT),
+        }),
+    };
+    let stale = snapshot.silent_for > state.stale_after;
+    let status = if stale {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    let body = json!({
+        "status": if stale { "stale" } else { "ok" },
+        "grpc": {
+            "connected": snapshot.all_connected(),
+            "programs": snapshot.connected,
+        },
+        "last_update_at": snapshot.last_update_at,
+        "silent_for_seconds": snapshot.silent_for.as_secs_f64(),
+        "stale_after_seconds": state.stale_after.as_secs_f64(),
+        "rpc": rpc,
+    });
+    (status, Json(body))
+}
+
+// Ready once every program's subscription is connected
+async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    let snapshot = state.health.snapshot();
+    let ready = snapshot.all_connected();
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(json!({ "ready": ready, "programs": snapshot.connected })),
+    )
+}
+
+// Supply, holders and transfer volume of every mint seen on the stream, by
+// address
+async fn mints(State(state): State<AppState>) -> Json<BTreeMap<String, MintStats>> {
+    Json(state.mints.snapshot())
+}
+
+// Errors as JSON, like everything else served here
+type Lookup = Result<Json<Value>, (StatusCode, Json<Value>)>;
+
+fn not_found(what: &str, pubkey: &str) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": format!("No {} {} seen on the stream", what, pubkey) })),
+    )
+}
+
+fn check_pubkey(pubkey: &str) -> Result<(), (StatusCode, Json<Value>)> {
+    match Pubkey::from_str(pubkey) {
+        Ok(_) => Ok(()),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("Invalid pubkey {:?}: {}", pubkey, e) })),
+        )),
+    }
+}
+
+async fn mint(State(state): State<AppState>, Path(pubkey): Path<String>) -> Lookup {
+    check_pubkey(&pubkey)?;
+    match state.mints.mint(&pubkey) {
+        Some(stats) => Ok(Json(json!(stats))),
+        None => Err(not_found("mint", &pubkey)),
+    }
+}
+
+// A token account named by an instruction on the stream, with its balance
+// once one of its updates could be told apart
+async fn account(State(state): State<AppState>, Path(pubkey): Path<String>) -> Lookup {
+    check_pubkey(&pubkey)?;
+    match state.mints.account(&pubkey) {
+        Some(stats) => Ok(Json(json!(stats))),
+        None => Err(not_found("token account", &pubkey)),
+    }
+}
+
+const DEFAULT_TRANSFER_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+struct TransferQuery {
+    mint: Option<String>,
+    limit: Option<usize>,
+}
+
+// The latest transfers, newest first
+async fn transfers(
+    State(state): State<AppState>,
+    Query(query): Query<TransferQuery>,
+) -> Json<Vec<TransferRecord>> {
+    Json(state.mints.transfers(
+        query.mint.as_deref(),
+        query.limit.unwrap_or(DEFAULT_TRANSFER_LIMIT),
+    ))
+}
+
+// Prometheus scrape endpoint, served on its own port so it can be exposed
+// without the status endpoints
+pub async fn serve_metrics(port: u16, handle: PrometheusHandle) -> Result<()> {
+    let app = Router::new().route("/metrics", get(move || async move { handle.render() }));
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let listener = TcpListener::bind(addr).await?;
+    info!("Serving Prometheus metrics on {}/metrics", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use std::{sync::OnceLock, time::SystemTime};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use prost_reflect::{DescriptorPool, DynamicMessage};
+use serde::Serialize;
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use yellowstone_vixen_proto::{
+    parser::DESCRIPTOR_SET,
+    prost::{Message, Name},
+    prost_types::Any,
+};
+
+use crate::{
+    error::{Error, Result},
+    subscriber::ParsedUpdate,
+};
+
+static POOL: OnceLock<DescriptorPool> = OnceLock::new();
+
+// Descriptors for the parser protos, for when the generated code alone
+// can't answer a question generically
+pub fn descriptor_pool() -> &'static DescriptorPool {
+    POOL.get_or_init(|| {
+        DescriptorPool::decode(DESCRIPTOR_SET).expect("parser descriptor set is valid")
+    })
+}
+
+// Re-decode a generated message as a DynamicMessage so it can be inspected
+// through its descriptor
+pub fn to_dynamic<M: Message + Name>(message: &M) -> Result<DynamicMessage> {
+    let descriptor = descriptor_pool()
+        .get_message_by_name(&M::full_name())
+        .ok_or_else(|| Error::decode(format!("No descriptor found for {}", M::full_name())))?;
+    Ok(DynamicMessage::decode(
+        descriptor,
+        message.encode_to_vec().as_slice(),
+    )?)
+}
+
+// The generated protos don't implement serde, so go through the descriptor
+// set shipped with the parser protos to get a faithful JSON rendering
+pub fn to_json<M: Message + Name>(message: &M) -> Result<Value> {
+    Ok(serde_json::to_value(&to_dynamic(message)?)?)
+}
+
+impl ParsedUpdate {
+    pub fn type_name(&self) -> String {
+        match self {
+            ParsedUpdate::Instruction(ix) => full_name_of(ix),
+            ParsedUpdate::State(state) => full_name_of(state),
+            ParsedUpdate::LegacyInstruction(ix) => full_name_of(ix),
+            ParsedUpdate::LegacyState(state) => full_name_of(state),
+            ParsedUpdate::Custom(custom) => custom.type_name().to_string(),
+            ParsedUpdate::Unknown(any) => any
+                .type_url
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        }
+    }
+
+    // Unknown payloads can't be rendered field by field, so they keep their
+    // raw bytes, as do custom ones of a type outside the descriptor set
+    pub fn to_json(&self) -> Result<Value> {
+        match self {
+            ParsedUpdate::Instruction(ix) => to_json(ix),
+            ParsedUpdate::State(state) => to_json(state),
+            ParsedUpdate::LegacyInstruction(ix) => to_json(ix),
+            ParsedUpdate::LegacyState(state) => to_json(state),
+            ParsedUpdate::Custom(custom) => match self.to_dynamic() {
+                Some(message) => Ok(serde_json::to_value(&message?)?),
+                None => Ok(raw_json(custom.payload())),
+            },
+            ParsedUpdate::Unknown(any) => Ok(raw_json(any)),
+        }
+    }
+
+    // None for an unknown payload, which has no descriptor to go by, or a
+    // custom one whose type the descriptor set doesn't have
+    pub fn to_dynamic(&self) -> Option<Result<DynamicMessage>> {
+        match self {
+            ParsedUpdate::Instruction(ix) => Some(to_dynamic(ix)),
+            ParsedUpdate::State(state) => Some(to_dynamic(state)),
+            ParsedUpdate::LegacyInstruction(ix) => Some(to_dynamic(ix)),
+            ParsedUpdate::LegacyState(state) => Some(to_dynamic(state)),
+            ParsedUpdate::Custom(custom) => {
+                let descriptor = descriptor_pool().get_message_by_name(custom.type_name())?;
+                Some(
+                    DynamicMessage::decode(descriptor, custom.payload().value.as_slice())
+                        .map_err(Error::from),
+                )
+            }
+            ParsedUpdate::Unknown(_) => None,
+        }
+    }
+}
+
+fn raw_json(any: &Any) -> Value {
+    json!({
+        "type_url": any.type_url,
+        "value": BASE64.encode(&any.value),
+    })
+}
+
+fn full_name_of<M: Name>(_: &M) -> String {
+    M::full_name()
+}
+
+// Bumped whenever a field is renamed, removed or changes meaning, adding one
+// doesn't count
+const SCHEMA_VERSION: u32 = 1;
+
+// The record written by the structured sinks for each update. Every field is
+// always present so consumers can rely on the keys. The stream doesn't carry
+// the slot or transaction signature yet, so those are null until it does.
+#[derive(Serialize)]
+pub struct UpdateRecord {
+    pub schema_version: u32,
+    pub received_at: String,
+    pub slot: Option<u64>,
+    pub signature: Option<String>,
+    pub program: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub message: Value,
+}
+
+pub fn update_record(update: &ParsedUpdate, program: &Pubkey) -> Result<UpdateRecord> {
+    Ok(UpdateRecord {
+        schema_version: SCHEMA_VERSION,
+        received_at: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+        slot: None,
+        signature: None,
+        program: program.to_string(),
+        type_name: update.type_name(),
+        message: update.to_json()?,
+    })
+}
@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use color_eyre::{eyre::eyre, Result};
+use futures::future::join_all;
+use prost_reflect::{DynamicMessage, Value};
+use rdkafka::{
+    message::{Header, OwnedHeaders},
+    producer::{DeliveryFuture, FutureProducer, FutureRecord, Producer as _},
+    ClientConfig,
+};
+use solana_sdk::pubkey::Pubkey;
+use svmkit_vixen_demo::{json::update_record, subscriber::ParsedUpdate};
+use tracing::warn;
+use yellowstone_vixen_proto::prost::Message as _;
+
+use crate::sink::{KafkaFormat, KafkaKey, KafkaOptions, MessageHandler};
+
+// Bounded so an unreachable broker slows the stream down but never stalls it
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+// What's needed to produce a record again when its delivery fails
+struct Record {
+    key: String,
+    payload: Vec<u8>,
+    type_name: String,
+    attempt: u32,
+}
+
+// Produces each update as a keyed record. Records are queued up to the batch
+// size before their deliveries are awaited, so the producer can batch them,
+// and failed deliveries are queued again with a backoff.
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic: String,
+    options: KafkaOptions,
+    in_flight: Vec<(Record, DeliveryFuture)>,
+}
+
+impl KafkaSink {
+    pub fn new(brokers: &str, topic: String, options: KafkaOptions) -> Result<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("message.timeout.ms", SEND_TIMEOUT.as_millis().to_string())
+            .set("linger.ms", options.linger.as_millis().to_string())
+            .create()?;
+        Ok(Self {
+            producer,
+            topic,
+            options,
+            in_flight: vec![],
+        })
+    }
+
+    fn enqueue(&self, record: &Record) -> Result<DeliveryFuture> {
+        let headers = OwnedHeaders::new().insert(Header {
+            key: "type",
+            value: Some(&record.type_name),
+        });
+        let future_record = FutureRecord::to(&self.topic)
+            .key(&record.key)
+            .payload(&record.payload)
+            .headers(headers);
+        self.producer
+            .send_result(future_record)
+            .map_err(|(e, _)| eyre!("Kafka produce to {} failed: {}", self.topic, e))
+    }
+
+    // Wait for everything in flight, retrying failed deliveries until they
+    // land or run out of attempts
+    async fn drain(&mut self) -> Result<()> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut lost = 0;
+        while !self.in_flight.is_empty() {
+            let (records, deliveries): (Vec<_>, Vec<_>) = self.in_flight.drain(..).unzip();
+            let mut failed = vec![];
+            for (record, delivery) in records.into_iter().zip(join_all(deliveries).await) {
+                match delivery {
+                    Ok(Ok(_)) => {}
+                    Ok(Err((e, _))) => failed.push((record, e.to_string())),
+                    Err(canceled) => failed.push((record, canceled.to_string())),
+                }
+            }
+            let Some((_, error)) = failed.first() else {
+                break;
+            };
+            warn!(
+                "{} Kafka delivery(s) to {} failed, retrying in {:?}: {}",
+                failed.len(),
+                self.topic,
+                backoff,
+                error
+            );
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            for (mut record, _) in failed {
+                record.attempt += 1;
+                if record.attempt > MAX_ATTEMPTS {
+                    lost += 1;
+                    continue;
+                }
+                match self.enqueue(&record) {
+                    Ok(delivery) => self.in_flight.push((record, delivery)),
+                    Err(_) => lost += 1,
+                }
+            }
+        }
+        if lost > 0 {
+            return Err(eyre!(
+                "{} update(s) not delivered to Kafka topic {} after {} attempts",
+                lost,
+                self.topic,
+                MAX_ATTEMPTS
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageHandler for KafkaSink {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    async fn handle(&mut self, program: &Pubkey, update: &ParsedUpdate) -> Result<()> {
+        let key = match self.options.key {
+            KafkaKey::Program => None,
+            KafkaKey::Account => account_key(update),
+        }
+        .unwrap_or_else(|| program.to_string());
+        let payload = match self.options.format {
+            KafkaFormat::Json => serde_json::to_vec(&update_record(update, program)?)?,
+            KafkaFormat::Proto => match update {
+                ParsedUpdate::Instruction(ix) => ix.encode_to_vec(),
+                ParsedUpdate::State(state) => state.encode_to_vec(),
+                ParsedUpdate::LegacyInstruction(ix) => ix.encode_to_vec(),
+                ParsedUpdate::LegacyState(state) => state.encode_to_vec(),
+                ParsedUpdate::Custom(custom) => custom.payload().value.clone(),
+                ParsedUpdate::Unknown(any) => any.value.clone(),
+            },
+        };
+        let record = Record {
+            key,
+            payload,
+            type_name: update.type_name(),
+            attempt: 1,
+        };
+        // A full producer queue is drained before trying again
+        let delivery = match self.enqueue(&record) {
+            Ok(delivery) => delivery,
+            Err(_) => {
+                self.drain().await?;
+                self.enqueue(&record)?
+            }
+        };
+        self.in_flight.push((record, delivery));
+        if self.in_flight.len() >= self.options.batch_size {
+            self.drain().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.drain().await?;
+        self.producer.flush(SEND_TIMEOUT)?;
+        Ok(())
+    }
+}
+
+// The first non-empty string in the update, depth first. Account fields come
+// first in the instruction protos, and state updates don't name their own
+// account, so a token account is keyed by its mint.
+fn account_key(update: &ParsedUpdate) -> Option<String> {
+    first_string(&update.to_dynamic()?.ok()?)
+}
+
+fn first_string(message: &DynamicMessage) -> Option<String> {
+    message.fields().find_map(|(_, value)| match value {
+        Value::String(key) if !key.is_empty() => Some(key.clone()),
+        Value::Message(inner) => first_string(inner),
+        _ => None,
+    })
+}
@@ -0,0 +1,350 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use metrics::histogram;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use tracing::{debug, info, warn};
+use yellowstone_vixen_proto::parser::{
+    token_extension_program_ix_proto::IxOneof, token_program_ix_proto::IxOneof as TokenIx,
+    transfer_fee_ix_proto::IxOneof as TransferFeeIx, TransferFeeIxProto,
+};
+
+use crate::subscriber::ParsedUpdate;
+
+// What a transfer instruction on the stream has in common with the transfer
+// that sent it. Updates don't carry the transaction signature, so this is all
+// there is to match them on.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct TransferKey {
+    source: String,
+    destination: String,
+    amount: u64,
+}
+
+impl TransferKey {
+    fn new(source: &Pubkey, destination: &Pubkey, amount: u64) -> Self {
+        Self {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            amount,
+        }
+    }
+
+    fn from_update(update: &ParsedUpdate) -> Option<Self> {
+        if let Some(TokenIx::TransferChecked(transfer)) = update.token_instruction() {
+            let accounts = transfer.accounts.as_ref()?;
+            return Some(Self {
+                source: accounts.source.clone(),
+                destination: accounts.destination.clone(),
+                amount: transfer.data.as_ref()?.amount,
+            });
+        }
+        let ParsedUpdate::Instruction(ix) = update else {
+            return None;
+        };
+        match ix.ix_oneof.as_ref()? {
+            IxOneof::TransferFeeIx(TransferFeeIxProto {
+                ix_oneof: Some(TransferFeeIx::TransferCheckedWithFeeIx(transfer)),
+            }) => {
+                let accounts = transfer.accounts.as_ref()?;
+                Some(Self {
+                    source: accounts.source.clone(),
+                    destination: accounts.destination.clone(),
+                    amount: transfer.data.as_ref()?.amount,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct TrackerState {
+    // Only transfers out of these accounts are tracked, so nobody else's
+    // traffic on a shared validator piles up unmatched
+    watched: HashSet<String>,
+    // Sent and not seen yet, oldest first, with the id the transfer's memo
+    // carried
+    sent: HashMap<TransferKey, VecDeque<(Signature, Instant, Option<String>)>>,
+    // Seen before the sender got its signature back, which happens when the
+    // stream beats the confirmation
+    seen: HashMap<TransferKey, VecDeque<Instant>>,
+    latencies: Vec<Duration>,
+    // Token accounts closed by the workload whose CloseAccount hasn't been
+    // seen yet, and how many have been
+    closing: HashSet<String>,
+    closes_seen: usize,
+}
+
+impl TrackerState {
+    fn matched(
+        &mut self,
+        signature: &Signature,
+        id: Option<String>,
+        sent_at: Instant,
+        seen_at: Instant,
+    ) {
+        let latency = seen_at.saturating_duration_since(sent_at);
+        histogram!("vixen_transfer_latency_seconds").record(latency);
+        match id {
+            // The memo itself isn't on the stream, Vixen only parses the
+            // token programs, so this is where a tagged transfer gets its id
+            Some(id) => info!(
+                "Transfer {} tagged {} reached the stream {:?} after it was sent",
+                signature, id, latency
+            ),
+            None => debug!(
+                "Transfer {} reached the stream {:?} after it was sent",
+                signature, latency
+            ),
+        }
+        self.latencies.push(latency);
+    }
+}
+
+// Pairs each transfer the workload sends with its instruction on the stream
+// and keeps the time in between. Transfers with the same accounts and amount,
+// like the load's ping-pong, are paired in the order they were sent. Clones
+// share their state, one goes to the workload and one to the stream.
+#[derive(Clone, Default)]
+pub struct TransferTracker {
+    state: Arc<Mutex<TrackerState>>,
+}
+
+impl TransferTracker {
+    pub fn watch(&self, accounts: &[Pubkey]) {
+        let mut state = self.state.lock().unwrap();
+        state.watched.extend(accounts.iter().map(Pubkey::to_string));
+    }
+
+    // `sent_at` is taken before sending, as the signature may only come back
+    // once the transaction has confirmed
+    pub fn sent(
+        &self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        amount: u64,
+        signature: Signature,
+        sent_at: Instant,
+        id: Option<String>,
+    ) {
+        let key = TransferKey::new(source, destination, amount);
+        let mut state = self.state.lock().unwrap();
+        let seen_at = state.seen.get_mut(&key).and_then(VecDeque::pop_front);
+        match seen_at {
+            Some(seen_at) => state.matched(&signature, id, sent_at, seen_at),
+            None => state
+                .sent
+                .entry(key)
+                .or_default()
+                .push_back((signature, sent_at, id)),
+        }
+    }
+
+    // A token account the workload is closing, to check its CloseAccount
+    // makes it onto the stream
+    pub fn closing(&self, account: &Pubkey) {
+        let mut state = self.state.lock().unwrap();
+        state.closing.insert(account.to_string());
+    }
+
+    pub fn observe(&self, update: &ParsedUpdate) {
+        let seen_at = Instant::now();
+        if let Some(TokenIx::CloseAccount(close)) = update.token_instruction() {
+            let Some(accounts) = &close.accounts else {
+                return;
+            };
+            let mut state = self.state.lock().unwrap();
+            if state.closing.remove(&accounts.account) {
+                info!("CloseAccount for {} reached the stream", accounts.account);
+                state.closes_seen += 1;
+            }
+            return;
+        }
+        let Some(key) = TransferKey::from_update(update) else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        if !state.watched.contains(&key.source) {
+            return;
+        }
+        let sent = state.sent.get_mut(&key).and_then(VecDeque::pop_front);
+        match sent {
+            Some((signature, sent_at, id)) => state.matched(&signature, id, sent_at, seen_at),
+            None => state.seen.entry(key).or_default().push_back(seen_at),
+        }
+    }
+
+    pub fn report(&self) -> LatencyReport {
+        let state = self.state.lock().unwrap();
+        let mut latencies = state.latencies.clone();
+        latencies.sort();
+        LatencyReport {
+            matched: latencies.len(),
+            unmatched: state.sent.values().map(VecDeque::len).sum(),
+            latencies,
+            closes_seen: state.closes_seen,
+            closes_unseen: state.closing.len(),
+        }
+    }
+}
+
+// Send-to-stream latency of the matched transfers, and how many were sent
+// without ever showing up
+pub struct LatencyReport {
+    pub matched: usize,
+    pub unmatched: usize,
+    latencies: Vec<Duration>,
+    // Of the accounts the workload closed
+    pub closes_seen: usize,
+    pub closes_unseen: usize,
+}
+
+impl LatencyReport {
+    // Nearest rank, so every percentile is a latency that was measured
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let rank = (percentile / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        Some(self.latencies[rank.clamp(1, self.latencies.len()) - 1])
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.latencies.last().copied()
+    }
+
+    pub fn log(&self) {
+        match (self.percentile(50.0), self.percentile(95.0), self.max()) {
+            (Some(p50), Some(p95), Some(max)) => info!(
+                "Send-to-stream latency over {} transfer(s): p50 {:?}, p95 {:?}, max {:?}, {} never seen",
+                self.matched, p50, p95, max, self.unmatched
+            ),
+            _ => info!(
+                "No transfer seen on the stream, {} sent",
+                self.unmatched
+            ),
+        }
+        if self.closes_unseen > 0 {
+            warn!(
+                "{} of {} closed token account(s) never had their CloseAccount on the stream",
+                self.closes_unseen,
+                self.closes_seen + self.closes_unseen
+            );
+        } else if self.closes_seen > 0 {
+            info!(
+                "CloseAccount seen on the stream for all {} closed token account(s)",
+                self.closes_seen
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yellowstone_vixen_proto::parser::{
+        CloseAccountAccountsProto, CloseAccountIxProto, TokenExtensionProgramIxProto,
+        TokenProgramIxProto, TransferCheckedAccountsProto, TransferCheckedDataProto,
+        TransferCheckedIxProto,
+    };
+
+    use super::*;
+
+    fn token_transfer(source: &Pubkey, destination: &Pubkey, amount: u64) -> TokenProgramIxProto {
+        TokenProgramIxProto {
+            ix_oneof: Some(TokenIx::TransferChecked(TransferCheckedIxProto {
+                accounts: Some(TransferCheckedAccountsProto {
+                    source: source.to_string(),
+                    destination: destination.to_string(),
+                    ..Default::default()
+                }),
+                data: Some(TransferCheckedDataProto {
+                    amount,
+                    decimals: 6,
+                }),
+            })),
+        }
+    }
+
+    fn transfer(source: &Pubkey, destination: &Pubkey, amount: u64) -> ParsedUpdate {
+        ParsedUpdate::Instruction(TokenExtensionProgramIxProto {
+            ix_oneof: Some(IxOneof::TokenProgramIx(token_transfer(
+                source,
+                destination,
+                amount,
+            ))),
+        })
+    }
+
+    #[test]
+    fn matches_transfers_in_either_order() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let tracker = TransferTracker::default();
+        tracker.watch(&[a, b]);
+        let sent_at = Instant::now();
+        tracker.sent(&a, &b, 10, Signature::default(), sent_at, None);
+        tracker.observe(&transfer(&a, &b, 10));
+        // The stream can beat the confirmation
+        tracker.observe(&transfer(&b, &a, 10));
+        tracker.sent(&b, &a, 10, Signature::default(), sent_at, None);
+        tracker.sent(&a, &b, 20, Signature::default(), sent_at, None);
+        let report = tracker.report();
+        assert_eq!(report.matched, 2);
+        assert_eq!(report.unmatched, 1);
+    }
+
+    #[test]
+    fn matches_transfers_through_the_legacy_token_program() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let tracker = TransferTracker::default();
+        tracker.watch(&[a]);
+        tracker.sent(&a, &b, 10, Signature::default(), Instant::now(), None);
+        tracker.observe(&ParsedUpdate::LegacyInstruction(token_transfer(&a, &b, 10)));
+        assert_eq!(tracker.report().matched, 1);
+    }
+
+    #[test]
+    fn ignores_transfers_out_of_unwatched_accounts() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let tracker = TransferTracker::default();
+        tracker.watch(&[a]);
+        tracker.observe(&transfer(&b, &a, 10));
+        tracker.sent(&b, &a, 10, Signature::default(), Instant::now(), None);
+        assert_eq!(tracker.report().matched, 0);
+    }
+
+    #[test]
+    fn checks_closed_accounts_reach_the_stream() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let tracker = TransferTracker::default();
+        tracker.closing(&a);
+        tracker.closing(&b);
+        tracker.observe(&ParsedUpdate::LegacyInstruction(TokenProgramIxProto {
+            ix_oneof: Some(TokenIx::CloseAccount(CloseAccountIxProto {
+                accounts: Some(CloseAccountAccountsProto {
+                    account: a.to_string(),
+                    ..Default::default()
+                }),
+            })),
+        }));
+        let report = tracker.report();
+        assert_eq!((report.closes_seen, report.closes_unseen), (1, 1));
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let report = LatencyReport {
+            matched: 4,
+            unmatched: 0,
+            latencies: [1, 2, 3, 4].map(Duration::from_millis).to_vec(),
+            closes_seen: 0,
+            closes_unseen: 0,
+        };
+        assert_eq!(report.percentile(50.0), Some(Duration::from_millis(2)));
+        assert_eq!(report.percentile(95.0), Some(Duration::from_millis(4)));
+        assert_eq!(report.max(), Some(Duration::from_millis(4)));
+    }
+}
@@ -0,0 +1,23 @@
+// The token helpers, Vixen subscription and demo workload behind the
+// vixen-client binary, reusable from other binaries and integration tests
+pub mod amount;
+pub mod batch;
+pub mod bench;
+pub mod chaos;
+pub mod cluster;
+pub mod error;
+pub mod explain;
+pub mod json;
+pub mod latency;
+pub mod load;
+pub mod mints;
+pub mod program;
+pub mod raw;
+pub mod scenario;
+pub mod subscriber;
+pub mod token;
+pub mod wallet;
+pub mod wire;
+pub mod workload;
+
+pub use error::{Error, Result};
@@ -0,0 +1,292 @@
+use std::time::{Duration, Instant};
+
+use metrics::counter;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+use tracing::{info, warn};
+
+use crate::{
+    batch::{batch_of, pack, BatchItem},
+    error::Result,
+    token::SendOptions,
+    workload::{transfer_instructions, Workload},
+};
+
+// Back-to-back transfers in the same direction would otherwise be byte for
+// byte identical whenever they share a blockhash, and the second one dropped
+// as a duplicate, so each one asks for a slightly different compute limit
+const COMPUTE_UNIT_LIMIT_BASE: u32 = 100_000;
+const COMPUTE_UNIT_LIMIT_SPREAD: u32 = 1_000_000;
+
+// get_signature_statuses accepts at most this many signatures per call
+const STATUS_BATCH_SIZE: usize = 256;
+
+// A blockhash stays usable for about a minute, so there's no need to fetch
+// one per transfer
+const BLOCKHASH_REFRESH: Duration = Duration::from_secs(10);
+
+// How often the running totals are logged, polling the pending transfers
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+// The two accounts of the demo flow, plus as many more as are needed
+pub const MIN_POOL_ACCOUNTS: usize = 2;
+
+#[derive(Clone, Copy)]
+pub struct LoadConfig {
+    pub tps: u32,
+    pub duration: Duration,
+    // Token accounts the transfers go round, at least MIN_POOL_ACCOUNTS
+    pub accounts: usize,
+    // Transfers packed into each transaction, fewer when they don't fit
+    pub batch: usize,
+}
+
+// One hop around the pool, with the keypair owning the source account
+pub struct TransferLeg<'a> {
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    pub owner: &'a Keypair,
+}
+
+#[derive(Default)]
+struct LoadStats {
+    sent: u64,
+    transfers: u64,
+    confirmed: u64,
+    failed: u64,
+}
+
+// Send `transfer_amount` round the legs in turn at roughly the requested
+// rate. Transactions are only sent on each tick and their confirmations
+// polled alongside, so a slow confirmation doesn't hold back the rate.
+// Shutdown ends the load early, with a single pass over the confirmations.
+pub async fn run(
+    client: &RpcClient,
+    payer: &Keypair,
+    mint: &Pubkey,
+    legs: &[TransferLeg<'_>],
+    workload: &Workload,
+    load: LoadConfig,
+    send_opts: SendOptions,
+) -> Result<()> {
+    info!(
+        "Generating load: {} transaction(s) per second of {} transfer(s) round {} account(s) for {:?}",
+        load.tps,
+        load.batch,
+        legs.len(),
+        load.duration
+    );
+    let mut stats = LoadStats::default();
+    let mut pending = vec![];
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / load.tps as f64));
+    let mut progress = tokio::time::interval_at(
+        tokio::time::Instant::now() + PROGRESS_INTERVAL,
+        PROGRESS_INTERVAL,
+    );
+    let mut blockhash = client.get_latest_blockhash().await?;
+    let mut blockhash_fetched = Instant::now();
+    let start = Instant::now();
+    let mut seq: u32 = 0;
+    let shutdown = workload.shutdown();
+    while start.elapsed() < load.duration {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = progress.tick() => {
+                if let Err(e) = poll_pending(client, &mut pending, &mut stats, send_opts).await {
+                    warn!("Unable to poll the load transfers: {}", e);
+                }
+                info!(
+                    "Load running for {:.0}s: {} sent, {} confirmed, {} failed, {} pending",
+                    start.elapsed().as_secs_f64(),
+                    stats.sent,
+                    stats.confirmed,
+                    stats.failed,
+                    pending.len()
+                );
+                continue;
+            }
+            _ = shutdown.cancelled() => {
+                info!("Load stopped by shutdown");
+                break;
+            }
+        }
+        if blockhash_fetched.elapsed() >= BLOCKHASH_REFRESH {
+            match client.get_latest_blockhash().await {
+                Ok(latest) => {
+                    blockhash = latest;
+                    blockhash_fetched = Instant::now();
+                }
+                Err(e) => warn!(
+                    "Unable to refresh the blockhash, keeping the last one: {}",
+                    e
+                ),
+            }
+        }
+        let mut items = vec![];
+        let mut ids = vec![];
+        for _ in 0..load.batch {
+            let leg = &legs[seq as usize % legs.len()];
+            let id = workload.next_transfer_id();
+            items.push(BatchItem {
+                instructions: transfer_instructions(
+                    workload,
+                    mint,
+                    &leg.source,
+                    &leg.destination,
+                    &leg.owner.pubkey(),
+                    id.as_deref(),
+                )?,
+                signers: vec![leg.owner],
+            });
+            ids.push((leg, id));
+            seq = seq.wrapping_add(1);
+        }
+        // A limit past the maximum is held to it, the instruction still
+        // tells the transactions apart
+        let compute_limit = [ComputeBudgetInstruction::set_compute_unit_limit(
+            COMPUTE_UNIT_LIMIT_BASE * load.batch as u32 + seq % COMPUTE_UNIT_LIMIT_SPREAD,
+        )];
+        let fee_payer = workload.fee_payer(payer);
+        for batch in pack(&fee_payer.pubkey(), &compute_limit, &items, load.batch)? {
+            let (instructions, signers) = batch_of(&compute_limit, &items[batch.clone()]);
+            stats.sent += 1;
+            let sent_at = Instant::now();
+            match send_transfer(client, fee_payer, &signers, &instructions, blockhash).await {
+                Ok(signature) => {
+                    workload.record_sent();
+                    for (leg, id) in ids[batch.clone()].iter_mut() {
+                        workload.transfer_sent(
+                            &leg.source,
+                            &leg.destination,
+                            signature,
+                            sent_at,
+                            id.take(),
+                        );
+                    }
+                    stats.transfers += batch.len() as u64;
+                    pending.push(signature);
+                }
+                Err(e) => {
+                    stats.failed += 1;
+                    warn!("Failed to send load transaction {}: {}", stats.sent, e);
+                }
+            }
+        }
+    }
+    let elapsed = start.elapsed();
+
+    let send_opts = if shutdown.is_cancelled() {
+        SendOptions {
+            confirm_attempts: 1,
+            ..send_opts
+        }
+    } else {
+        send_opts
+    };
+    confirm_pending(client, pending, &mut stats, send_opts).await?;
+    info!(
+        "Load finished after {:.1}s: {} sent ({:.1}/s) carrying {} transfer(s), {} confirmed, \
+         {} failed",
+        elapsed.as_secs_f64(),
+        stats.sent,
+        stats.sent as f64 / elapsed.as_secs_f64(),
+        stats.transfers,
+        stats.confirmed,
+        stats.failed
+    );
+    Ok(())
+}
+
+// Sign with the current blockhash and hand the transaction to the node
+// without waiting for it to land
+async fn send_transfer(
+    client: &RpcClient,
+    payer: &Keypair,
+    owners: &[&Keypair],
+    instructions: &[Instruction],
+    blockhash: Hash,
+) -> Result<Signature> {
+    let mut signers = vec![payer];
+    for owner in owners {
+        if owner.pubkey() != payer.pubkey() {
+            signers.push(owner);
+        }
+    }
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &signers,
+        blockhash,
+    );
+    let signature = client.send_transaction(&tx).await?;
+    counter!("vixen_transactions_submitted_total").increment(1);
+    Ok(signature)
+}
+
+// One pass over the statuses, leaving what's still unresolved in `pending`.
+// A failed call leaves the signatures it didn't get to in place as well.
+async fn poll_pending(
+    client: &RpcClient,
+    pending: &mut Vec<Signature>,
+    stats: &mut LoadStats,
+    send_opts: SendOptions,
+) -> Result<()> {
+    let commitment = send_opts.tx_commitment(client);
+    let mut unresolved = vec![];
+    let mut polled = 0;
+    let mut res = Ok(());
+    for batch in pending.chunks(STATUS_BATCH_SIZE) {
+        let statuses = match client.get_signature_statuses(batch).await {
+            Ok(statuses) => statuses,
+            Err(e) => {
+                res = Err(e.into());
+                break;
+            }
+        };
+        for (signature, status) in batch.iter().zip(statuses.value) {
+            match status {
+                Some(status) if status.err.is_some() => stats.failed += 1,
+                Some(status) if status.satisfies_commitment(commitment) => stats.confirmed += 1,
+                _ => unresolved.push(*signature),
+            }
+        }
+        polled += batch.len();
+    }
+    unresolved.extend_from_slice(&pending[polled..]);
+    *pending = unresolved;
+    res
+}
+
+// Give every sent transaction the usual confirmation window, polling them in
+// batches, and count anything still unresolved afterwards as failed
+async fn confirm_pending(
+    client: &RpcClient,
+    mut pending: Vec<Signature>,
+    stats: &mut LoadStats,
+    send_opts: SendOptions,
+) -> Result<()> {
+    for attempt in 1..=send_opts.confirm_attempts {
+        poll_pending(client, &mut pending, stats, send_opts).await?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        if attempt < send_opts.confirm_attempts {
+            tokio::time::sleep(send_opts.confirm_interval).await;
+        }
+    }
+    warn!(
+        "{} load transfer(s) not confirmed after {} attempt(s)",
+        pending.len(),
+        send_opts.confirm_attempts
+    );
+    stats.failed += pending.len() as u64;
+    Ok(())
+}
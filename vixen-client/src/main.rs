@@ -1,315 +1,2514 @@
-use color_eyre::Result;
-use solana_client::{
-    rpc_client::RpcClient, rpc_config::RpcRequestAirdropConfig, rpc_response::Response,
+mod benchmark;
+mod checkpoint;
+mod config;
+mod daemon;
+mod dead_letter;
+mod export;
+mod filter;
+mod grpc;
+mod health;
+mod healthcheck;
+mod history;
+mod http;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod ndjson;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod pipeline;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod pretty;
+mod proto_tap;
+mod recording;
+mod replay;
+mod sink;
+mod snapshot;
+mod spawn;
+mod state;
+mod stats;
+mod tui;
+mod verify;
+mod ws;
+
+use std::{
+    any::Any as PanicPayload,
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::IsTerminal as _,
+    net::SocketAddr,
+    ops::ControlFlow,
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use benchmark::{BenchArgs, BenchmarkArgs};
+use checkpoint::{Checkpointer, FileCheckpointStore, TrackedAccounts};
+use clap::Parser as _;
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
 };
+use config::Config;
+use dead_letter::DeadLetters;
+use export::{ExportFormat, TransferExporter};
+use filter::{AccountFilter, IxFilter, OwnerFilter, UpdateFilters};
+use futures::FutureExt as _;
+use grpc::{grpc_subscriber, GrpcOptions};
+use health::StreamHealth;
+use history::{ConnectionEvent, ConnectionHistory, ConnectionOutcome};
+use http::AppState;
+use metrics::{counter, histogram};
+use metrics_exporter_prometheus::PrometheusBuilder;
+use ndjson::NdjsonHandler;
+use pipeline::{queue, Overflow, PipelineOptions, QueueSender};
+use pretty::PrettyFormatter;
+use proto_tap::ProtoTap;
+use recording::{RecordArgs, Recorder, Recording, ReplayArgs, ReplaySpeed};
+use replay::ReplayFilter;
+use sink::{KafkaFormat, KafkaKey, KafkaOptions, LogHandler, MessageHandler, Sinks};
+use snapshot::{snapshot_collector, SnapshotArgs};
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    commitment_config::CommitmentConfig, program_pack::Pack, pubkey::Pubkey, signature::Keypair,
-    signer::Signer, system_instruction, transaction::Transaction,
+    commitment_config::CommitmentConfig, pubkey::Pubkey, signature::read_keypair_file,
 };
-use spl_token_2022::{
-    amount_to_ui_amount_string,
-    instruction::{initialize_account, initialize_mint},
-    state::{Account as TokenAccount, Mint},
+use spawn::{LocalCluster, SpawnOptions};
+use stats::StreamThroughput;
+use svmkit_vixen_demo::{
+    amount::UiAmount,
+    chaos::{self, ChaosOptions, MalformedReport, Recoveries},
+    cluster::{detect_cluster, Cluster},
+    latency::TransferTracker,
+    load::{LoadConfig, MIN_POOL_ACCOUNTS},
+    mints::MintAggregator,
+    program::{is_token_program, parse_program, program_alias, program_label, program_name},
+    raw::unpack_token_state,
+    scenario::Scenario,
+    subscriber::{
+        DecoderRegistry, ParsedUpdate, ReconnectPolicy, SubscriptionFailure, SubscriptionHooks,
+        VixenSubscriber, DEFAULT_MAX_MESSAGE_SIZE,
+    },
+    token::{SendOptions, TransferFeeParams},
+    wallet::Wallets,
+    workload::{
+        Workload, DEFAULT_BURN_AMOUNT, DEFAULT_DECIMALS, DEFAULT_MINT_AMOUNT,
+        DEFAULT_TRANSFER_AMOUNT,
+    },
 };
-use tracing::{error, info, info_span, Instrument};
-use tracing_subscriber::FmtSubscriber;
+use tokio::{sync::oneshot, task::JoinSet};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, warn, Instrument};
+use tracing_subscriber::{
+    filter::LevelFilter, fmt::writer::BoxMakeWriter, layer::SubscriberExt as _, EnvFilter, Layer,
+    Registry,
+};
+use tui::{Dashboard, TuiHandler};
+use verify::{balance_verifier, VerifyArgs};
+use ws::WsBroadcast;
 use yellowstone_vixen_proto::{
-    parser::{TokenExtensionProgramIxProto, TokenExtensionStateProto},
-    prost::Message,
-    stream::{program_streams_client::ProgramStreamsClient, SubscribeRequest},
+    prost::Message as _, prost_types::Any, stream::SubscribeUpdate,
+    tonic::codec::CompressionEncoding,
 };
 
 const GRPC_SERVER_ADDR: &str = "http://localhost:9000";
 const VALIDATOR_RPC_ADDR: &str = "http://localhost:8899";
+// In SOL
+const DEFAULT_AIRDROP_AMOUNT: u64 = 1;
+const SOL_DECIMALS: u8 = 9;
+// For --workload continuous without --load-tps or --load-duration
+const DEFAULT_LOAD_TPS: u32 = 10;
+const DEFAULT_LOAD_DURATION: Duration = Duration::from_secs(60);
+// Each extra account costs a couple of transactions to set up
+const MAX_POOL_ACCOUNTS: i64 = 16;
+// Each wallet may need an airdrop of its own
+const MAX_WALLETS: i64 = 32;
+// Loaded when present and no --config is given
+const DEFAULT_CONFIG_PATH: &str = "vixen-client.toml";
+// Where the log goes while --tui has the terminal
+const TUI_LOG_FILE: &str = "vixen-client.log";
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    color_eyre::install()?;
-    let subscriber = FmtSubscriber::builder().finish();
-    tracing::subscriber::set_global_default(subscriber)?;
+#[derive(clap::Parser)]
+#[command(version, author, about)]
+pub struct Opts {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// TOML file of demo parameters; flags given here take precedence over it
+    /// [default: vixen-client.toml, when it exists]
+    #[arg(long, value_name = "PATH", env = "VIXEN_CLIENT_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Run as a service next to the Vixen server and validator, e.g. in a
+    /// compose stack: every other flag comes from its VIXEN_<FLAG> variable,
+    /// the log is JSON on stdout, only the stream runs, never the workload,
+    /// and /healthz is served on --http-addr, 0.0.0.0:8080 by default
+    #[arg(long, env = "VIXEN_DAEMON", value_parser = clap::builder::BoolishValueParser::new())]
+    daemon: bool,
+
+    /// Vixen gRPC server to stream from, https:// URLs connect over TLS. A
+    /// comma-separated list names servers with the same stream to fail over
+    /// to, in order [default: http://localhost:9000]
+    #[arg(long, value_name = "URL", env = "VIXEN_GRPC_URL")]
+    grpc_url: Option<String>,
+
+    /// Validator JSON-RPC endpoint to send the demo transactions to
+    /// [default: http://localhost:8899]
+    #[arg(long, value_name = "URL", env = "SOLANA_RPC_URL")]
+    rpc_url: Option<String>,
+
+    /// Connect to the gRPC server over TLS, even with an http:// URL
+    #[arg(long)]
+    tls: bool,
+
+    /// PEM CA certificate to trust for the gRPC server, on top of the system roots
+    #[arg(long, alias = "ca-cert", value_name = "PATH")]
+    tls_ca_cert: Option<PathBuf>,
+
+    /// Domain name to verify the gRPC server's certificate against, if it
+    /// differs from the URL's host
+    #[arg(long, value_name = "NAME")]
+    tls_domain_name: Option<String>,
+
+    /// PEM client certificate for gRPC servers that require mutual TLS
+    #[arg(long, value_name = "PATH", requires = "client_key")]
+    client_cert: Option<PathBuf>,
+
+    /// PEM private key for --client-cert
+    #[arg(long, value_name = "PATH", requires = "client_cert")]
+    client_key: Option<PathBuf>,
+
+    /// Auth token sent in the x-token header of every gRPC request
+    #[arg(
+        long,
+        env = "VIXEN_X_TOKEN",
+        value_name = "TOKEN",
+        hide_env_values = true
+    )]
+    x_token: Option<String>,
+
+    /// How long to wait for the gRPC connection to be established, e.g. "10s"
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "10s")]
+    grpc_connect_timeout: Duration,
+
+    /// Send HTTP/2 keepalive pings this often, so a connection a NAT or load
+    /// balancer silently dropped is noticed and reconnected; "0s" turns them off
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "30s")]
+    grpc_keepalive_interval: Duration,
+
+    /// How long a keepalive ping may go unanswered before the connection is
+    /// considered dead
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "10s")]
+    grpc_keepalive_timeout: Duration,
+
+    /// Set TCP_NODELAY on the gRPC connection, trading some bandwidth for
+    /// latency
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    grpc_tcp_nodelay: bool,
+
+    /// Ask the Vixen server to compress the stream with any of these,
+    /// comma-separated, e.g. "gzip,zstd". The encoding it picks, and what it
+    /// saves, show up in the --stats-interval lines.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    grpc_compression: Vec<Compression>,
+
+    /// Compress the subscription requests as well, which only works with an
+    /// encoding the server accepts
+    #[arg(long, value_enum)]
+    grpc_send_compression: Option<Compression>,
+
+    /// Program to subscribe to: a pubkey or an alias such as token-2022, spl-token or ata.
+    /// Repeat it, or separate with commas, to subscribe to several at once
+    /// [default: token-2022]
+    #[arg(
+        long,
+        visible_alias = "program-id",
+        value_parser = parse_program,
+        value_delimiter = ',',
+        env = "VIXEN_PROGRAM_ID"
+    )]
+    program: Vec<Pubkey>,
+
+    /// Commitment to read at and wait for when confirming transactions,
+    /// including the airdrop; finalized is slower but can't be rolled back
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Commitment::Confirmed,
+        env = "SOLANA_COMMITMENT"
+    )]
+    commitment: Commitment,
+
+    /// Commitment to wait for on the airdrop [default: --commitment]
+    #[arg(long, value_enum)]
+    airdrop_commitment: Option<Commitment>,
+
+    /// Commitment to wait for on the workload's transactions [default:
+    /// --commitment]
+    #[arg(long, value_enum)]
+    tx_commitment: Option<Commitment>,
+
+    /// Commitment to read accounts and balances at, verify's included
+    /// [default: --commitment]
+    #[arg(long, value_enum)]
+    read_commitment: Option<Commitment>,
+
+    /// Start a solana-test-validator with a fresh ledger on the --rpc-url
+    /// port before anything else, and stop it on exit
+    #[arg(long, conflicts_with = "dry_run")]
+    spawn_validator: bool,
+
+    /// Geyser plugin config to load into the spawned validator, such as the
+    /// Yellowstone gRPC plugin a Vixen server subscribes to
+    #[arg(long, value_name = "PATH", requires = "spawn_validator")]
+    validator_geyser_config: Option<PathBuf>,
+
+    /// Also start a Vixen server with this config once the validator is up,
+    /// and wait for it to accept connections on --grpc-url
+    #[arg(long, value_name = "PATH", requires = "spawn_validator")]
+    spawn_vixen_server: Option<PathBuf>,
+
+    /// The Vixen server binary for --spawn-vixen-server
+    #[arg(long, value_name = "PATH", default_value = "vixen-server")]
+    vixen_server_bin: PathBuf,
+
+    /// Where the spawned processes keep their ledger and logs
+    #[arg(long, value_name = "DIR", default_value = ".vixen-local")]
+    spawn_dir: PathBuf,
+
+    /// How long each spawned process may take to start answering, e.g. "60s"
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "60s")]
+    spawn_timeout: Duration,
+
+    /// Log a breakdown of each transaction's instructions before sending it
+    #[arg(long)]
+    explain: bool,
+
+    /// Show a live dashboard of the stream and the workload instead of the
+    /// log, which goes to vixen-client.log
+    #[arg(long, conflicts_with = "dry_run")]
+    tui: bool,
+
+    /// Log each demo transaction and its estimated fee instead of sending it,
+    /// skipping the airdrop and the Vixen stream
+    #[arg(long, conflicts_with = "load_tps")]
+    dry_run: bool,
+
+    /// Payer keypair file to reuse across runs, created if it doesn't exist.
+    /// The payer is only airdropped SOL when its balance runs low.
+    #[arg(long, value_name = "PATH")]
+    keypair_path: Option<PathBuf>,
+
+    /// Spread the workload's transaction fees over this many payer wallets.
+    /// The first funds and owns what the workload creates.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u8).range(1..=MAX_WALLETS)
+    )]
+    wallets: u8,
+
+    /// Keep the --wallets in this directory across runs, creating any that
+    /// are missing. They're only airdropped SOL when their balance runs low.
+    #[arg(long, value_name = "DIR", conflicts_with = "keypair_path")]
+    wallet_dir: Option<PathBuf>,
+
+    /// Encrypt the wallets saved in --wallet-dir with this passphrase
+    #[arg(
+        long,
+        env = "VIXEN_WALLET_PASSPHRASE",
+        hide_env_values = true,
+        requires = "wallet_dir"
+    )]
+    wallet_passphrase: Option<String>,
+
+    /// Decimal places of the demo mint, at most 9 [default: 6]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(..=9))]
+    decimals: Option<u8>,
+
+    /// Tokens to mint into the first token account, e.g. "10000" or "1.5"
+    /// [default: 10000]
+    #[arg(long)]
+    mint_amount: Option<UiAmount>,
+
+    /// Tokens to transfer from the first token account to the second
+    /// [default: 1000]
+    #[arg(long)]
+    transfer_amount: Option<UiAmount>,
+
+    /// Tokens to burn from the first token account after the transfer
+    /// [default: 100]
+    #[arg(long)]
+    burn_amount: Option<UiAmount>,
+
+    /// SOL to airdrop to the payer when its balance runs low
+    /// [default: 1]
+    #[arg(long, value_name = "SOL")]
+    airdrop_amount: Option<UiAmount>,
+
+    /// Fund the payer and wallets with SOL transfers from this keypair file
+    /// instead of airdrops, for clusters whose faucet is disabled or rate
+    /// limited. The payer is topped up to what the run needs.
+    #[arg(long, value_name = "PATH")]
+    faucet_keypair: Option<PathBuf>,
+
+    /// Create the mint with the Token-2022 transfer fee extension, charging
+    /// this many basis points on the demo transfer
+    #[arg(long, value_name = "BPS", value_parser = clap::value_parser!(u16).range(..=10_000))]
+    transfer_fee_bps: Option<u16>,
+
+    /// Cap on the transfer fee, in tokens; uncapped by default
+    #[arg(long, requires = "transfer_fee_bps")]
+    transfer_fee_max: Option<UiAmount>,
+
+    /// Create the mint with the Token-2022 interest-bearing extension,
+    /// accruing this many basis points a year; negative rates are allowed
+    #[arg(long, value_name = "BPS", allow_negative_numbers = true)]
+    interest_rate_bps: Option<i16>,
+
+    /// Create the mint with the Token-2022 metadata pointer extension,
+    /// pointing at the mint itself
+    #[arg(long)]
+    metadata_pointer: bool,
+
+    /// Have the demo token accounts require a memo on incoming transfers,
+    /// with the Token-2022 memo transfer extension, and send every transfer
+    /// with one
+    #[arg(long)]
+    require_transfer_memos: bool,
+
+    /// Send every workload transfer with a memo carrying an id of its own,
+    /// e.g. vixen-demo-1a2b3c4d-7, and log the id with the transfer once it
+    /// shows up on the stream
+    #[arg(long)]
+    tag_transfers: bool,
+
+    /// Once the demo, its load or the scenario is done, burn what's left in
+    /// its token accounts and close them, reclaiming their rent to the payer
+    #[arg(long)]
+    teardown: bool,
+
+    /// Hold the demo tokens in associated token accounts, as a wallet would,
+    /// instead of freshly generated token account keypairs
+    #[arg(long)]
+    associated_token_accounts: bool,
+
+    /// Rotate the mint authority to a new keypair and mint again with it
+    #[arg(long)]
+    demo_rotate_authority: bool,
+
+    /// Hand the mint authority to a 2-of-3 multisig and mint again with two
+    /// of its signers
+    #[arg(long, conflicts_with = "demo_rotate_authority")]
+    demo_multisig: bool,
+
+    /// After the burn, approve a delegate, have it sign one more transfer and
+    /// revoke it
+    #[arg(long)]
+    demo_delegate: bool,
+
+    /// Log the raw type URL and base64 payload of the first N updates received
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    dump_first_n_raw: usize,
+
+    /// Write the raw bytes of payloads that fail to decode to this directory,
+    /// indexed in its index.ndjson
+    #[arg(long, value_name = "DIR")]
+    dead_letter_dir: Option<PathBuf>,
+
+    /// Only handle instructions of these variants, by proto field name, e.g.
+    /// transfer_checked or transfer_fee_ix. State updates are unaffected.
+    #[arg(long, value_name = "VARIANT", num_args = 1.., value_delimiter = ',')]
+    filter_ix: Vec<String>,
+
+    /// Only handle instructions of these variants, like --filter-ix, and drop
+    /// every other update, state updates included
+    #[arg(
+        long,
+        value_name = "VARIANT",
+        num_args = 1..,
+        value_delimiter = ',',
+        conflicts_with = "filter_ix"
+    )]
+    only_ix: Vec<String>,
+
+    /// Only handle updates with an owner or authority field set to one of
+    /// these keys, e.g. the payer from --keypair-path
+    #[arg(
+        long,
+        alias = "filter-owner",
+        value_name = "PUBKEY",
+        num_args = 1..,
+        value_delimiter = ','
+    )]
+    owner: Vec<Pubkey>,
+
+    /// Only handle updates naming one of these accounts in any field, e.g. a
+    /// transfer's source or destination, or a token account's mint
+    #[arg(long, value_name = "PUBKEY", num_args = 1.., value_delimiter = ',')]
+    filter_account: Vec<Pubkey>,
+
+    /// Which protos to decode stream payloads as, picked by their type URL;
+    /// anything else is passed on as an unknown update
+    #[arg(long, value_enum, default_value_t = DecodeKinds::Both)]
+    decode: DecodeKinds,
+
+    /// When a payload isn't one of the parser protos, or fails to decode as
+    /// one, try unpacking it as the raw bytes of a token account or mint
+    #[arg(long)]
+    raw_account_fallback: bool,
+
+    /// Largest stream message to accept, in bytes. Applied to the gRPC transport
+    /// and to each payload before decoding; larger payloads are skipped. The
+    /// default matches tonic's 4 MiB limit, far above any token update. Decode
+    /// recursion depth is fixed by prost at 100 levels and can't be tuned, but
+    /// the token protos nest only a few levels deep.
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_MAX_MESSAGE_SIZE)]
+    max_message_size: usize,
+
+    /// Let a panic while handling an update take down the stream instead of
+    /// logging it and moving on, useful when debugging a handler
+    #[arg(long)]
+    no_catch_panics: bool,
+
+    /// Exit non-zero on the first payload of an unknown type, or that fails to
+    /// decode as the type it claims, logging its type URL and payload. Meant
+    /// for conformance test harnesses checking that a server only emits
+    /// expected message types, not for production monitoring.
+    #[arg(long)]
+    fail_fast_on_first_decode_error: bool,
+
+    /// Consecutive connect or stream failures to tolerate before giving up on
+    /// the Vixen stream. Reconnects back off from 500ms up to 30s, with jitter.
+    #[arg(long, default_value_t = 10)]
+    max_reconnect_failures: u32,
+
+    /// Consecutive failures on one --grpc-url before failing over to the next
+    /// one in the list. Until the first connection each failure moves on.
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    grpc_failover_after: u32,
+
+    /// Resubscribe when the stream has sent nothing for this long while the
+    /// RPC node's slot kept advancing, e.g. "30s". Longer than the quietest
+    /// the program gets, a program with no traffic looks stale too.
+    #[arg(long, value_parser = humantime::parse_duration, value_name = "DURATION")]
+    resubscribe_stale_after: Option<Duration>,
+
+    /// Inject faults to show the demo recovering from them: delay
+    /// transactions, send some twice, drop the stream every --chaos-interval
+    /// and send the server malformed subscriptions, then report how it went
+    #[arg(long, conflicts_with = "dry_run")]
+    chaos: bool,
+
+    /// Chance of each demo transaction being delayed, and of it being sent
+    /// twice, with --chaos
+    #[arg(long, default_value_t = 0.2, requires = "chaos")]
+    chaos_rate: f64,
+
+    /// Longest delay --chaos holds a transaction back for, e.g. "2s"
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "2s", requires = "chaos")]
+    chaos_max_delay: Duration,
+
+    /// How often --chaos drops the stream and sends a malformed subscription,
+    /// e.g. "30s"
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "30s", requires = "chaos")]
+    chaos_interval: Duration,
+
+    /// Number of times to poll for a transaction's confirmation before giving up
+    #[arg(long, default_value_t = 30)]
+    confirm_attempts: u32,
+
+    /// Delay between confirmation polls, e.g. "500ms" or "2s"
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "1s")]
+    confirm_interval: Duration,
+
+    /// Times to sign and send a transaction with a fresh blockhash when the
+    /// previous one expired, and to resend it or fetch its blockhash after a
+    /// transient RPC error
+    #[arg(long, default_value_t = 3)]
+    send_attempts: u32,
+
+    /// How long a transaction may take to confirm, retries included, e.g. "2m"
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "2m")]
+    send_timeout: Duration,
+
+    /// How long to wait for the airdrop to reach --airdrop-commitment, e.g.
+    /// "30s"
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "30s")]
+    airdrop_timeout: Duration,
+
+    /// Number of times to look for a just-created account before deciding it doesn't exist
+    #[arg(long, default_value_t = 10)]
+    read_attempts: u32,
+
+    /// Create the mint right away instead of waiting for the Vixen stream
+    /// subscription, which may miss the first updates
+    #[arg(long)]
+    no_wait_for_stream: bool,
+
+    /// How long to wait for the stream subscription before minting, e.g. "30s"
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "30s")]
+    stream_timeout: Duration,
+
+    /// Serve HTTP status endpoints (e.g. /reconnects) on this address
+    #[arg(long, value_name = "ADDR")]
+    http_addr: Option<SocketAddr>,
+
+    /// How long the stream may go without an update before /healthz reports
+    /// it unhealthy, e.g. "60s"
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "60s")]
+    health_stale_after: Duration,
+
+    /// Re-broadcast parsed updates as JSON to WebSocket clients on this
+    /// address, e.g. 0.0.0.0:8080
+    #[arg(long, value_name = "ADDR")]
+    serve_ws: Option<SocketAddr>,
 
-    tokio::spawn(async {
-        let span = info_span!("Mint Token");
-        let res = airdrop_and_mint_token().instrument(span).await;
-        if let Err(_e) = res {
-            error!("Error airdropping or minting token");
+    /// Serve Prometheus metrics on this port at /metrics
+    #[arg(long, value_name = "PORT")]
+    metrics_port: Option<u16>,
+
+    /// How to emit parsed updates: pretty log lines, newline-delimited JSON, or
+    /// only to Kafka or PostgreSQL. Repeat it, or separate with commas, to emit
+    /// them several ways at once, each sink failing on its own.
+    #[arg(
+        long,
+        alias = "sink",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "pretty"
+    )]
+    output: Vec<OutputFormat>,
+
+    /// Log the stream's update and byte rates, decode failure rate and most
+    /// common update types this often, e.g. "10s", and a summary for the whole
+    /// run when it stops
+    #[arg(long, value_parser = humantime::parse_duration, value_name = "INTERVAL")]
+    stats_interval: Option<Duration>,
+
+    /// Format of the client's own log lines; levels are set with RUST_LOG
+    #[arg(long, value_enum, default_value_t = LogFormat::Pretty)]
+    log_format: LogFormat,
+
+    /// OTLP/gRPC collector to export the client's tracing spans to, e.g.
+    /// "http://localhost:4317" for Jaeger or Tempo. Needs vixen-client built
+    /// with the `otlp` feature.
+    #[arg(long, value_name = "URL")]
+    otlp_endpoint: Option<String>,
+
+    /// Write --output json records to this file instead of stdout
+    #[arg(long, value_name = "PATH")]
+    output_file: Option<PathBuf>,
+
+    /// File or named pipe to write the --output proto payloads to
+    #[arg(long, value_name = "PATH")]
+    proto_file: Option<PathBuf>,
+
+    /// Index of the --output proto payloads [default: the --proto-file path
+    /// with .index.ndjson added]
+    #[arg(long, value_name = "PATH", requires = "proto_file")]
+    proto_index: Option<PathBuf>,
+
+    /// Kafka bootstrap servers to publish parsed updates to
+    #[arg(
+        long,
+        alias = "brokers",
+        value_name = "BROKERS",
+        requires = "kafka_topic"
+    )]
+    kafka_brokers: Option<String>,
+
+    /// Kafka topic to publish parsed updates to
+    #[arg(
+        long,
+        alias = "topic",
+        value_name = "TOPIC",
+        requires = "kafka_brokers"
+    )]
+    kafka_topic: Option<String>,
+
+    /// PostgreSQL database to write parsed instructions and account states to
+    #[arg(long, env = "VIXEN_POSTGRES_URL", value_name = "URL")]
+    postgres_url: Option<String>,
+
+    /// Write every transfer_checked instruction to files in this directory,
+    /// alongside whatever --output does
+    #[arg(long, value_name = "DIR")]
+    export_transfers: Option<PathBuf>,
+
+    /// What kind of files --export-transfers writes
+    #[arg(long, value_enum, default_value_t = ExportFormat::Csv, requires = "export_transfers")]
+    export_format: ExportFormat,
+
+    /// Keep the last state of every token account the log has seen in this
+    /// file, so after a restart their next updates are still logged as what
+    /// changed
+    #[arg(long, value_name = "PATH")]
+    checkpoint: Option<PathBuf>,
+
+    /// How often --checkpoint is saved, besides once on shutdown
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "30s", requires = "checkpoint")]
+    checkpoint_interval: Duration,
+
+    /// How long --export-transfers writes to one file before starting the next
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "1h", requires = "export_transfers")]
+    export_rotate_after: Duration,
+
+    /// What to key Kafka records by
+    #[arg(long, value_enum, default_value_t = KafkaKey::Program)]
+    kafka_key: KafkaKey,
+
+    /// What to put in Kafka records
+    #[arg(long, value_enum, default_value_t = KafkaFormat::Json)]
+    kafka_format: KafkaFormat,
+
+    /// Kafka records to queue before waiting on their deliveries
+    #[arg(long, value_name = "N", default_value_t = 1000, value_parser = clap::value_parser!(u32).range(1..))]
+    kafka_batch_size: u32,
+
+    /// How long the Kafka producer waits to fill a batch before sending it
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "5ms")]
+    kafka_linger: Duration,
+
+    /// Updates each queue in the pipeline holds: one between the gRPC
+    /// subscriptions and decoding, then one in front of each sink
+    #[arg(long, value_name = "N", default_value_t = 1024, value_parser = clap::value_parser!(u32).range(1..))]
+    pipeline_capacity: u32,
+
+    /// What a full pipeline queue does with the next update
+    #[arg(long, value_enum, default_value_t = Overflow::Block)]
+    pipeline_overflow: Overflow,
+
+    /// Warn when a sink takes longer than this to handle a single update
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "100ms")]
+    slow_handler_threshold: Duration,
+
+    /// `once` stops after the demo flow, `continuous` then keeps transferring
+    /// tokens round a pool of accounts, see --load-tps
+    #[arg(long = "workload", value_enum, default_value_t = WorkloadMode::Once)]
+    workload_mode: WorkloadMode,
+
+    /// After the demo flow, keep transferring tokens round the pool of
+    /// accounts at this many transactions per second. Implies
+    /// --workload continuous, which defaults to 10.
+    #[arg(
+        long,
+        visible_alias = "tps",
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    load_tps: Option<u32>,
+
+    /// How long to keep the load going, e.g. "5m" [default: 60s]
+    #[arg(long, visible_alias = "duration", value_parser = humantime::parse_duration)]
+    load_duration: Option<Duration>,
+
+    /// Token accounts the load transfers go round, the demo's two and any
+    /// more created for it
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = MIN_POOL_ACCOUNTS as u8,
+        value_parser = clap::value_parser!(u8).range(MIN_POOL_ACCOUNTS as i64..=MAX_POOL_ACCOUNTS)
+    )]
+    load_accounts: u8,
+
+    /// Transfers packed into each load transaction, so each of the --load-tps
+    /// transactions per second carries this many. Fewer go in when they
+    /// don't fit, and the rest follow in another transaction.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u8).range(1..)
+    )]
+    load_batch: u8,
+
+    /// Send the steps in this YAML file in place of the demo flow, e.g.
+    /// scenarios/burn_and_close.yaml
+    #[arg(long, value_name = "FILE")]
+    scenario: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum WorkloadMode {
+    Once,
+    Continuous,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum LogFormat {
+    /// Human-readable lines
+    Pretty,
+    /// One JSON object per event, for log collectors
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum DecodeKinds {
+    /// Only TokenExtensionProgramIxProto
+    Ix,
+    /// Only TokenExtensionStateProto
+    State,
+    /// Both protos
+    Both,
+}
+
+impl DecodeKinds {
+    fn registry(self) -> DecoderRegistry {
+        match self {
+            DecodeKinds::Ix => DecoderRegistry::empty().with_instructions(),
+            DecodeKinds::State => DecoderRegistry::empty().with_states(),
+            DecodeKinds::Both => DecoderRegistry::default(),
         }
-    });
+    }
 
-    let vixen_client = tokio::spawn(async {
-        let span = info_span!("Vixen Streaming Client");
-        let res = vixen_client().instrument(span).await;
-        if let Err(_e) = res {
-            error!("Error connecting to Vixen client");
+    fn as_str(self) -> &'static str {
+        match self {
+            DecodeKinds::Ix => "ix",
+            DecodeKinds::State => "state",
+            DecodeKinds::Both => "both",
         }
-    });
-    vixen_client.await?;
+    }
+}
 
-    Ok(())
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
 }
 
-async fn vixen_client() -> Result<()> {
-    let mut client = ProgramStreamsClient::connect(GRPC_SERVER_ADDR).await?;
-    let req = SubscribeRequest {
-        program: spl_token_2022::id().to_string(),
-    };
-    let mut stream = client.subscribe(req).await?.into_inner();
-    info!("Connected to Vixen gRPC server");
-    while let Some(update) = stream.message().await? {
-        let any = update.parsed.unwrap();
-        if let Ok(parsed_message) = TokenExtensionProgramIxProto::decode(&*any.value) {
-            let val = parsed_message.ix_oneof.unwrap();
-            info!("Parsed message: {:?}", val);
-        } else if let Ok(parsed_message) = TokenExtensionStateProto::decode(&*any.value) {
-            let val = parsed_message.state_oneof.unwrap();
-            info!("Parsed message: {:?}", val);
-        }
-        // else {
-        //     warn!("Failed to parse TokenProgramIxProto message {:?}", any);
-        // }
+impl Commitment {
+    fn config(self) -> CommitmentConfig {
+        match self {
+            Commitment::Processed => CommitmentConfig::processed(),
+            Commitment::Confirmed => CommitmentConfig::confirmed(),
+            Commitment::Finalized => CommitmentConfig::finalized(),
+        }
     }
-    Ok(())
 }
 
-async fn airdrop_and_mint_token() -> Result<()> {
-    let kp = Keypair::new();
-    info!("Public key: {}", kp.pubkey());
-    // Fund the Keypair
-    let rpc_client = RpcClient::new(VALIDATOR_RPC_ADDR);
-    airdrop_new_address(kp.pubkey(), &rpc_client).await?;
-    // Create a new keypair for the mint
-    let mint_keypair = Keypair::new();
-    create_mint(&mint_keypair, &kp, &rpc_client).await?;
-    let (pk1, pk2) = create_token_accounts(&rpc_client, &kp, &mint_keypair.pubkey())?;
-    info!("Token Account 1 created: {}", pk1);
-    info!("Token Account 2 created: {}", pk2);
-
-    mint_to(
-        &rpc_client,
-        &kp,
-        &mint_keypair.pubkey(),
-        &pk1,
-        10_000_000_000,
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn encoding(self) -> CompressionEncoding {
+        match self {
+            Compression::Gzip => CompressionEncoding::Gzip,
+            Compression::Zstd => CompressionEncoding::Zstd,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+enum OutputFormat {
+    /// Each instruction as a one-line summary in the tracing log, colored on
+    /// a terminal, e.g. `TRANSFER_CHECKED 1.5 source=… mint=… destination=…`
+    Pretty,
+    /// Debug representation of each instruction in the tracing log
+    Debug,
+    /// One JSON object per line with a timestamp and the program id
+    Json,
+    /// Nothing in the log, only the records published with --kafka-brokers
+    Kafka,
+    /// Nothing in the log, only the rows written with --postgres-url
+    Postgres,
+    /// Nothing in the log, only the payloads as received, undecoded and
+    /// length-delimited, written to --proto-file alongside an index
+    Proto,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Measure the maximum sustainable stream throughput
+    Benchmark(BenchmarkArgs),
+    /// Set up mints, token accounts and durable nonces, then send batched
+    /// transfers as fast as they confirm while measuring the stream
+    Bench(BenchArgs),
+    /// Run the token workload and stream its updates, as without a subcommand
+    Demo,
+    /// Only stream and handle updates, without sending any transactions
+    Stream,
+    /// Only run the token workload, without subscribing to the stream
+    Workload,
+    /// Check that the Vixen server and the validator RPC are reachable
+    Healthcheck,
+    /// Run the demo as usual while writing every update received to a file
+    Record(RecordArgs),
+    /// Feed a recording back through the decoders, filters and outputs,
+    /// without a Vixen server or validator
+    Replay(ReplayArgs),
+    /// Run the demo while checking the token balances seen on the stream
+    /// against RPC, exiting non-zero if any diverged
+    Verify(VerifyArgs),
+    /// Run the demo for a while, then write every mint and token account
+    /// seen on the stream to a JSON file
+    Snapshot(SnapshotArgs),
+}
+
+// Running totals for the updates received on the stream
+#[derive(Default)]
+struct StreamStats {
+    instructions: u64,
+    decode_failures: u64,
+    states: u64,
+    undecoded: u64,
+    filtered: u64,
+    empty: u64,
+    oversized: u64,
+    panics: u64,
+    replayed: u64,
+    // Undecoded, but unpacked from raw account bytes
+    raw_fallbacks: u64,
+}
+
+// Options controlling how stream updates are processed
+#[derive(Clone, Copy)]
+struct StreamOptions {
+    dump_first_n_raw: usize,
+    decode: DecodeKinds,
+    raw_account_fallback: bool,
+    max_message_size: usize,
+    catch_panics: bool,
+    fail_fast: bool,
+    max_reconnect_failures: u32,
+    failover_after: u32,
+    stale_after: Option<Duration>,
+    pipeline: PipelineOptions,
+    chaos: Option<ChaosOptions>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    let opts = Opts::parse();
+    let opts = if opts.daemon {
+        Opts::parse_from(daemon::args_from_env()?)
+    } else {
+        opts
+    };
+    let Opts {
+        command,
+        config,
+        daemon,
+        keypair_path,
+        wallets,
+        wallet_dir,
+        wallet_passphrase,
+        grpc_url,
+        rpc_url,
+        tls,
+        tls_ca_cert,
+        tls_domain_name,
+        client_cert,
+        client_key,
+        x_token,
+        grpc_connect_timeout,
+        grpc_keepalive_interval,
+        grpc_keepalive_timeout,
+        grpc_tcp_nodelay,
+        grpc_compression,
+        grpc_send_compression,
+        program,
+        commitment,
+        airdrop_commitment,
+        tx_commitment,
+        read_commitment,
+        spawn_validator,
+        validator_geyser_config,
+        spawn_vixen_server,
+        vixen_server_bin,
+        spawn_dir,
+        spawn_timeout,
+        explain,
+        dry_run,
+        tui,
+        decimals,
+        mint_amount,
+        transfer_amount,
+        burn_amount,
+        airdrop_amount,
+        faucet_keypair,
+        transfer_fee_bps,
+        transfer_fee_max,
+        interest_rate_bps,
+        metadata_pointer,
+        require_transfer_memos,
+        tag_transfers,
+        teardown,
+        associated_token_accounts,
+        demo_rotate_authority,
+        demo_multisig,
+        demo_delegate,
+        dump_first_n_raw,
+        dead_letter_dir,
+        filter_ix,
+        only_ix,
+        owner,
+        filter_account,
+        decode,
+        raw_account_fallback,
+        max_message_size,
+        no_catch_panics,
+        fail_fast_on_first_decode_error,
+        max_reconnect_failures,
+        grpc_failover_after,
+        resubscribe_stale_after,
+        chaos,
+        chaos_rate,
+        chaos_max_delay,
+        chaos_interval,
+        confirm_attempts,
+        confirm_interval,
+        read_attempts,
+        airdrop_timeout,
+        send_attempts,
+        send_timeout,
+        no_wait_for_stream,
+        stream_timeout,
+        http_addr,
+        health_stale_after,
+        serve_ws,
+        metrics_port,
+        output,
+        stats_interval,
+        log_format,
+        otlp_endpoint,
+        output_file,
+        proto_file,
+        proto_index,
+        kafka_brokers,
+        kafka_topic,
+        postgres_url,
+        export_transfers,
+        export_format,
+        export_rotate_after,
+        checkpoint,
+        checkpoint_interval,
+        kafka_key,
+        kafka_format,
+        kafka_batch_size,
+        kafka_linger,
+        pipeline_capacity,
+        pipeline_overflow,
+        slow_handler_threshold,
+        workload_mode,
+        load_tps,
+        load_duration,
+        load_accounts,
+        load_batch,
+        scenario,
+    } = opts;
+    let config = match config {
+        Some(path) => Config::load(&path)?,
+        None if !daemon && Path::new(DEFAULT_CONFIG_PATH).exists() => {
+            Config::load(Path::new(DEFAULT_CONFIG_PATH))?
+        }
+        None => Config::default(),
+    };
+    let grpc_url = grpc_url
+        .or(config.grpc_url)
+        .unwrap_or_else(|| GRPC_SERVER_ADDR.to_string());
+    let rpc_configured = rpc_url.is_some() || config.rpc_url.is_some();
+    let rpc_url = rpc_url
+        .or(config.rpc_url)
+        .unwrap_or_else(|| VALIDATOR_RPC_ADDR.to_string());
+    // A list on the command line replaces the file's rather than adding to it
+    let mut programs = if !program.is_empty() {
+        program
+    } else if !config.program.is_empty() {
+        config.program
+    } else {
+        vec![spl_token_2022::id()]
+    };
+    let mut seen = HashSet::new();
+    programs.retain(|program| seen.insert(*program));
+    // The demo's transactions only show up in the stream when they go through
+    // a subscribed program, so build them for the first token program among
+    // them and fall back to Token-2022 otherwise
+    let token_program = programs
+        .iter()
+        .copied()
+        .find(is_token_program)
+        .unwrap_or(spl_token_2022::id());
+    let decimals = decimals.or(config.decimals).unwrap_or(DEFAULT_DECIMALS);
+    let mint_amount = mint_amount
+        .or(config.mint_amount)
+        .unwrap_or(UiAmount::from_base_units(DEFAULT_MINT_AMOUNT, 0));
+    let transfer_amount = transfer_amount
+        .or(config.transfer_amount)
+        .unwrap_or(UiAmount::from_base_units(DEFAULT_TRANSFER_AMOUNT, 0));
+    let burn_amount = burn_amount
+        .or(config.burn_amount)
+        .unwrap_or(UiAmount::from_base_units(DEFAULT_BURN_AMOUNT, 0));
+    let airdrop_amount = airdrop_amount
+        .or(config.airdrop_amount)
+        .unwrap_or(UiAmount::from_base_units(DEFAULT_AIRDROP_AMOUNT, 0));
+    if !(0.0..=1.0).contains(&chaos_rate) {
+        bail!("--chaos-rate must be between 0 and 1");
+    }
+    if chaos_interval.is_zero() {
+        bail!("--chaos-interval must be more than 0s");
+    }
+    if export_rotate_after.is_zero() {
+        bail!("--export-rotate-after must be more than 0s");
+    }
+    if resubscribe_stale_after.is_some_and(|after| after.is_zero()) {
+        bail!("--resubscribe-stale-after must be more than 0s");
+    }
+    if checkpoint_interval.is_zero() {
+        bail!("--checkpoint-interval must be more than 0s");
+    }
+    let chaos = chaos.then_some(ChaosOptions {
+        rate: chaos_rate,
+        max_delay: chaos_max_delay,
+        interval: chaos_interval,
+    });
+    let airdrop_commitment = airdrop_commitment.unwrap_or(commitment);
+    let tx_commitment = tx_commitment.unwrap_or(commitment);
+    let read_commitment = read_commitment.unwrap_or(commitment);
+    let send_opts = SendOptions {
+        explain,
+        confirm_attempts,
+        confirm_interval,
+        read_attempts,
+        airdrop_timeout,
+        send_attempts,
+        send_timeout,
+        dry_run,
+        chaos,
+        airdrop_commitment: Some(airdrop_commitment.config()),
+        tx_commitment: Some(tx_commitment.config()),
+        cluster: None,
+    };
+    let load = match (workload_mode, load_tps) {
+        (WorkloadMode::Once, None) => {
+            if load_duration.is_some()
+                || usize::from(load_accounts) != MIN_POOL_ACCOUNTS
+                || load_batch != 1
+            {
+                bail!(
+                    "--load-duration, --load-accounts and --load-batch need --workload continuous \
+                     or --load-tps"
+                );
+            }
+            None
+        }
+        (_, tps) => Some(LoadConfig {
+            tps: tps.unwrap_or(DEFAULT_LOAD_TPS),
+            duration: load_duration.unwrap_or(DEFAULT_LOAD_DURATION),
+            accounts: load_accounts.into(),
+            batch: load_batch.into(),
+        }),
+    };
+    if dry_run && load.is_some() {
+        bail!("--dry-run can't generate load");
+    }
+    // Checked up front, so a mistake in the file fails before anything is sent
+    let scenario = scenario.as_deref().map(Scenario::load).transpose()?;
+    if scenario.is_some() && load.is_some() {
+        bail!(
+            "--scenario replaces the demo flow, which --workload continuous and --load-tps follow"
+        );
+    }
+    let faucet = faucet_keypair
+        .map(|path| {
+            read_keypair_file(&path)
+                .map_err(|e| eyre!("Unable to read --faucet-keypair {}: {}", path.display(), e))
+        })
+        .transpose()?;
+    let workload = Workload::new(rpc_url.clone())
+        .with_commitment(read_commitment.config())
+        .with_decimals(decimals)
+        .with_mint_amount(
+            mint_amount
+                .to_base_units(decimals)
+                .map_err(|e| eyre!("Invalid --mint-amount: {}", e))?,
+        )
+        .with_transfer_amount(
+            transfer_amount
+                .to_base_units(decimals)
+                .map_err(|e| eyre!("Invalid --transfer-amount: {}", e))?,
+        )
+        .with_burn_amount(
+            burn_amount
+                .to_base_units(decimals)
+                .map_err(|e| eyre!("Invalid --burn-amount: {}", e))?,
+        )
+        .with_airdrop_lamports(
+            airdrop_amount
+                .to_base_units(SOL_DECIMALS)
+                .map_err(|e| eyre!("Invalid --airdrop-amount: {}", e))?,
+        )
+        .with_transfer_fee(
+            transfer_fee_bps
+                .map(|basis_points| -> Result<_> {
+                    let maximum_fee = match transfer_fee_max {
+                        Some(max) => max
+                            .to_base_units(decimals)
+                            .map_err(|e| eyre!("Invalid --transfer-fee-max: {}", e))?,
+                        None => u64::MAX,
+                    };
+                    Ok(TransferFeeParams {
+                        basis_points,
+                        maximum_fee,
+                    })
+                })
+                .transpose()?,
+        )
+        .with_interest_rate(interest_rate_bps)
+        .with_metadata_pointer(metadata_pointer)
+        .with_memo_transfers(require_transfer_memos)
+        .with_transfer_ids(tag_transfers)
+        .with_teardown(teardown)
+        .with_associated_token_accounts(associated_token_accounts)
+        .with_rotate_authority(demo_rotate_authority)
+        .with_multisig_authority(demo_multisig)
+        .with_delegated_transfer(demo_delegate)
+        .with_keypair_path(keypair_path)
+        .with_faucet(faucet)
+        .with_token_program(token_program)
+        .with_load(load)
+        .with_scenario(scenario)
+        .with_send_options(send_opts);
+    let stream_opts = StreamOptions {
+        dump_first_n_raw,
+        decode,
+        raw_account_fallback,
+        max_message_size,
+        catch_panics: !no_catch_panics,
+        fail_fast: fail_fast_on_first_decode_error,
+        max_reconnect_failures,
+        failover_after: grpc_failover_after,
+        stale_after: resubscribe_stale_after,
+        pipeline: PipelineOptions {
+            capacity: pipeline_capacity as usize,
+            overflow: pipeline_overflow,
+        },
+        chaos,
+    };
+    let extension_flags = [
+        ("--transfer-fee-bps", transfer_fee_bps.is_some()),
+        ("--interest-rate-bps", interest_rate_bps.is_some()),
+        ("--metadata-pointer", metadata_pointer),
+        ("--require-transfer-memos", require_transfer_memos),
+    ];
+    if let Some((flag, _)) = extension_flags.iter().find(|(_, set)| *set) {
+        if token_program != spl_token_2022::id() {
+            bail!("{} requires the Token-2022 program", flag);
+        }
+    }
+    let mut seen = HashSet::new();
+    let mut output = output;
+    output.retain(|output| seen.insert(*output));
+    let json_to_stdout = output.contains(&OutputFormat::Json) && output_file.is_none();
+    // A compose stack restarts the client and collects its log, there's
+    // nobody to watch a dashboard or wait for a one-shot workload
+    let (command, log_format, http_addr) = if daemon {
+        if json_to_stdout {
+            bail!("With --daemon the log has stdout, set VIXEN_OUTPUT_FILE for the --output json records");
+        }
+        if tui {
+            bail!("--tui doesn't go with --daemon");
+        }
+        let http_addr = match http_addr {
+            Some(addr) => addr,
+            None => daemon::DEFAULT_HTTP_ADDR.parse()?,
+        };
+        (Some(Command::Stream), LogFormat::Json, Some(http_addr))
+    } else {
+        (command, log_format, http_addr)
+    };
+    if output_file.is_some() && !output.contains(&OutputFormat::Json) {
+        bail!("--output-file requires --output json");
+    }
+    if proto_file.is_some() != output.contains(&OutputFormat::Proto) {
+        bail!("--output proto and --proto-file go together");
+    }
+    if tui {
+        if json_to_stdout {
+            bail!("--tui needs --output-file to write --output json records");
+        }
+        if matches!(
+            command,
+            Some(Command::Benchmark(_) | Command::Bench(_) | Command::Healthcheck)
+        ) {
+            bail!("--tui only applies to the demo flow, record and replay");
+        }
+    }
+    // Keep stdout clean for the JSON records when they're written there, and
+    // the terminal for the dashboard
+    let log_writer = if tui {
+        BoxMakeWriter::new(Mutex::new(File::create(TUI_LOG_FILE)?))
+    } else if json_to_stdout {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+    // Without RUST_LOG, log at info like the plain FmtSubscriber used to
+    let env_filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env_lossy();
+    // Only a terminal gets the --output pretty colors, never a file or pipe
+    let log_color = matches!(log_format, LogFormat::Pretty)
+        && !tui
+        && !json_to_stdout
+        && std::io::stdout().is_terminal()
+        && std::env::var_os("NO_COLOR").is_none();
+    let fmt_layer = tracing_subscriber::fmt::layer().with_writer(log_writer);
+    let fmt_layer = match log_format {
+        LogFormat::Pretty => fmt_layer.boxed(),
+        LogFormat::Json => fmt_layer.json().boxed(),
+    };
+    // Held until main returns, which flushes the spans
+    let (otlp_layer, _otlp) = otlp_layer(otlp_endpoint.as_deref())?.unzip();
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::registry()
+            .with(otlp_layer)
+            .with(env_filter)
+            .with(fmt_layer),
     )?;
+    // After the logger, which reports what was loaded and created
+    let wallets = match &wallet_dir {
+        Some(dir) => Some(Wallets::load_or_create(
+            dir,
+            wallets.into(),
+            wallet_passphrase.as_deref(),
+        )?),
+        None if wallets > 1 => Some(Wallets::generate(wallets.into())),
+        None => None,
+    };
+    let workload = workload.with_wallets(wallets);
+    if !programs.contains(&token_program) {
+        let subscribed: Vec<_> = programs.iter().map(program_label).collect();
+        warn!(
+            "Subscribed to {} but the demo sends its transactions through {} ({}), \
+             so they won't show up in the stream",
+            subscribed.join(", "),
+            program_name(&token_program),
+            token_program
+        );
+    }
+    if dry_run {
+        if !matches!(command, None | Some(Command::Demo | Command::Workload)) {
+            bail!("--dry-run only applies to the demo flow");
+        }
+        // Nothing lands on chain, so there's nothing for the stream to show
+        workload.run_configured(std::future::ready(Ok(()))).await?;
+        return Ok(());
+    }
+    let grpc_urls: Vec<_> = grpc_url
+        .split(',')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .collect();
+    let subscriber = grpc_subscriber(
+        &grpc_urls,
+        GrpcOptions {
+            tls,
+            ca_cert: tls_ca_cert.as_deref(),
+            domain_name: tls_domain_name.as_deref(),
+            client_identity: client_cert.as_deref().zip(client_key.as_deref()),
+            x_token: x_token.as_deref(),
+            connect_timeout: grpc_connect_timeout,
+            keepalive_interval: (!grpc_keepalive_interval.is_zero())
+                .then_some(grpc_keepalive_interval),
+            keepalive_timeout: grpc_keepalive_timeout,
+            tcp_nodelay: grpc_tcp_nodelay,
+        },
+    )?
+    .with_max_message_size(max_message_size)
+    .with_compression(grpc_compression.iter().map(|c| c.encoding()).collect())
+    .with_send_compression(grpc_send_compression.map(Compression::encoding));
+    let subscriber = if resubscribe_stale_after.is_some() {
+        subscriber.with_slots(Arc::new(RpcClient::new_with_commitment(
+            rpc_url.clone(),
+            read_commitment.config(),
+        )))
+    } else {
+        subscriber
+    };
 
-    let balance = fetch_token_balance(&rpc_client, &pk1)?;
+    let rpc_client = RpcClient::new_with_commitment(rpc_url.clone(), read_commitment.config());
+    // Held until main returns, which stops them
+    let _local_cluster = if spawn_validator {
+        let opts = SpawnOptions {
+            dir: &spawn_dir,
+            geyser_config: validator_geyser_config.as_deref(),
+            vixen_server: spawn_vixen_server
+                .as_deref()
+                .map(|config| (vixen_server_bin.as_path(), config)),
+            timeout: spawn_timeout,
+        };
+        Some(LocalCluster::start(opts, &rpc_client, &subscriber).await?)
+    } else {
+        let runs_workload = !matches!(
+            command,
+            Some(Command::Stream | Command::Replay(_) | Command::Healthcheck)
+        );
+        if runs_workload && rpc_client.get_version().await.is_err() {
+            warn!(
+                "No validator is answering at {}, start solana-test-validator \
+                 or pass --spawn-validator",
+                redact_url(&rpc_url)
+            );
+        }
+        None
+    };
+    if let Some(Command::Healthcheck) = command {
+        return healthcheck::run(&subscriber, &rpc_client).await;
+    }
+    let cluster = detect_cluster(&rpc_client).await.unwrap_or_else(|e| {
+        warn!("Unable to detect cluster from genesis hash: {}", e);
+        Cluster::Unknown
+    });
+    let workload = workload.with_cluster(cluster);
+    // Installed before the sinks are built so their histograms are registered
+    // with it; without a recorder the metrics macros are no-ops
+    let metrics = match metrics_port {
+        Some(port) => Some((
+            port,
+            PrometheusBuilder::new()
+                .add_global_label("cluster", cluster.as_str())
+                .install_recorder()?,
+        )),
+        None => None,
+    };
+    let filters = UpdateFilters {
+        ix: if !only_ix.is_empty() {
+            Some(IxFilter::new("--only-ix", &only_ix)?)
+        } else if !filter_ix.is_empty() {
+            Some(IxFilter::new("--filter-ix", &filter_ix)?)
+        } else {
+            None
+        },
+        instructions_only: !only_ix.is_empty(),
+        owner: (!owner.is_empty()).then(|| OwnerFilter::new(&owner)),
+        account: (!filter_account.is_empty()).then(|| AccountFilter::new(&filter_account)),
+    };
+    let ws = serve_ws.map(|addr| (addr, WsBroadcast::default()));
+    let dashboard = tui.then(Dashboard::new);
+    let new_sinks = |handlers| {
+        Sinks::new(
+            handlers,
+            slow_handler_threshold,
+            stream_opts.pipeline,
+            stream_opts.catch_panics,
+        )
+    };
+    let mut balance_checker = None;
+    let checkpointer = checkpoint
+        .map(|path| {
+            Checkpointer::restore(
+                Box::new(FileCheckpointStore::new(path)),
+                checkpoint_interval,
+            )
+        })
+        .transpose()?;
+    let mut snapshot = None;
+    let (mode, sinks) = match command {
+        Some(Command::Benchmark(_)) => ("benchmark", new_sinks(vec![])),
+        Some(Command::Bench(_)) => ("bench", new_sinks(vec![])),
+        Some(Command::Workload) => ("workload", new_sinks(vec![])),
+        _ => {
+            let kafka = kafka_brokers.zip(kafka_topic).map(|(brokers, topic)| {
+                let options = KafkaOptions {
+                    key: kafka_key,
+                    format: kafka_format,
+                    batch_size: kafka_batch_size as usize,
+                    linger: kafka_linger,
+                };
+                (brokers, topic, options)
+            });
+            // Streaming or replaying needs no validator, so the log only
+            // looks accounts up over RPC when one was asked for
+            let lookup_rpc_url = (rpc_configured
+                || !matches!(command, Some(Command::Stream | Command::Replay(_))))
+            .then_some(rpc_url.as_str());
+            let mut handlers = build_sinks(
+                lookup_rpc_url,
+                read_commitment.config(),
+                checkpointer
+                    .as_ref()
+                    .map(Checkpointer::accounts)
+                    .unwrap_or_default(),
+                &output,
+                output_file.as_deref(),
+                kafka,
+                postgres_url.as_deref(),
+                export_transfers
+                    .as_deref()
+                    .map(|dir| (dir, export_format, export_rotate_after)),
+                dashboard.as_ref(),
+                log_color,
+            )
+            .await?;
+            if let Some((_, broadcast)) = &ws {
+                handlers.push(Box::new(broadcast.clone()));
+            }
+            if let Some(Command::Verify(args)) = &command {
+                let (verifier, checker) =
+                    balance_verifier(&rpc_url, read_commitment.config(), args);
+                handlers.push(Box::new(verifier));
+                balance_checker = Some(checker);
+            }
+            if let Some(Command::Snapshot(args)) = &command {
+                let (collector, writer) = snapshot_collector(&rpc_url, read_commitment.config());
+                handlers.push(Box::new(collector));
+                snapshot = Some((writer, args.path.clone(), args.duration));
+            }
+            let mode = match command {
+                Some(Command::Record(_)) => "record",
+                Some(Command::Replay(_)) => "replay",
+                Some(Command::Stream) => "stream",
+                Some(Command::Verify(_)) => "verify",
+                Some(Command::Snapshot(_)) => "snapshot",
+                _ => "demo",
+            };
+            (mode, new_sinks(handlers))
+        }
+    };
     info!(
-        "Token Account {} balance: {}",
-        pk1,
-        amount_to_ui_amount_string(balance, 6)
+        mode,
+        %cluster,
+        grpc_endpoint = %grpc_urls.iter().map(|url| redact_url(url)).collect::<Vec<_>>().join(","),
+        rpc_endpoint = %redact_url(&rpc_url),
+        programs = %programs.iter().map(Pubkey::to_string).collect::<Vec<_>>().join(","),
+        program_aliases = %programs
+            .iter()
+            .map(|program| program_alias(program).unwrap_or("-"))
+            .collect::<Vec<_>>()
+            .join(","),
+        read_commitment = ?rpc_client.commitment().commitment,
+        airdrop_commitment = ?airdrop_commitment.config().commitment,
+        tx_commitment = ?tx_commitment.config().commitment,
+        sinks = %sinks.names().join(","),
+        "vixen-client started"
     );
-    let balance2 = fetch_token_balance(&rpc_client, &pk2)?;
-    info!(
-        "Token Account {} balance: {}",
-        pk2,
-        amount_to_ui_amount_string(balance2, 6)
+
+    let run_workload = !matches!(
+        command,
+        Some(
+            Command::Stream
+                | Command::Verify(VerifyArgs {
+                    stream_only: true,
+                    ..
+                })
+                | Command::Snapshot(SnapshotArgs {
+                    stream_only: true,
+                    ..
+                })
+        )
     );
+    let run_stream = !matches!(command, Some(Command::Workload));
+    let (recorder, recording) = match command {
+        Some(Command::Benchmark(args)) => {
+            return benchmark::run(args, &subscriber, &programs, workload).await;
+        }
+        Some(Command::Bench(args)) => {
+            return benchmark::run_bench(args, &subscriber, &programs, workload).await;
+        }
+        Some(Command::Record(args)) => (Some(Recorder::create(&args.path)?), None),
+        Some(Command::Replay(args)) => (None, Some(args)),
+        _ => (None, None),
+    };
+    let dead_letters = dead_letter_dir
+        .as_deref()
+        .map(DeadLetters::new)
+        .transpose()?;
+    let proto_tap = proto_file
+        .as_deref()
+        .map(|path| ProtoTap::create(path, proto_index.as_deref()))
+        .transpose()?;
 
-    let transfer_amount = 1_000_000_000;
-    let transfer_instruction = spl_token_2022::instruction::transfer_checked(
-        &spl_token_2022::id(),
-        &pk1,
-        &mint_keypair.pubkey(),
-        &pk2,
-        &kp.pubkey(),
-        &[],
-        transfer_amount,
-        6,
-    )?;
+    let history = ConnectionHistory::default();
+    let health = StreamHealth::new(&programs);
+    let mints = MintAggregator::default();
+    if let Some(addr) = http_addr {
+        let state = AppState {
+            history: history.clone(),
+            health: health.clone(),
+            mints: mints.clone(),
+            rpc_client: Arc::new(RpcClient::new_with_commitment(
+                rpc_url.clone(),
+                read_commitment.config(),
+            )),
+            stale_after: health_stale_after,
+        };
+        tokio::spawn(async move {
+            if let Err(e) = http::serve(addr, state).await {
+                error!("HTTP status server failed: {}", e);
+            }
+        });
+    }
 
-    let recent_blockhash = rpc_client.get_latest_blockhash()?;
-    let tx = Transaction::new_signed_with_payer(
-        &[transfer_instruction],
-        Some(&kp.pubkey()),
-        &[&kp],
-        recent_blockhash,
-    );
+    if let Some((addr, broadcast)) = ws {
+        let mints = mints.clone();
+        tokio::spawn(async move {
+            if let Err(e) = ws::serve(addr, broadcast, mints).await {
+                error!("WebSocket server failed: {}", e);
+            }
+        });
+    }
 
-    let signature = rpc_client.send_and_confirm_transaction(&tx)?;
-    info!("Transfer transaction signature: {}", signature);
+    if let Some((port, handle)) = metrics {
+        tokio::spawn(async move {
+            if let Err(e) = http::serve_metrics(port, handle).await {
+                error!("Metrics server failed: {}", e);
+            }
+        });
+    }
+
+    let (stream_ready_tx, stream_ready_rx) = oneshot::channel();
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            let signal = shutdown_signal().await;
+            info!("Received {}, shutting down", signal);
+            shutdown.cancel();
+            // Draining waits on the network, so give a second signal a way out
+            let signal = shutdown_signal().await;
+            warn!("Received {} again, exiting without draining", signal);
+            if tui {
+                ratatui::restore();
+            }
+            std::process::exit(130);
+        }
+    });
+
+    let transfers = TransferTracker::default();
+    let workload = workload
+        .with_shutdown(shutdown.clone())
+        .with_transfer_tracker(transfers.clone());
+    let dashboard = dashboard
+        .map(|dashboard| dashboard.spawn(workload.clone(), transfers.clone(), shutdown.clone()));
+    let balance_checker =
+        balance_checker.map(|checker| tokio::spawn(checker.run(shutdown.clone())));
+    let checkpointer =
+        checkpointer.map(|checkpointer| tokio::spawn(checkpointer.run(shutdown.clone())));
+    if let Some((_, _, duration)) = &snapshot {
+        let (duration, shutdown) = (*duration, shutdown.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            info!(
+                "Built state from the stream for {:?}, taking the snapshot",
+                duration
+            );
+            shutdown.cancel();
+        });
+    }
 
-    let balance = fetch_token_balance(&rpc_client, &pk1)?;
+    if let Some(args) = recording {
+        let mut consumer = StreamConsumer::new(
+            stream_opts,
+            sinks,
+            filters,
+            transfers,
+            mints,
+            dead_letters,
+            None,
+            proto_tap,
+            stats_interval,
+        );
+        let res = replay_recording(&args, &mut consumer, &shutdown).await;
+        consumer.close().await;
+        if let Some(dashboard) = dashboard {
+            // Leave the replayed updates on screen until the user quits
+            shutdown.cancelled().await;
+            close_dashboard(dashboard)?;
+        }
+        return res;
+    }
+    if !run_stream {
+        // Nothing to wait for, the transactions go out straight away
+        let res = workload
+            .run_configured(std::future::ready(Ok(())))
+            .instrument(info_span!("Mint Token"))
+            .await;
+        match &res {
+            Err(_) if shutdown.is_cancelled() => info!("Mint workflow stopped by shutdown"),
+            Err(e) => error!("Error airdropping or minting token: {:#}", e),
+            Ok(_) => {}
+        }
+        info!(
+            "Exiting after {} transaction(s) sent",
+            workload.transactions_sent()
+        );
+        if chaos.is_some() {
+            chaos::log_report(None);
+        }
+        shutdown.cancel();
+        if let Some(dashboard) = dashboard {
+            close_dashboard(dashboard)?;
+        }
+        return res.map_err(Into::into);
+    }
+    let mint_task = run_workload.then(|| {
+        tokio::spawn({
+            let workload = workload.clone();
+            let shutdown = shutdown.clone();
+            async move {
+                let span = info_span!("Mint Token");
+                let stream_ready = async move {
+                    if no_wait_for_stream {
+                        return Ok(());
+                    }
+                    wait_for_stream(stream_ready_rx, stream_timeout).await
+                };
+                let res = workload.run_configured(stream_ready).instrument(span).await;
+                match res {
+                    Err(_) if shutdown.is_cancelled() => {
+                        info!("Mint workflow stopped by shutdown")
+                    }
+                    Err(e) => error!("Error airdropping or minting token: {:#}", e),
+                    Ok(_) => {}
+                }
+            }
+        })
+    });
+
+    let recoveries = chaos.map(|_| Recoveries::default());
+    let malformed = chaos.map(|chaos| {
+        tokio::spawn(chaos::malformed_subscriptions(
+            subscriber.clone(),
+            chaos.interval,
+            shutdown.clone(),
+        ))
+    });
+    let vixen_client = tokio::spawn({
+        let shutdown = shutdown.clone();
+        let transfers = transfers.clone();
+        let recoveries = recoveries.clone();
+        async move {
+            let span = info_span!("Vixen Streaming Client");
+            let mut consumer = StreamConsumer::new(
+                stream_opts,
+                sinks,
+                filters,
+                transfers,
+                mints,
+                dead_letters,
+                recorder,
+                proto_tap,
+                stats_interval,
+            );
+            if let Some(throughput) = &mut consumer.throughput {
+                if !grpc_compression.is_empty() {
+                    throughput.watch_wire(subscriber.wire_stats().clone());
+                }
+            }
+            let res = vixen_client(
+                subscriber,
+                programs,
+                &mut consumer,
+                stream_ready_tx,
+                history,
+                health,
+                recoveries,
+                shutdown,
+            )
+            .instrument(span)
+            .await;
+            if let Err(e) = &res {
+                error!("Vixen streaming client stopped: {}", e);
+            }
+            (consumer.received, res)
+        }
+    });
+    let (received, res) = vixen_client.await?;
+    // The workload has nothing left to show once the stream is gone
+    shutdown.cancel();
+    if let Some(mint_task) = mint_task {
+        mint_task.await?;
+    }
     info!(
-        "Token Account {} updated balance: {}",
-        pk1,
-        amount_to_ui_amount_string(balance, 6)
+        "Exiting after {} update(s) received and {} transaction(s) sent",
+        received,
+        workload.transactions_sent()
     );
-    let balance2 = fetch_token_balance(&rpc_client, &pk2)?;
+    transfers.report().log();
+    if let (Some(recoveries), Some(malformed)) = (recoveries, malformed) {
+        let malformed = malformed
+            .await
+            .unwrap_or_else(|_| MalformedReport::default());
+        chaos::log_report(Some((&recoveries, &malformed)));
+    }
+    if let Some(checkpointer) = checkpointer {
+        checkpointer.await?;
+    }
+    let divergences = match balance_checker {
+        Some(checker) => checker.await?.divergences,
+        None => 0,
+    };
+    // A stream that gave up leaves nothing whole to snapshot
+    if let (Some((writer, path, _)), Ok(_)) = (snapshot, &res) {
+        writer.write(&path).await?;
+    }
+    if let Some(dashboard) = dashboard {
+        close_dashboard(dashboard)?;
+    }
+    // A stream that gave up after --max-reconnect-failures, or failed fast on
+    // a bad payload, exits non-zero, as does a balance that diverged; test
+    // harnesses rely on the status
+    if res.is_ok() && divergences > 0 {
+        bail!(
+            "{} token account balance(s) on the stream diverged from RPC",
+            divergences
+        );
+    }
+    res
+}
+
+// Feed each recorded update to the consumer, as fast as possible or spaced
+// out as they were received divided by the multiplier, until the recording
+// ends or shutdown
+async fn replay_recording(
+    args: &ReplayArgs,
+    consumer: &mut StreamConsumer,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    let mut recording = Recording::open(&args.path)?;
+    info!("Replaying {} at {}", args.path.display(), args.replay_speed);
+    let start = tokio::time::Instant::now();
+    let mut first_received = None;
+    while let Some(frame) = recording.next_frame()? {
+        let first = *first_received.get_or_insert(frame.received_at);
+        if let ReplaySpeed::Multiplier(multiplier) = args.replay_speed {
+            let offset = frame
+                .received_at
+                .duration_since(first)
+                .unwrap_or_default()
+                .div_f64(multiplier);
+            tokio::select! {
+                _ = tokio::time::sleep_until(start + offset) => {}
+                _ = shutdown.cancelled() => break,
+            }
+        } else if shutdown.is_cancelled() {
+            break;
+        }
+        let update = SubscribeUpdate {
+            parsed: Some(frame.payload),
+        };
+        let span = info_span!("update", program = %program_label(&frame.program));
+        consumer
+            .handle(frame.program, update)
+            .instrument(span)
+            .await?;
+    }
+    info!("Replayed {} update(s)", consumer.received);
+    Ok(())
+}
+
+// Wait for the dashboard thread to put the terminal back, once shutdown has
+// told it to stop
+fn close_dashboard(dashboard: std::thread::JoinHandle<std::io::Result<()>>) -> Result<()> {
+    match dashboard.join() {
+        Ok(res) => Ok(res?),
+        Err(_) => bail!("The dashboard thread panicked"),
+    }
+}
+
+// Resolves with the name of the first SIGINT or SIGTERM to arrive, the latter
+// being what container runtimes send to stop the demo
+async fn shutdown_signal() -> &'static str {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            // No handler could be installed, so never report Ctrl-C
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut sigterm) = signal(SignalKind::terminate()) {
+            return tokio::select! {
+                _ = ctrl_c => "Ctrl-C",
+                _ = sigterm.recv() => "SIGTERM",
+            };
+        }
+    }
+    ctrl_c.await;
+    "Ctrl-C"
+}
+
+// An update or a (re)connection from one of the per-program subscriptions
+enum StreamEvent {
+    Connected(Pubkey),
+    Update(Pubkey, SubscribeUpdate),
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn vixen_client(
+    subscriber: VixenSubscriber,
+    programs: Vec<Pubkey>,
+    consumer: &mut StreamConsumer,
+    ready: oneshot::Sender<()>,
+    history: ConnectionHistory,
+    health: StreamHealth,
+    recoveries: Option<Recoveries>,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    // Bounded so a slow consumer either holds the subscriptions back or
+    // loses the oldest updates, per --pipeline-overflow, rather than
+    // buffering updates without limit. Reconnections are never dropped, the
+    // replay filters depend on them.
+    let (events_tx, mut events) = queue("stream", consumer.stream_opts.pipeline, |event| {
+        matches!(event, StreamEvent::Update(..))
+    });
+    let mut subscriptions = JoinSet::new();
+    for &program in &programs {
+        let span = info_span!("subscription", program = %program_label(&program));
+        subscriptions.spawn(
+            subscription(
+                subscriber.clone(),
+                program,
+                consumer.stream_opts,
+                events_tx.clone(),
+                history.clone(),
+                health.clone(),
+                recoveries.clone(),
+                shutdown.clone(),
+            )
+            .instrument(span),
+        );
+    }
+    drop(events_tx);
+
+    let mut ready = Some(ready);
+    let mut connected = HashSet::new();
+    let mut stats_ticks = consumer.throughput.as_ref().map(|throughput| {
+        let start = tokio::time::Instant::now() + throughput.interval;
+        tokio::time::interval_at(start, throughput.interval)
+    });
+    let result = loop {
+        let stats_tick = async {
+            match &mut stats_ticks {
+                Some(ticks) => ticks.tick().await,
+                None => std::future::pending().await,
+            }
+        };
+        let event = tokio::select! {
+            event = events.recv() => event,
+            _ = stats_tick => {
+                if let Some(throughput) = &mut consumer.throughput {
+                    throughput.report();
+                    consumer.mints.log();
+                }
+                continue;
+            }
+            Some(joined) = subscriptions.join_next() => match joined {
+                Ok(Ok(())) => continue,
+                // One program giving up stops the client, like a single
+                // subscription always did
+                Ok(Err(e)) => break Err(e),
+                Err(e) => break Err(e.into()),
+            },
+        };
+        let event = match event {
+            Some(event) => event,
+            // Every subscription has stopped, so surface the first failure
+            None => break join_subscriptions(&mut subscriptions).await,
+        };
+        match event {
+            StreamEvent::Connected(program) => {
+                consumer
+                    .replays
+                    .entry(program)
+                    .or_insert_with(ReplayFilter::new)
+                    .reconnected();
+                connected.insert(program);
+                // Nobody may be waiting on this, which is fine
+                if connected.len() == programs.len() {
+                    if let Some(ready) = ready.take() {
+                        ready.send(()).ok();
+                    }
+                }
+            }
+            StreamEvent::Update(program, update) => {
+                let span = info_span!("update", program = %program_label(&program));
+                if let Err(e) = consumer.handle(program, update).instrument(span).await {
+                    break Err(e);
+                }
+            }
+        }
+    };
+    consumer.close().await;
+    if events.dropped() > 0 {
+        warn!(
+            "Dropped {} update(s) from the stream queue with --pipeline-overflow {}",
+            events.dropped(),
+            consumer.stream_opts.pipeline.overflow.as_str()
+        );
+    }
     info!(
-        "Token Account {} updated balance: {}",
-        pk2,
-        amount_to_ui_amount_string(balance2, 6)
+        "Connection history: {}",
+        serde_json::to_string(&history.snapshot())?
     );
+    result
+}
 
+async fn join_subscriptions(subscriptions: &mut JoinSet<Result<()>>) -> Result<()> {
+    while let Some(joined) = subscriptions.join_next().await {
+        joined??;
+    }
     Ok(())
 }
 
-async fn airdrop_new_address(pubkey: Pubkey, rpc_client: &RpcClient) -> Result<()> {
-    let signature = rpc_client.request_airdrop_with_config(
-        &pubkey,
-        1_000_000_000,
-        RpcRequestAirdropConfig {
-            recent_blockhash: None,
-            commitment: Some(CommitmentConfig::finalized()),
-        },
-    )?;
-    let mut res: Response<bool> = rpc_client
-        .confirm_transaction_with_commitment(&signature, CommitmentConfig::finalized())?;
-    while !res.value {
-        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-        res = rpc_client
-            .confirm_transaction_with_commitment(&signature, CommitmentConfig::finalized())?;
+// Everything needed to take an update from any subscription through the
+// decoders and filters to the sinks
+struct StreamConsumer {
+    stream_opts: StreamOptions,
+    sinks: Sinks,
+    filters: UpdateFilters,
+    decoders: DecoderRegistry,
+    transfers: TransferTracker,
+    mints: MintAggregator,
+    dead_letters: Option<DeadLetters>,
+    recorder: Option<Recorder>,
+    proto_tap: Option<ProtoTap>,
+    stats: StreamStats,
+    // Replays are per connection, so each program tracks its own
+    replays: HashMap<Pubkey, ReplayFilter>,
+    received: usize,
+    throughput: Option<StreamThroughput>,
+}
+
+impl StreamConsumer {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        stream_opts: StreamOptions,
+        sinks: Sinks,
+        filters: UpdateFilters,
+        transfers: TransferTracker,
+        mints: MintAggregator,
+        dead_letters: Option<DeadLetters>,
+        recorder: Option<Recorder>,
+        proto_tap: Option<ProtoTap>,
+        stats_interval: Option<Duration>,
+    ) -> Self {
+        Self {
+            stream_opts,
+            sinks,
+            filters,
+            decoders: stream_opts.decode.registry(),
+            transfers,
+            mints,
+            dead_letters,
+            recorder,
+            proto_tap,
+            stats: StreamStats::default(),
+            replays: HashMap::new(),
+            received: 0,
+            throughput: stats_interval.map(StreamThroughput::new),
+        }
+    }
+
+    // Flush the sinks and the recording, then log what went through them
+    async fn close(&mut self) {
+        let StreamConsumer {
+            stream_opts,
+            sinks,
+            stats,
+            dead_letters,
+            recorder,
+            proto_tap,
+            throughput,
+            mints,
+            ..
+        } = self;
+        sinks.close().await;
+        if let Some(throughput) = throughput {
+            throughput.summary();
+            mints.log();
+        }
+        info!(
+            "Stream closed after {} instruction(s) and {} state update(s), {} undecoded with --decode {}, {} filtered out, {} without a payload, {} oversized payload(s), {} panic(s), {} replay(s) skipped after reconnecting",
+            stats.instructions,
+            stats.states,
+            stats.undecoded,
+            stream_opts.decode.as_str(),
+            stats.filtered,
+            stats.empty,
+            stats.oversized,
+            stats.panics,
+            stats.replayed
+        );
+        sinks.log_summary();
+        if stats.raw_fallbacks > 0 {
+            info!(
+                "{} undecoded update(s) unpacked from raw account bytes",
+                stats.raw_fallbacks
+            );
+        }
+        if let Some(dead_letters) = dead_letters {
+            info!(
+                "{} payload(s) that failed to decode written to {}",
+                dead_letters.written(),
+                dead_letters.dir().display()
+            );
+        }
+        if let Some(recorder) = recorder {
+            match recorder.flush() {
+                Ok(()) => info!("Recorded {} update(s)", recorder.written()),
+                Err(e) => error!("Unable to finish writing the recording: {}", e),
+            }
+        }
+        if let Some(proto_tap) = proto_tap {
+            info!(
+                "{} payload(s) written to {}",
+                proto_tap.written(),
+                proto_tap.path().display()
+            );
+        }
+    }
+
+    // Only fails when --fail-fast-on-first-decode-error should stop the stream
+    async fn handle(&mut self, program: Pubkey, update: SubscribeUpdate) -> Result<()> {
+        let stream_opts = self.stream_opts;
+        if let Some(throughput) = &mut self.throughput {
+            throughput.framed(update.encoded_len());
+        }
+        let Some(any) = update.parsed else {
+            self.stats.empty += 1;
+            warn!("Received an update with no parsed payload, skipping it");
+            return Ok(());
+        };
+        if self
+            .replays
+            .get_mut(&program)
+            .is_some_and(|replays| replays.is_replay(&any))
+        {
+            self.stats.replayed += 1;
+            counter!("vixen_replayed_updates_total").increment(1);
+            return Ok(());
+        }
+        self.received += 1;
+        if let Some(throughput) = &mut self.throughput {
+            throughput.received(any.value.len());
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.write(&program, &any)?;
+        }
+        if let Some(proto_tap) = &mut self.proto_tap {
+            proto_tap.write(&program, &any)?;
+        }
+        if self.received <= stream_opts.dump_first_n_raw {
+            info!(
+                "Raw update {}/{}: type_url={} value={}",
+                self.received,
+                stream_opts.dump_first_n_raw,
+                any.type_url,
+                BASE64.encode(&any.value)
+            );
+        }
+        let undecoded = self.stats.undecoded;
+        let decode_failures = self.stats.decode_failures;
+        let process = async {
+            let parsed = info_span!("decode").in_scope(|| {
+                decode_payload(
+                    &any,
+                    stream_opts,
+                    &self.decoders,
+                    self.dead_letters.as_mut(),
+                    &mut self.stats,
+                )
+            });
+            if let Some(parsed) = parsed {
+                if let Some(throughput) = &mut self.throughput {
+                    throughput.decoded(&parsed);
+                }
+                self.transfers.observe(&parsed);
+                self.mints.observe(&parsed);
+                if !self.filters.matches(&parsed) {
+                    self.stats.filtered += 1;
+                    return;
+                }
+                self.sinks.dispatch(program, parsed).await;
+            }
+        };
+        if stream_opts.catch_panics {
+            // A bad payload or a buggy handler shouldn't end a long-running stream
+            if let Err(panic) = AssertUnwindSafe(process).catch_unwind().await {
+                self.stats.panics += 1;
+                error!(
+                    "Panic while handling update with type URL {:?} ({} bytes): {}",
+                    any.type_url,
+                    any.value.len(),
+                    panic_message(&*panic)
+                );
+            }
+        } else {
+            process.await;
+        }
+        if self.stats.decode_failures > decode_failures {
+            if let Some(throughput) = &mut self.throughput {
+                throughput.decode_failed();
+            }
+        }
+        if stream_opts.fail_fast && self.stats.undecoded > undecoded {
+            error!(
+                "Update of no known proto type with --decode {}: type_url={} value={}",
+                stream_opts.decode.as_str(),
+                any.type_url,
+                BASE64.encode(&any.value)
+            );
+            bail!(
+                "Undecodable update with type URL {:?}, failing fast",
+                any.type_url
+            );
+        }
+        Ok(())
     }
+}
+
+// Subscribe to a single program and forward its updates, reconnecting with
+// backoff until shutdown or until it runs out of consecutive failures
+#[allow(clippy::too_many_arguments)]
+async fn subscription(
+    subscriber: VixenSubscriber,
+    program: Pubkey,
+    stream_opts: StreamOptions,
+    events: QueueSender<StreamEvent>,
+    history: ConnectionHistory,
+    health: StreamHealth,
+    recoveries: Option<Recoveries>,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let policy = ReconnectPolicy {
+        max_failures: stream_opts.max_reconnect_failures,
+        failover_after: stream_opts.failover_after,
+        drop_after: stream_opts.chaos.map(|chaos| chaos.interval),
+        stale_after: stream_opts.stale_after,
+        ..ReconnectPolicy::default()
+    };
+    let mut hooks = ForwardUpdates {
+        program,
+        events,
+        history,
+        health,
+        last_message: None,
+        recoveries,
+        dropped_at: None,
+    };
+    subscriber
+        .run(&program, policy, &mut hooks, &shutdown)
+        .await?;
     Ok(())
 }
 
-async fn create_mint(mint_keypair: &Keypair, kp: &Keypair, rpc_client: &RpcClient) -> Result<()> {
-    let mint_pubkey = mint_keypair.pubkey();
-    let decimals = 6; // e.g., 6 decimal places like USDC
-
-    // Calculate minimum balance for rent exemption
-    let rent = rpc_client.get_minimum_balance_for_rent_exemption(Mint::LEN)?;
-    info!("Mint Address {}", mint_keypair.pubkey());
-    // Create the mint account
-    let create_account_ix = system_instruction::create_account(
-        &kp.pubkey(),
-        &mint_pubkey,
-        rent,
-        Mint::LEN as u64,
-        &spl_token_2022::id(),
-    );
+// Hands one program's updates to the consumer, keeping the connection
+// history, stream health and metrics up to date as the subscriber reconnects
+struct ForwardUpdates {
+    program: Pubkey,
+    events: QueueSender<StreamEvent>,
+    history: ConnectionHistory,
+    health: StreamHealth,
+    last_message: Option<Instant>,
+    // With --chaos, to time how long a dropped stream takes to come back
+    recoveries: Option<Recoveries>,
+    dropped_at: Option<Instant>,
+}
 
-    // Initialize the mint
-    let initialize_mint_ix = initialize_mint(
-        &spl_token_2022::id(),
-        &mint_pubkey,
-        &kp.pubkey(), // Mint authority
-        None,         // Optional freeze authority
-        decimals,
-    )?;
+#[async_trait]
+impl SubscriptionHooks for ForwardUpdates {
+    async fn connected(&mut self, attempt: u32) -> ControlFlow<()> {
+        self.history.record(ConnectionEvent::new(
+            &self.program,
+            attempt,
+            ConnectionOutcome::Connected,
+        ));
+        self.health.connected(&self.program, true);
+        self.last_message = None;
+        if let (Some(recoveries), Some(dropped_at)) = (&self.recoveries, self.dropped_at.take()) {
+            recoveries.recovered(dropped_at.elapsed());
+        }
+        // The receiver only goes away once the client is stopping
+        match self.events.send(StreamEvent::Connected(self.program)).await {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(_) => ControlFlow::Break(()),
+        }
+    }
 
-    // Build and send the transaction
-    let recent_blockhash = rpc_client.get_latest_blockhash()?;
-    let tx = Transaction::new_signed_with_payer(
-        &[create_account_ix, initialize_mint_ix],
-        Some(&kp.pubkey()),
-        &[&kp, &mint_keypair],
-        recent_blockhash,
-    );
-    let signature = rpc_client.send_and_confirm_transaction(&tx)?;
-    info!("Mint created with signature: {}", signature);
-    Ok(())
+    async fn update(&mut self, update: SubscribeUpdate) -> ControlFlow<()> {
+        counter!("vixen_grpc_messages_total", "program" => program_label(&self.program))
+            .increment(1);
+        self.health.updated();
+        let now = Instant::now();
+        if let Some(last) = self.last_message.replace(now) {
+            histogram!("vixen_message_interval_seconds").record(now - last);
+        }
+        match self
+            .events
+            .send(StreamEvent::Update(self.program, update))
+            .await
+        {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(_) => ControlFlow::Break(()),
+        }
+    }
+
+    fn failed(&mut self, attempt: u32, failure: &SubscriptionFailure, retry_in: Option<Duration>) {
+        self.health.connected(&self.program, false);
+        let event = |outcome| ConnectionEvent::new(&self.program, attempt, outcome);
+        let event = match failure {
+            SubscriptionFailure::Connect(e) => {
+                event(ConnectionOutcome::ConnectFailed).with_reason(e)
+            }
+            SubscriptionFailure::Subscribe(status) => {
+                event(ConnectionOutcome::SubscribeFailed).with_status(status)
+            }
+            SubscriptionFailure::Stream(status) => {
+                event(ConnectionOutcome::StreamError).with_status(status)
+            }
+            SubscriptionFailure::Ended => event(ConnectionOutcome::StreamEnded),
+            SubscriptionFailure::Dropped => {
+                if let Some(recoveries) = &self.recoveries {
+                    recoveries.dropped();
+                }
+                self.dropped_at = Some(Instant::now());
+                event(ConnectionOutcome::Dropped)
+            }
+            SubscriptionFailure::Stale { .. } => {
+                event(ConnectionOutcome::Stale).with_reason(failure)
+            }
+        };
+        match retry_in {
+            Some(delay) => {
+                self.history.record(event.with_backoff(delay));
+                counter!("vixen_reconnect_attempts_total", "program" => program_label(&self.program))
+                    .increment(1);
+            }
+            None => self.history.record(event),
+        }
+    }
 }
 
-// Create two token accounts for the mint
-fn create_token_accounts(
-    client: &RpcClient,
-    payer: &Keypair,
-    mint_pubkey: &Pubkey,
-) -> Result<(Pubkey, Pubkey)> {
-    // Create two new keypairs for the token accounts
-    let token_account1 = Keypair::new();
-    let token_account2 = Keypair::new();
-
-    // Get minimum balance for rent exemption
-    let rent = client.get_minimum_balance_for_rent_exemption(TokenAccount::LEN)?;
-
-    // Create account instructions
-    let create_account1_ix = system_instruction::create_account(
-        &payer.pubkey(),
-        &token_account1.pubkey(),
-        rent,
-        TokenAccount::LEN as u64,
-        &spl_token_2022::id(),
-    );
+// Wait for the stream task to report that the subscription is live. If the
+// stream task gives up the sender is dropped, so this fails fast rather than
+// waiting out the whole timeout
+async fn wait_for_stream(
+    ready: oneshot::Receiver<()>,
+    timeout: Duration,
+) -> svmkit_vixen_demo::Result<()> {
+    match tokio::time::timeout(timeout, ready).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(_)) => Err(svmkit_vixen_demo::Error::transport(
+            "Vixen stream failed to connect, not starting the mint workflow",
+        )),
+        Err(_) => Err(svmkit_vixen_demo::Error::transport(format!(
+            "Vixen stream did not connect within {:?}, not starting the mint workflow",
+            timeout
+        ))),
+    }
+}
 
-    let create_account2_ix = system_instruction::create_account(
-        &payer.pubkey(),
-        &token_account2.pubkey(),
-        rent,
-        TokenAccount::LEN as u64,
-        &spl_token_2022::id(),
-    );
+// Endpoints may carry credentials in their userinfo or query string, which
+// must never end up in the logs
+fn redact_url(url: &str) -> String {
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (format!("{}://", scheme), rest),
+        None => (String::new(), url),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+    let host = match authority.rsplit_once('@') {
+        Some((_, host)) => format!("***@{}", host),
+        None => authority.to_string(),
+    };
+    let path = match path.split_once('?') {
+        Some((path, _)) => format!("{}?***", path),
+        None => path.to_string(),
+    };
+    format!("{}{}{}", scheme, host, path)
+}
 
-    // Initialize token account instructions
-    let init_account1_ix = initialize_account(
-        &spl_token_2022::id(),
-        &token_account1.pubkey(),
-        mint_pubkey,
-        &payer.pubkey(), // Using payer as owner for simplicity
-    )?;
+#[allow(clippy::too_many_arguments)]
+async fn build_sinks(
+    rpc_url: Option<&str>,
+    commitment: CommitmentConfig,
+    accounts: TrackedAccounts,
+    outputs: &[OutputFormat],
+    output_file: Option<&Path>,
+    kafka: Option<(String, String, KafkaOptions)>,
+    postgres_url: Option<&str>,
+    export: Option<(&Path, ExportFormat, Duration)>,
+    dashboard: Option<&Dashboard>,
+    color: bool,
+) -> Result<Vec<Box<dyn MessageHandler>>> {
+    let mut handlers: Vec<Box<dyn MessageHandler>> = vec![];
+    for output in outputs {
+        match output {
+            OutputFormat::Pretty | OutputFormat::Debug => match dashboard {
+                Some(dashboard) => handlers.push(Box::new(TuiHandler::new(
+                    dashboard.clone(),
+                    rpc_url.map(str::to_string),
+                    commitment,
+                ))),
+                None => handlers.push(Box::new(LogHandler::new(
+                    rpc_url.map(str::to_string),
+                    commitment,
+                    accounts.clone(),
+                    (*output == OutputFormat::Pretty).then_some(PrettyFormatter { color }),
+                ))),
+            },
+            OutputFormat::Json => handlers.push(Box::new(NdjsonHandler::new(output_file)?)),
+            OutputFormat::Kafka if kafka.is_none() => {
+                bail!("--output kafka needs --kafka-brokers and --kafka-topic")
+            }
+            OutputFormat::Postgres if postgres_url.is_none() => {
+                bail!("--output postgres needs --postgres-url")
+            }
+            // Added below whenever they're configured
+            OutputFormat::Kafka | OutputFormat::Postgres => {}
+            // Written by the StreamConsumer, before anything is decoded
+            OutputFormat::Proto => {}
+        }
+    }
+    if let Some((brokers, topic, options)) = kafka {
+        handlers.push(kafka_sink(&brokers, topic, options)?);
+    }
+    if let Some(url) = postgres_url {
+        handlers.push(postgres_sink(url).await?);
+    }
+    if let Some((dir, format, rotate_after)) = export {
+        handlers.push(Box::new(TransferExporter::new(dir, format, rotate_after)?));
+    }
+    Ok(handlers)
+}
 
-    let init_account2_ix = initialize_account(
-        &spl_token_2022::id(),
-        &token_account2.pubkey(),
-        mint_pubkey,
-        &payer.pubkey(), // Using payer as owner for simplicity
-    )?;
+#[cfg(feature = "kafka")]
+fn kafka_sink(
+    brokers: &str,
+    topic: String,
+    options: KafkaOptions,
+) -> Result<Box<dyn MessageHandler>> {
+    Ok(Box::new(kafka::KafkaSink::new(brokers, topic, options)?))
+}
 
-    // Create and sign transaction
-    let recent_blockhash = client.get_latest_blockhash()?;
-    let tx = Transaction::new_signed_with_payer(
-        &[
-            create_account1_ix,
-            init_account1_ix,
-            create_account2_ix,
-            init_account2_ix,
-        ],
-        Some(&payer.pubkey()),
-        &[payer, &token_account1, &token_account2],
-        recent_blockhash,
-    );
+#[cfg(not(feature = "kafka"))]
+fn kafka_sink(_: &str, _: String, _: KafkaOptions) -> Result<Box<dyn MessageHandler>> {
+    bail!("--kafka-brokers requires vixen-client to be built with the `kafka` feature")
+}
 
-    // Send and confirm transaction
-    let signature = client.send_and_confirm_transaction(&tx)?;
-    info!(
-        "Transaction signature for 2 token account creations: {}",
-        signature
-    );
+#[cfg(feature = "postgres")]
+async fn postgres_sink(url: &str) -> Result<Box<dyn MessageHandler>> {
+    Ok(Box::new(postgres::PostgresSink::connect(url).await?))
+}
 
-    Ok((token_account1.pubkey(), token_account2.pubkey()))
+#[cfg(not(feature = "postgres"))]
+async fn postgres_sink(_: &str) -> Result<Box<dyn MessageHandler>> {
+    bail!("--postgres-url requires vixen-client to be built with the `postgres` feature")
 }
 
-fn mint_to(
-    client: &RpcClient,
-    payer: &Keypair,
-    mint_pubkey: &Pubkey,
-    token_account_pubkey: &Pubkey,
-    amount: u64,
-) -> Result<()> {
-    // Create the mint_to instruction
-    let mint_to_ix = spl_token_2022::instruction::mint_to(
-        &spl_token_2022::id(),
-        mint_pubkey,
-        token_account_pubkey,
-        &payer.pubkey(), // Using payer as authority for simplicity
-        &[],
-        amount,
-    )?;
+type OtlpLayer = Box<dyn Layer<Registry> + Send + Sync>;
 
-    // Create and sign the transaction
-    let recent_blockhash = client.get_latest_blockhash()?;
-    let tx = Transaction::new_signed_with_payer(
-        &[mint_to_ix],
-        Some(&payer.pubkey()),
-        &[payer],
-        recent_blockhash,
-    );
+#[cfg(feature = "otlp")]
+fn otlp_layer(endpoint: Option<&str>) -> Result<Option<(OtlpLayer, otlp::OtlpExporter)>> {
+    let Some(endpoint) = endpoint else {
+        return Ok(None);
+    };
+    let exporter = otlp::OtlpExporter::new(endpoint)?;
+    Ok(Some((exporter.layer().boxed(), exporter)))
+}
 
-    // Send and confirm the transaction
-    let signature = client.send_and_confirm_transaction(&tx)?;
-    info!(
-        "Minted {} tokens to account {} with signature {}",
-        amount_to_ui_amount_string(amount, 6),
-        token_account_pubkey,
-        signature
-    );
+#[cfg(not(feature = "otlp"))]
+fn otlp_layer(endpoint: Option<&str>) -> Result<Option<(OtlpLayer, ())>> {
+    if endpoint.is_some() {
+        bail!("--otlp-endpoint requires vixen-client to be built with the `otlp` feature");
+    }
+    Ok(None)
+}
 
-    Ok(())
+// Decode a stream payload as the proto its type URL names. Types without a
+// decoder under --decode still reach the sinks, as unknown updates.
+fn decode_payload(
+    any: &Any,
+    stream_opts: StreamOptions,
+    decoders: &DecoderRegistry,
+    dead_letters: Option<&mut DeadLetters>,
+    stats: &mut StreamStats,
+) -> Option<ParsedUpdate> {
+    if any.value.len() > stream_opts.max_message_size {
+        stats.oversized += 1;
+        warn!(
+            "Skipping {} byte payload with type URL {:?}, larger than the {} byte limit",
+            any.value.len(),
+            any.type_url,
+            stream_opts.max_message_size
+        );
+        return None;
+    }
+    match decoders.decode(any) {
+        Ok(parsed) if parsed.is_instruction() => {
+            stats.instructions += 1;
+            counter!("vixen_parsed_instructions_total").increment(1);
+            Some(parsed)
+        }
+        Ok(parsed) if parsed.is_state() => {
+            stats.states += 1;
+            counter!("vixen_parsed_states_total").increment(1);
+            Some(parsed)
+        }
+        Ok(parsed) => {
+            stats.undecoded += 1;
+            counter!("vixen_unparseable_messages_total").increment(1);
+            Some(raw_fallback(any, stream_opts, stats).unwrap_or(parsed))
+        }
+        Err(e) => {
+            stats.undecoded += 1;
+            counter!("vixen_unparseable_messages_total").increment(1);
+            stats.decode_failures += 1;
+            counter!("vixen_decode_failures_total").increment(1);
+            warn!(
+                "Update with type URL {:?} ({} bytes) failed to decode as that type: {}",
+                any.type_url,
+                any.value.len(),
+                e
+            );
+            if let Some(dead_letters) = dead_letters {
+                dead_letters.write(any, &e.to_string());
+            }
+            raw_fallback(any, stream_opts, stats)
+        }
+    }
 }
 
-fn fetch_token_balance(client: &RpcClient, token_account_pubkey: &Pubkey) -> Result<u64> {
-    let account_info = client.get_account(token_account_pubkey)?;
-    let token_account = TokenAccount::unpack(&account_info.data)?;
-    Ok(token_account.amount)
+fn raw_fallback(
+    any: &Any,
+    stream_opts: StreamOptions,
+    stats: &mut StreamStats,
+) -> Option<ParsedUpdate> {
+    if !stream_opts.raw_account_fallback {
+        return None;
+    }
+    let parsed = unpack_token_state(&any.value)?;
+    stats.raw_fallbacks += 1;
+    counter!("vixen_raw_fallbacks_total").increment(1);
+    debug!(
+        "Unpacked the {} byte payload with type URL {:?} as raw account bytes",
+        any.value.len(),
+        any.type_url
+    );
+    Some(parsed)
+}
+
+fn panic_message(panic: &(dyn PanicPayload + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic payload"
+    }
 }
@@ -0,0 +1,529 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use serde::Serialize;
+use tracing::info;
+use yellowstone_vixen_proto::parser::{
+    token_extension_program_ix_proto::IxOneof, token_extension_state_proto::StateOneof,
+    token_program_ix_proto::IxOneof as TokenIx,
+    token_program_state_proto::StateOneof as TokenStateOneof,
+    transfer_fee_ix_proto::IxOneof as TransferFeeIx, ExtendedMintProto, MintProto,
+    TokenExtensionProgramIxProto, TokenExtensionStateProto, TokenProgramStateProto,
+    TransferFeeIxProto,
+};
+
+use crate::{amount::UiAmount, subscriber::ParsedUpdate};
+
+// What the stream has shown of one mint since subscribing, amounts in base
+// units
+#[derive(Clone, Default, Serialize)]
+pub struct MintStats {
+    // From the checked instructions and the mint's own updates
+    pub decimals: Option<u8>,
+    // The latest supply off the mint's state update, once one can be told
+    // to be this mint's
+    pub supply: Option<u64>,
+    pub minted: u64,
+    pub burned: u64,
+    pub holders: usize,
+    pub transfers: u64,
+    pub volume: u64,
+}
+
+impl MintStats {
+    fn amount(&self, amount: u64) -> String {
+        match self.decimals {
+            Some(decimals) => UiAmount::from_base_units(amount, decimals).to_string(),
+            None => format!("{} base units", amount),
+        }
+    }
+}
+
+// What the stream has shown of one token account since subscribing, amounts
+// in base units. Its address only ever appears in instructions, so what's
+// known depends on which of them went by.
+#[derive(Clone, Default, Serialize)]
+pub struct AccountStats {
+    pub mint: Option<String>,
+    // Off the instruction that initialized it
+    pub owner: Option<String>,
+    // The latest amount off the account's state update, once one can be
+    // told to be this account's
+    pub balance: Option<u64>,
+    pub transfers: u64,
+    pub sent: u64,
+    pub received: u64,
+    pub closed: bool,
+}
+
+// A checked transfer as it went by on the stream
+#[derive(Clone, Serialize)]
+pub struct TransferRecord {
+    pub received_at: String,
+    pub mint: String,
+    pub source: String,
+    pub destination: String,
+    pub amount: u64,
+    pub decimals: u64,
+}
+
+// Enough for a look at what just happened, the aggregates cover the rest
+const RECENT_TRANSFERS: usize = 1_000;
+
+#[derive(Default)]
+struct AggregatorState {
+    mints: BTreeMap<String, MintStats>,
+    accounts: HashMap<String, AccountStats>,
+    // The last RECENT_TRANSFERS transfers, oldest first
+    transfers: VecDeque<TransferRecord>,
+    // The accounts each owner was seen initializing for each mint, to tell
+    // which a token account update belongs to
+    initialized: HashMap<(String, String), HashSet<String>>,
+    // The latest balance of every owner's account for each mint. Token
+    // account updates don't name their account, so an owner with several
+    // accounts for a mint counts as holding whatever the last one holds.
+    balances: HashMap<(String, String), u64>,
+    // The mints each authority was seen minting. A mint's state update
+    // doesn't name the mint either, so it's matched by its mint authority,
+    // and only when that authority mints nothing else.
+    minted_by: HashMap<String, HashSet<String>>,
+}
+
+impl AggregatorState {
+    fn mint(&mut self, mint: &str) -> &mut MintStats {
+        self.mints.entry(mint.to_string()).or_default()
+    }
+
+    fn minted(&mut self, mint: &str, authority: &str, amount: u64, decimals: Option<u64>) {
+        self.minted_by
+            .entry(authority.to_string())
+            .or_default()
+            .insert(mint.to_string());
+        let stats = self.mint(mint);
+        stats.minted = stats.minted.saturating_add(amount);
+        stats.decimals = decimals
+            .and_then(|d| u8::try_from(d).ok())
+            .or(stats.decimals);
+    }
+
+    fn burned(&mut self, mint: &str, amount: u64, decimals: Option<u64>) {
+        let stats = self.mint(mint);
+        stats.burned = stats.burned.saturating_add(amount);
+        stats.decimals = decimals
+            .and_then(|d| u8::try_from(d).ok())
+            .or(stats.decimals);
+    }
+
+    fn account(&mut self, account: &str, mint: &str) -> &mut AccountStats {
+        let stats = self.accounts.entry(account.to_string()).or_default();
+        stats.mint.get_or_insert_with(|| mint.to_string());
+        stats
+    }
+
+    fn initialized(&mut self, account: &str, mint: &str, owner: &str) {
+        self.initialized
+            .entry((mint.to_string(), owner.to_string()))
+            .or_default()
+            .insert(account.to_string());
+        let stats = self.account(account, mint);
+        stats.owner = Some(owner.to_string());
+        stats.closed = false;
+    }
+
+    fn transferred(
+        &mut self,
+        mint: &str,
+        source: &str,
+        destination: &str,
+        amount: u64,
+        decimals: u64,
+    ) {
+        let stats = self.mint(mint);
+        stats.transfers += 1;
+        stats.volume = stats.volume.saturating_add(amount);
+        stats.decimals = u8::try_from(decimals).ok().or(stats.decimals);
+        let sender = self.account(source, mint);
+        sender.transfers += 1;
+        sender.sent = sender.sent.saturating_add(amount);
+        let recipient = self.account(destination, mint);
+        recipient.transfers += 1;
+        recipient.received = recipient.received.saturating_add(amount);
+        if self.transfers.len() == RECENT_TRANSFERS {
+            self.transfers.pop_front();
+        }
+        self.transfers.push_back(TransferRecord {
+            received_at: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            mint: mint.to_string(),
+            source: source.to_string(),
+            destination: destination.to_string(),
+            amount,
+            decimals,
+        });
+    }
+
+    fn mint_state(&mut self, state: &MintProto) {
+        let Some(authority) = &state.mint_authority else {
+            return;
+        };
+        let Some(mints) = self
+            .minted_by
+            .get(authority)
+            .filter(|mints| mints.len() == 1)
+        else {
+            return;
+        };
+        let mint = mints.iter().next().unwrap().clone();
+        let stats = self.mint(&mint);
+        let decimals = u8::try_from(state.decimals).ok();
+        if stats.decimals.is_some_and(|known| Some(known) != decimals) {
+            return;
+        }
+        stats.decimals = decimals;
+        stats.supply = Some(state.supply);
+    }
+
+    fn balance(&mut self, mint: &str, owner: &str, amount: u64) {
+        let key = (mint.to_string(), owner.to_string());
+        // The update is the account's when its owner initialized no other
+        // for the mint
+        if let Some(accounts) = self.initialized.get(&key).filter(|a| a.len() == 1) {
+            let account = accounts.iter().next().unwrap().clone();
+            self.account(&account, mint).balance = Some(amount);
+        }
+        let held = |amount: Option<u64>| amount.is_some_and(|amount| amount > 0);
+        let before = held(self.balances.insert(key, amount));
+        let stats = self.mint(mint);
+        match (before, amount > 0) {
+            (false, true) => stats.holders += 1,
+            (true, false) => stats.holders -= 1,
+            _ => {}
+        }
+    }
+}
+
+// Circulating supply, holders and transfer volume for every mint on the
+// stream, derived from nothing but its updates: minting, burning and
+// transfers from the instructions, holders from the token account updates
+// and supply from the mint's own. Transfers without the mint, the unchecked
+// `transfer`, aren't counted. The token accounts named by those instructions
+// and the latest transfers are kept too, to be looked up by address. Clones
+// share their state, one goes to the stream and the others to whatever
+// reports it.
+#[derive(Clone, Default)]
+pub struct MintAggregator {
+    state: Arc<Mutex<AggregatorState>>,
+}
+
+impl MintAggregator {
+    pub fn observe(&self, update: &ParsedUpdate) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(account) = update.token_account() {
+            state.balance(&account.mint, &account.owner, account.amount);
+            return;
+        }
+        if let Some(mint) = mint_state(update) {
+            state.mint_state(mint);
+            return;
+        }
+        match update.token_instruction() {
+            Some(TokenIx::MintTo(ix)) => {
+                if let (Some(accounts), Some(data)) = (&ix.accounts, &ix.data) {
+                    state.minted(&accounts.mint, &accounts.mint_authority, data.amount, None);
+                }
+            }
+            Some(TokenIx::MintToChecked(ix)) => {
+                if let (Some(accounts), Some(data)) = (&ix.accounts, &ix.data) {
+                    state.minted(
+                        &accounts.mint,
+                        &accounts.mint_authority,
+                        data.amount,
+                        Some(data.decimals),
+                    );
+                }
+            }
+            Some(TokenIx::Burn(ix)) => {
+                if let (Some(accounts), Some(data)) = (&ix.accounts, &ix.data) {
+                    state.burned(&accounts.mint, data.amount, None);
+                }
+            }
+            Some(TokenIx::BurnChecked(ix)) => {
+                if let (Some(accounts), Some(data)) = (&ix.accounts, &ix.data) {
+                    state.burned(&accounts.mint, data.amount, Some(data.decimals));
+                }
+            }
+            Some(TokenIx::TransferChecked(ix)) => {
+                if let (Some(accounts), Some(data)) = (&ix.accounts, &ix.data) {
+                    state.transferred(
+                        &accounts.mint,
+                        &accounts.source,
+                        &accounts.destination,
+                        data.amount,
+                        data.decimals,
+                    );
+                }
+            }
+            Some(TokenIx::InitializeAccount(ix)) => {
+                if let Some(accounts) = &ix.accounts {
+                    state.initialized(&accounts.account, &accounts.mint, &accounts.owner);
+                }
+            }
+            Some(TokenIx::InitializeAccount2(ix)) => {
+                if let (Some(accounts), Some(data)) = (&ix.accounts, &ix.data) {
+                    state.initialized(&accounts.account, &accounts.mint, &data.owner);
+                }
+            }
+            Some(TokenIx::InitializeAccount3(ix)) => {
+                if let (Some(accounts), Some(data)) = (&ix.accounts, &ix.data) {
+                    state.initialized(&accounts.account, &accounts.mint, &data.owner);
+                }
+            }
+            Some(TokenIx::CloseAccount(ix)) => {
+                if let Some(stats) = ix
+                    .accounts
+                    .as_ref()
+                    .and_then(|accounts| state.accounts.get_mut(&accounts.account))
+                {
+                    stats.closed = true;
+                    stats.balance = Some(0);
+                }
+            }
+            Some(_) => {}
+            None => {
+                if let ParsedUpdate::Instruction(TokenExtensionProgramIxProto {
+                    ix_oneof:
+                        Some(IxOneof::TransferFeeIx(TransferFeeIxProto {
+                            ix_oneof: Some(TransferFeeIx::TransferCheckedWithFeeIx(ix)),
+                        })),
+                }) = update
+                {
+                    if let (Some(accounts), Some(data)) = (&ix.accounts, &ix.data) {
+                        state.transferred(
+                            &accounts.mint,
+                            &accounts.source,
+                            &accounts.destination,
+                            data.amount,
+                            data.decimals,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // Every mint seen so far, by address
+    pub fn snapshot(&self) -> BTreeMap<String, MintStats> {
+        self.state.lock().unwrap().mints.clone()
+    }
+
+    pub fn mint(&self, mint: &str) -> Option<MintStats> {
+        self.state.lock().unwrap().mints.get(mint).cloned()
+    }
+
+    pub fn account(&self, account: &str) -> Option<AccountStats> {
+        self.state.lock().unwrap().accounts.get(account).cloned()
+    }
+
+    // Up to `limit` of the latest transfers, newest first, only those of
+    // `mint` when given
+    pub fn transfers(&self, mint: Option<&str>, limit: usize) -> Vec<TransferRecord> {
+        let state = self.state.lock().unwrap();
+        state
+            .transfers
+            .iter()
+            .rev()
+            .filter(|transfer| mint.is_none_or(|mint| transfer.mint == mint))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    pub fn log(&self) {
+        for (mint, stats) in self.snapshot() {
+            info!(
+                "Mint {}: supply {}, {} holder(s), {} in {} transfer(s), {} minted and {} burned",
+                mint,
+                stats
+                    .supply
+                    .map_or_else(|| "unknown".to_string(), |supply| stats.amount(supply)),
+                stats.holders,
+                stats.amount(stats.volume),
+                stats.transfers,
+                stats.amount(stats.minted),
+                stats.amount(stats.burned)
+            );
+        }
+    }
+}
+
+fn mint_state(update: &ParsedUpdate) -> Option<&MintProto> {
+    match update {
+        ParsedUpdate::State(TokenExtensionStateProto {
+            state_oneof:
+                Some(StateOneof::ExtendedMintAccount(ExtendedMintProto {
+                    base_mint: Some(mint),
+                    ..
+                })),
+        })
+        | ParsedUpdate::LegacyState(TokenProgramStateProto {
+            state_oneof: Some(TokenStateOneof::Mint(mint)),
+        }) => Some(mint),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yellowstone_vixen_proto::parser::{
+        token_extension_state_proto, BurnAccountsProto, BurnDataProto, BurnIxProto,
+        ExtendedTokenAccountProto, InitializeAccount2AccountsProto, InitializeAccount3IxProto,
+        InitializeAccountData2Proto, MintToAccountsProto, MintToDataProto, MintToIxProto,
+        TokenAccountProto, TokenProgramIxProto, TransferCheckedAccountsProto,
+        TransferCheckedDataProto, TransferCheckedIxProto,
+    };
+
+    use super::*;
+
+    fn instruction(ix: TokenIx) -> ParsedUpdate {
+        ParsedUpdate::Instruction(TokenExtensionProgramIxProto {
+            ix_oneof: Some(IxOneof::TokenProgramIx(TokenProgramIxProto {
+                ix_oneof: Some(ix),
+            })),
+        })
+    }
+
+    fn mint_to(mint: &str, authority: &str, amount: u64) -> ParsedUpdate {
+        instruction(TokenIx::MintTo(MintToIxProto {
+            accounts: Some(MintToAccountsProto {
+                mint: mint.to_string(),
+                mint_authority: authority.to_string(),
+                ..Default::default()
+            }),
+            data: Some(MintToDataProto { amount }),
+        }))
+    }
+
+    fn account(mint: &str, owner: &str, amount: u64) -> ParsedUpdate {
+        ParsedUpdate::State(TokenExtensionStateProto {
+            state_oneof: Some(
+                token_extension_state_proto::StateOneof::ExtendedTokenAccount(
+                    ExtendedTokenAccountProto {
+                        base_account: Some(TokenAccountProto {
+                            mint: mint.to_string(),
+                            owner: owner.to_string(),
+                            amount,
+                            ..Default::default()
+                        }),
+                        extension_data_vec: vec![],
+                    },
+                ),
+            ),
+        })
+    }
+
+    fn mint_account(authority: &str, supply: u64) -> ParsedUpdate {
+        ParsedUpdate::LegacyState(TokenProgramStateProto {
+            state_oneof: Some(TokenStateOneof::Mint(MintProto {
+                mint_authority: Some(authority.to_string()),
+                supply,
+                decimals: 6,
+                ..Default::default()
+            })),
+        })
+    }
+
+    fn transfer(mint: &str, source: &str, destination: &str, amount: u64) -> ParsedUpdate {
+        instruction(TokenIx::TransferChecked(TransferCheckedIxProto {
+            accounts: Some(TransferCheckedAccountsProto {
+                mint: mint.to_string(),
+                source: source.to_string(),
+                destination: destination.to_string(),
+                ..Default::default()
+            }),
+            data: Some(TransferCheckedDataProto {
+                amount,
+                decimals: 6,
+            }),
+        }))
+    }
+
+    fn initialize(account: &str, mint: &str, owner: &str) -> ParsedUpdate {
+        instruction(TokenIx::InitializeAccount3(InitializeAccount3IxProto {
+            accounts: Some(InitializeAccount2AccountsProto {
+                account: account.to_string(),
+                mint: mint.to_string(),
+            }),
+            data: Some(InitializeAccountData2Proto {
+                owner: owner.to_string(),
+            }),
+        }))
+    }
+
+    #[test]
+    fn aggregates_supply_holders_and_volume_per_mint() {
+        let aggregator = MintAggregator::default();
+        aggregator.observe(&mint_to("mint", "authority", 100));
+        aggregator.observe(&mint_account("authority", 100));
+        aggregator.observe(&account("mint", "alice", 100));
+        aggregator.observe(&transfer("mint", "a", "b", 40));
+        aggregator.observe(&account("mint", "alice", 60));
+        aggregator.observe(&account("mint", "bob", 40));
+        aggregator.observe(&instruction(TokenIx::Burn(BurnIxProto {
+            accounts: Some(BurnAccountsProto {
+                mint: "mint".to_string(),
+                ..Default::default()
+            }),
+            data: Some(BurnDataProto { amount: 60 }),
+        })));
+        aggregator.observe(&account("mint", "alice", 0));
+        aggregator.observe(&mint_account("authority", 40));
+
+        let stats = &aggregator.snapshot()["mint"];
+        assert_eq!(stats.decimals, Some(6));
+        assert_eq!(stats.supply, Some(40));
+        assert_eq!((stats.minted, stats.burned), (100, 60));
+        assert_eq!(stats.holders, 1);
+        assert_eq!((stats.transfers, stats.volume), (1, 40));
+    }
+
+    #[test]
+    fn leaves_supply_unknown_when_the_authority_mints_several() {
+        let aggregator = MintAggregator::default();
+        aggregator.observe(&mint_to("a", "authority", 10));
+        aggregator.observe(&mint_to("b", "authority", 10));
+        aggregator.observe(&mint_account("authority", 10));
+        let mints = aggregator.snapshot();
+        assert_eq!(mints["a"].supply, None);
+        assert_eq!(mints["b"].supply, None);
+    }
+
+    #[test]
+    fn tracks_accounts_and_recent_transfers_by_address() {
+        let aggregator = MintAggregator::default();
+        aggregator.observe(&initialize("a", "mint", "alice"));
+        aggregator.observe(&initialize("b", "mint", "bob"));
+        aggregator.observe(&initialize("c", "mint", "bob"));
+        aggregator.observe(&transfer("mint", "a", "b", 40));
+        aggregator.observe(&transfer("other", "x", "y", 5));
+        aggregator.observe(&transfer("mint", "a", "c", 10));
+        aggregator.observe(&account("mint", "alice", 50));
+        // Bob has two accounts for the mint, so his update can't be placed
+        aggregator.observe(&account("mint", "bob", 40));
+
+        let a = aggregator.account("a").unwrap();
+        assert_eq!(a.owner.as_deref(), Some("alice"));
+        assert_eq!(a.balance, Some(50));
+        assert_eq!((a.transfers, a.sent, a.received), (2, 50, 0));
+        let b = aggregator.account("b").unwrap();
+        assert_eq!(b.balance, None);
+        assert_eq!((b.transfers, b.received), (1, 40));
+        assert!(aggregator.account("z").is_none());
+
+        let transfers = aggregator.transfers(Some("mint"), 10);
+        let amounts: Vec<u64> = transfers.iter().map(|t| t.amount).collect();
+        assert_eq!(amounts, [10, 40]);
+        assert_eq!(aggregator.transfers(None, 2).len(), 2);
+    }
+}
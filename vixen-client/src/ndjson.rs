@@ -0,0 +1,74 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use color_eyre::Result;
+use solana_sdk::pubkey::Pubkey;
+use svmkit_vixen_demo::{json::update_record, subscriber::ParsedUpdate};
+
+use crate::sink::MessageHandler;
+
+// Often enough for `tail -f` to keep up without a write syscall per update
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+type SharedWriter = Arc<Mutex<BufWriter<Box<dyn Write + Send>>>>;
+
+// Writes each update as one JSON object per line, to stdout or a file
+pub struct NdjsonHandler {
+    writer: SharedWriter,
+}
+
+impl NdjsonHandler {
+    pub fn new(path: Option<&Path>) -> Result<Self> {
+        let writer: Box<dyn Write + Send> = match path {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(io::stdout()),
+        };
+        let writer = Arc::new(Mutex::new(BufWriter::new(writer)));
+        // Flush on a timer rather than after N records, so the tail of a burst
+        // shows up even when the stream goes quiet
+        let flushed = Arc::downgrade(&writer);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(writer) = flushed.upgrade() else {
+                    break;
+                };
+                writer.lock().unwrap().flush().ok();
+            }
+        });
+        Ok(Self { writer })
+    }
+}
+
+#[async_trait]
+impl MessageHandler for NdjsonHandler {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    async fn handle(&mut self, program: &Pubkey, update: &ParsedUpdate) -> Result<()> {
+        let record = update_record(update, program)?;
+        let mut writer = self.writer.lock().unwrap();
+        serde_json::to_writer(&mut *writer, &record)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.writer.lock().unwrap().flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for NdjsonHandler {
+    fn drop(&mut self) {
+        self.writer.lock().unwrap().flush().ok();
+    }
+}
@@ -0,0 +1,45 @@
+use color_eyre::{eyre::WrapErr, Result};
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig as _};
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use tracing::{warn, Subscriber};
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+const SERVICE_NAME: &str = "vixen-client";
+
+// Sends every span to an OTLP collector over gRPC, batched in the
+// background. Dropping it flushes the spans still buffered.
+pub struct OtlpExporter {
+    provider: TracerProvider,
+}
+
+impl OtlpExporter {
+    pub fn new(endpoint: &str) -> Result<Self> {
+        let exporter = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()
+            .wrap_err("Unable to set up the OTLP exporter")?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_resource(Resource::new([KeyValue::new("service.name", SERVICE_NAME)]))
+            .build();
+        Ok(Self { provider })
+    }
+
+    pub fn layer<S>(&self) -> impl Layer<S>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer().with_tracer(self.provider.tracer(SERVICE_NAME))
+    }
+}
+
+// The collector being unreachable only costs a warning
+impl Drop for OtlpExporter {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            warn!("Unable to flush the spans to the OTLP collector: {}", e);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use metrics::{counter, gauge, Counter, Gauge};
+use tokio::sync::Notify;
+
+// What a full queue does with one more update
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Overflow {
+    /// Wait for room, holding the gRPC stream back until the consumer catches
+    /// up
+    Block,
+    /// Drop the oldest queued update to make room, so a slow consumer loses
+    /// updates rather than stalling the stream
+    DropOldest,
+}
+
+impl Overflow {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Overflow::Block => "block",
+            Overflow::DropOldest => "drop-oldest",
+        }
+    }
+}
+
+// How every queue between the stream and the sinks is sized and what it does
+// once full
+#[derive(Clone, Copy)]
+pub struct PipelineOptions {
+    pub capacity: usize,
+    pub overflow: Overflow,
+}
+
+struct QueueState<T> {
+    items: VecDeque<T>,
+    senders: usize,
+    receiver_alive: bool,
+    dropped: u64,
+}
+
+struct Shared<T> {
+    state: Mutex<QueueState<T>>,
+    // Wakes the receiver when an item arrives or the last sender goes away
+    readable: Notify,
+    // Wakes blocked senders when an item is taken or the receiver goes away
+    writable: Notify,
+    opts: PipelineOptions,
+    // Items that may be dropped under Overflow::DropOldest; anything else,
+    // e.g. a reconnection, is always delivered even if it overfills the queue
+    droppable: fn(&T) -> bool,
+    depth: Gauge,
+    drops: Counter,
+}
+
+pub struct QueueSender<T>(Arc<Shared<T>>);
+
+pub struct QueueReceiver<T>(Arc<Shared<T>>);
+
+// A bounded multi-producer, single-consumer queue. Unlike a channel it can
+// make room by dropping its oldest item, which a sender can't do to a channel.
+// `stage` labels its depth and drop metrics.
+pub fn queue<T>(
+    stage: &str,
+    opts: PipelineOptions,
+    droppable: fn(&T) -> bool,
+) -> (QueueSender<T>, QueueReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(QueueState {
+            items: VecDeque::with_capacity(opts.capacity),
+            senders: 1,
+            receiver_alive: true,
+            dropped: 0,
+        }),
+        readable: Notify::new(),
+        writable: Notify::new(),
+        opts,
+        droppable,
+        depth: gauge!("vixen_pipeline_queued", "stage" => stage.to_string()),
+        drops: counter!("vixen_pipeline_dropped_total", "stage" => stage.to_string()),
+    });
+    (QueueSender(shared.clone()), QueueReceiver(shared))
+}
+
+impl<T> QueueSender<T> {
+    // Hands the item back once the receiver has gone away
+    pub async fn send(&self, item: T) -> Result<(), T> {
+        let shared = &*self.0;
+        loop {
+            // Registered before checking, so a wakeup between the check and
+            // the await isn't missed
+            let writable = shared.writable.notified();
+            {
+                let mut state = shared.state.lock().unwrap();
+                if !state.receiver_alive {
+                    return Err(item);
+                }
+                let full = state.items.len() >= shared.opts.capacity;
+                if full && shared.opts.overflow == Overflow::DropOldest {
+                    if let Some(oldest) = state.items.iter().position(shared.droppable) {
+                        state.items.remove(oldest);
+                        state.dropped += 1;
+                        shared.drops.increment(1);
+                    }
+                }
+                if !full
+                    || shared.opts.overflow == Overflow::DropOldest
+                    || !(shared.droppable)(&item)
+                {
+                    state.items.push_back(item);
+                    shared.depth.set(state.items.len() as f64);
+                    shared.readable.notify_one();
+                    return Ok(());
+                }
+            }
+            writable.await;
+        }
+    }
+}
+
+impl<T> Clone for QueueSender<T> {
+    fn clone(&self) -> Self {
+        self.0.state.lock().unwrap().senders += 1;
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Drop for QueueSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.0.readable.notify_one();
+        }
+    }
+}
+
+impl<T> QueueReceiver<T> {
+    // None once every sender has gone away and the queue is drained
+    pub async fn recv(&mut self) -> Option<T> {
+        let shared = &*self.0;
+        loop {
+            let readable = shared.readable.notified();
+            {
+                let mut state = shared.state.lock().unwrap();
+                if let Some(item) = state.items.pop_front() {
+                    shared.depth.set(state.items.len() as f64);
+                    shared.writable.notify_one();
+                    return Some(item);
+                }
+                if state.senders == 0 {
+                    return None;
+                }
+            }
+            readable.await;
+        }
+    }
+
+    // Items dropped to make room so far
+    pub fn dropped(&self) -> u64 {
+        self.0.state.lock().unwrap().dropped
+    }
+}
+
+impl<T> Drop for QueueReceiver<T> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().receiver_alive = false;
+        self.0.writable.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn opts(capacity: usize, overflow: Overflow) -> PipelineOptions {
+        PipelineOptions { capacity, overflow }
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_the_newest_items() {
+        let (tx, mut rx) = queue("test", opts(2, Overflow::DropOldest), |_: &u32| true);
+        for i in 0..5 {
+            tx.send(i).await.unwrap();
+        }
+        drop(tx);
+        assert_eq!(rx.recv().await, Some(3));
+        assert_eq!(rx.recv().await, Some(4));
+        assert_eq!(rx.recv().await, None);
+        assert_eq!(rx.dropped(), 3);
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_never_drops_undroppable_items() {
+        // Odd numbers stand in for reconnections
+        let (tx, mut rx) = queue("test", opts(2, Overflow::DropOldest), |i: &u32| {
+            i.is_multiple_of(2)
+        });
+        for i in [1, 2, 3, 4] {
+            tx.send(i).await.unwrap();
+        }
+        drop(tx);
+        let mut received = vec![];
+        while let Some(i) = rx.recv().await {
+            received.push(i);
+        }
+        assert_eq!(received, [1, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_room() {
+        let (tx, mut rx) = queue("test", opts(1, Overflow::Block), |_: &u32| true);
+        tx.send(0).await.unwrap();
+        let blocked = tokio::spawn(async move { tx.send(1).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!blocked.is_finished());
+        assert_eq!(rx.recv().await, Some(0));
+        blocked.await.unwrap().unwrap();
+        assert_eq!(rx.recv().await, Some(1));
+        assert_eq!(rx.recv().await, None);
+        assert_eq!(rx.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn send_fails_once_the_receiver_is_gone() {
+        let (tx, rx) = queue("test", opts(1, Overflow::Block), |_: &u32| true);
+        tx.send(0).await.unwrap();
+        let blocked = tokio::spawn(async move { tx.send(1).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(rx);
+        assert_eq!(blocked.await.unwrap(), Err(1));
+    }
+}
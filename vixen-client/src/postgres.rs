@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use color_eyre::Result;
+use solana_sdk::pubkey::Pubkey;
+use sqlx::{postgres::PgPoolOptions, types::Json, PgPool};
+use svmkit_vixen_demo::subscriber::ParsedUpdate;
+use tracing::{debug, info};
+
+use crate::{filter::variant_name, sink::MessageHandler};
+
+// A handful is plenty, updates are written one at a time
+const MAX_CONNECTIONS: u32 = 4;
+
+// Writes instructions and account states to their own tables, running the
+// migrations under `migrations/` first. Updates of an unknown type have no
+// table and are skipped.
+pub struct PostgresSink {
+    pool: PgPool,
+}
+
+impl PostgresSink {
+    pub async fn connect(url: &str) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .connect(url)
+            .await?;
+        sqlx::migrate!().run(&pool).await?;
+        info!("Writing parsed updates to PostgreSQL");
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait]
+impl MessageHandler for PostgresSink {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    async fn handle(&mut self, program: &Pubkey, update: &ParsedUpdate) -> Result<()> {
+        let query = match update {
+            ParsedUpdate::Instruction(_) | ParsedUpdate::LegacyInstruction(_) => {
+                "INSERT INTO instructions (slot, signature, program, instruction_type, payload) \
+                 VALUES ($1, $2, $3, $4, $5)"
+            }
+            ParsedUpdate::State(_) | ParsedUpdate::LegacyState(_) => {
+                "INSERT INTO account_states (slot, signature, program, state_type, payload) \
+                 VALUES ($1, $2, $3, $4, $5)"
+            }
+            ParsedUpdate::Custom(custom) => {
+                debug!("Not writing {} update to PostgreSQL", custom.type_name());
+                return Ok(());
+            }
+            ParsedUpdate::Unknown(any) => {
+                debug!("Not writing {} update to PostgreSQL", any.type_url);
+                return Ok(());
+            }
+        };
+        // The stream doesn't carry the slot or signature yet
+        sqlx::query(query)
+            .bind(None::<i64>)
+            .bind(None::<String>)
+            .bind(program.to_string())
+            .bind(variant_name(update).unwrap_or_else(|| update.type_name()))
+            .bind(Json(update.to_json()?))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.pool.close().await;
+        Ok(())
+    }
+}
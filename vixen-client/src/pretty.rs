@@ -0,0 +1,181 @@
+use prost_reflect::{DynamicMessage, Value};
+use ratatui::crossterm::style::{Color, Stylize as _};
+
+use svmkit_vixen_demo::{amount::UiAmount, subscriber::ParsedUpdate};
+
+use crate::tui::short;
+
+// An instruction as one short line for --output pretty, e.g.
+// `TRANSFER_CHECKED 1.5 source=Aq3x…9fPk mint=… destination=… owner=…`: the
+// innermost variant, the amount in tokens when the decimals are known, then
+// the accounts with their addresses cut short. There's no slot to show, the
+// stream doesn't carry one. Colored only when the log goes to a terminal.
+#[derive(Clone, Copy)]
+pub struct PrettyFormatter {
+    pub color: bool,
+}
+
+impl PrettyFormatter {
+    // None for anything but an instruction, or one without its accounts
+    pub fn instruction(&self, update: &ParsedUpdate, mint_decimals: Option<u8>) -> Option<String> {
+        if !update.is_instruction() {
+            return None;
+        }
+        let (name, ix) = innermost(update.to_dynamic()?.ok()?)?;
+        let accounts = field_message(&ix, "accounts")?;
+        let data = field_message(&ix, "data");
+        let mut line = self.label(&name);
+        if let Some(amount) = data.as_ref().and_then(|data| field_u64(data, "amount")) {
+            let decimals = data
+                .as_ref()
+                .and_then(|data| field_u64(data, "decimals"))
+                .and_then(|decimals| u8::try_from(decimals).ok())
+                .or(mint_decimals);
+            let amount = match decimals {
+                Some(decimals) => UiAmount::from_base_units(amount, decimals).to_string(),
+                None => format!("{} base units", amount),
+            };
+            line.push(' ');
+            line.push_str(&self.paint(amount, |s| s.bold().to_string()));
+        }
+        for (field, value) in accounts.fields() {
+            if let Value::String(key) = value {
+                line.push_str(&format!(
+                    " {}{}",
+                    self.paint(format!("{}=", field.name()), |s| s.dim().to_string()),
+                    short(key)
+                ));
+            }
+        }
+        if let Some(fee) = data.as_ref().and_then(|data| field_u64(data, "fee")) {
+            line.push_str(&format!(
+                " {}{}",
+                self.paint("fee=".to_string(), |s| s.dim().to_string()),
+                fee
+            ));
+        }
+        Some(line)
+    }
+
+    // The mint an instruction names, to look its decimals up when the
+    // instruction doesn't carry them
+    pub fn mint(update: &ParsedUpdate) -> Option<String> {
+        let (_, ix) = innermost(update.to_dynamic()?.ok()?)?;
+        match field_message(&ix, "accounts")?
+            .get_field_by_name("mint")?
+            .as_ref()
+        {
+            Value::String(mint) if !mint.is_empty() => Some(mint.clone()),
+            _ => None,
+        }
+    }
+
+    fn label(&self, name: &str) -> String {
+        let label = name.trim_end_matches("_ix").to_uppercase();
+        let color = if label.starts_with("TRANSFER") {
+            Color::Green
+        } else if label.starts_with("MINT") {
+            Color::Cyan
+        } else if label.starts_with("BURN") || label.starts_with("CLOSE") {
+            Color::Red
+        } else {
+            Color::Yellow
+        };
+        self.paint(label, |s| s.with(color).bold().to_string())
+    }
+
+    fn paint(&self, text: String, style: impl FnOnce(String) -> String) -> String {
+        if self.color {
+            style(text)
+        } else {
+            text
+        }
+    }
+}
+
+// Follow the set oneof variants down to the instruction itself, with the
+// name of the last one
+fn innermost(mut message: DynamicMessage) -> Option<(String, DynamicMessage)> {
+    let mut name = None;
+    loop {
+        let variant = message
+            .fields()
+            .find(|(field, _)| field.containing_oneof().is_some())
+            .map(|(field, value)| (field.name().to_string(), value.as_message().cloned()));
+        match variant {
+            Some((variant, Some(inner))) => {
+                name = Some(variant);
+                message = inner;
+            }
+            _ => return Some((name?, message)),
+        }
+    }
+}
+
+fn field_message(message: &DynamicMessage, name: &str) -> Option<DynamicMessage> {
+    message
+        .get_field_by_name(name)
+        .and_then(|value| value.as_message().cloned())
+        .filter(|_| message.has_field_by_name(name))
+}
+
+fn field_u64(message: &DynamicMessage, name: &str) -> Option<u64> {
+    message.get_field_by_name(name)?.as_u64()
+}
+
+#[cfg(test)]
+mod tests {
+    use yellowstone_vixen_proto::parser::{
+        token_program_ix_proto::IxOneof as TokenIx, MintToAccountsProto, MintToDataProto,
+        MintToIxProto, TokenProgramIxProto, TransferCheckedAccountsProto, TransferCheckedDataProto,
+        TransferCheckedIxProto,
+    };
+
+    use super::*;
+
+    const PLAIN: PrettyFormatter = PrettyFormatter { color: false };
+
+    #[test]
+    fn formats_a_transfer_as_one_line() {
+        let update = ParsedUpdate::LegacyInstruction(TokenProgramIxProto {
+            ix_oneof: Some(TokenIx::TransferChecked(TransferCheckedIxProto {
+                accounts: Some(TransferCheckedAccountsProto {
+                    source: "So11111111111111111111111111111111111111112".to_string(),
+                    mint: "Mint111111111111111111111111111111111111111".to_string(),
+                    destination: "Dest111111111111111111111111111111111111111".to_string(),
+                    owner: "owner".to_string(),
+                    multisig_signers: vec![],
+                }),
+                data: Some(TransferCheckedDataProto {
+                    amount: 1_500_000,
+                    decimals: 6,
+                }),
+            })),
+        });
+        assert_eq!(
+            PLAIN.instruction(&update, None).unwrap(),
+            "TRANSFER_CHECKED 1.5 source=So11…1112 mint=Mint…1111 destination=Dest…1111 \
+             owner=owner"
+        );
+    }
+
+    #[test]
+    fn takes_the_mint_decimals_when_the_instruction_has_none() {
+        let update = ParsedUpdate::LegacyInstruction(TokenProgramIxProto {
+            ix_oneof: Some(TokenIx::MintTo(MintToIxProto {
+                accounts: Some(MintToAccountsProto {
+                    mint: "mint".to_string(),
+                    account: "account".to_string(),
+                    mint_authority: "authority".to_string(),
+                    multisig_signers: vec![],
+                }),
+                data: Some(MintToDataProto { amount: 2_000 }),
+            })),
+        });
+        assert_eq!(PrettyFormatter::mint(&update).as_deref(), Some("mint"));
+        let line = PLAIN.instruction(&update, Some(3)).unwrap();
+        assert!(line.starts_with("MINT_TO 2 mint=mint"), "{}", line);
+        let line = PLAIN.instruction(&update, None).unwrap();
+        assert!(line.starts_with("MINT_TO 2000 base units"), "{}", line);
+    }
+}
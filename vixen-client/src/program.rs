@@ -0,0 +1,84 @@
+use std::str::FromStr;
+
+use solana_sdk::{pubkey, pubkey::Pubkey, system_program};
+
+pub const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+struct KnownProgram {
+    // The first is the one shown in labels
+    aliases: &'static [&'static str],
+    name: &'static str,
+    id: Pubkey,
+}
+
+const KNOWN_PROGRAMS: &[KnownProgram] = &[
+    KnownProgram {
+        aliases: &["token-2022"],
+        name: "Token-2022 Program",
+        id: spl_token_2022::ID,
+    },
+    KnownProgram {
+        aliases: &["token", "spl-token"],
+        name: "Token Program",
+        id: pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+    },
+    KnownProgram {
+        aliases: &["ata"],
+        name: "Associated Token Account Program",
+        id: pubkey!("ATokenGPvbdGVxr1b2hGz3ZhH9iR7oiUxd5XkLTV6sQK"),
+    },
+    KnownProgram {
+        aliases: &["memo"],
+        name: "Memo Program",
+        id: MEMO_PROGRAM_ID,
+    },
+    KnownProgram {
+        aliases: &["system"],
+        name: "System Program",
+        id: system_program::ID,
+    },
+];
+
+// Resolve a well-known alias like `token-2022`, falling back to parsing the
+// argument as a literal base58 pubkey
+pub fn parse_program(arg: &str) -> Result<Pubkey, String> {
+    if let Some(program) = KNOWN_PROGRAMS.iter().find(|p| p.aliases.contains(&arg)) {
+        return Ok(program.id);
+    }
+    Pubkey::from_str(arg).map_err(|_| {
+        let aliases: Vec<_> = KNOWN_PROGRAMS
+            .iter()
+            .flat_map(|p| p.aliases)
+            .copied()
+            .collect();
+        format!(
+            "not a valid program id or known alias (known aliases: {})",
+            aliases.join(", ")
+        )
+    })
+}
+
+pub fn program_alias(id: &Pubkey) -> Option<&'static str> {
+    KNOWN_PROGRAMS
+        .iter()
+        .find(|p| p.id == *id)
+        .map(|p| p.aliases[0])
+}
+
+// The programs the demo can build its token instructions for; Token-2022's
+// builders accept the original program's id as well as their own
+pub fn is_token_program(id: &Pubkey) -> bool {
+    matches!(program_alias(id), Some("token-2022" | "token"))
+}
+
+// The alias when there is one, for log lines naming the program
+pub fn program_label(id: &Pubkey) -> String {
+    program_alias(id).map_or_else(|| id.to_string(), str::to_string)
+}
+
+pub fn program_name(id: &Pubkey) -> &'static str {
+    KNOWN_PROGRAMS
+        .iter()
+        .find(|p| p.id == *id)
+        .map_or("Unknown Program", |p| p.name)
+}
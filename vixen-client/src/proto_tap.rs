@@ -0,0 +1,146 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use color_eyre::{eyre::WrapErr, Result};
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use yellowstone_vixen_proto::{prost::Message as _, prost_types::Any};
+
+// Where one payload went in the payload file, written as one JSON object per
+// line. The stream doesn't carry the slot yet, so it's null like the --output
+// json records'.
+#[derive(Serialize)]
+struct IndexEntry<'a> {
+    offset: u64,
+    length: u64,
+    type_url: &'a str,
+    program: String,
+    received_at: String,
+    slot: Option<u64>,
+}
+
+// Writes every payload as received, the encoded `google.protobuf.Any` behind
+// its varint length the way protobuf's writeDelimitedTo does, so any proto
+// tooling can read them back without knowing the parser protos. Both files
+// are flushed after each payload, a reader on the other end of a pipe sees
+// it as soon as it arrives.
+pub struct ProtoTap {
+    path: PathBuf,
+    payloads: BufWriter<File>,
+    index: BufWriter<File>,
+    offset: u64,
+    written: u64,
+}
+
+impl ProtoTap {
+    // The index defaults to the payload file's path with `.index.ndjson`
+    // added
+    pub fn create(path: &Path, index: Option<&Path>) -> Result<Self> {
+        let index = match index {
+            Some(index) => index.to_path_buf(),
+            None => {
+                let mut index = OsString::from(path);
+                index.push(".index.ndjson");
+                PathBuf::from(index)
+            }
+        };
+        let payloads = File::create(path)
+            .wrap_err_with(|| format!("Unable to open {} for --output proto", path.display()))?;
+        let index_file = File::create(&index)
+            .wrap_err_with(|| format!("Unable to create the index {}", index.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            payloads: BufWriter::new(payloads),
+            index: BufWriter::new(index_file),
+            offset: 0,
+            written: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn write(&mut self, program: &Pubkey, payload: &Any) -> Result<()> {
+        let bytes = payload.encode_length_delimited_to_vec();
+        self.payloads.write_all(&bytes)?;
+        let entry = IndexEntry {
+            offset: self.offset,
+            length: bytes.len() as u64,
+            type_url: &payload.type_url,
+            program: program.to_string(),
+            received_at: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            slot: None,
+        };
+        serde_json::to_writer(&mut self.index, &entry)?;
+        self.index.write_all(b"\n")?;
+        self.payloads.flush()?;
+        self.index.flush()?;
+        self.offset += bytes.len() as u64;
+        self.written += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use yellowstone_vixen_proto::prost::bytes::Buf as _;
+
+    use super::*;
+
+    #[test]
+    fn payloads_read_back_through_the_index() {
+        let path = std::env::temp_dir().join(format!("vixen-proto-tap-{}", std::process::id()));
+        let program = Pubkey::new_unique();
+        let payloads = [
+            Any {
+                type_url: "type.googleapis.com/vixen.parser.TokenExtensionStateProto".to_string(),
+                value: vec![1, 2, 3],
+            },
+            Any {
+                type_url: "type.googleapis.com/vixen.parser.TokenProgramIxProto".to_string(),
+                value: vec![4; 200],
+            },
+        ];
+        let mut tap = ProtoTap::create(&path, None).unwrap();
+        for payload in &payloads {
+            tap.write(&program, payload).unwrap();
+        }
+        assert_eq!(tap.written(), 2);
+
+        let bytes = fs::read(&path).unwrap();
+        let mut index_path = OsString::from(&path);
+        index_path.push(".index.ndjson");
+        let index = fs::read_to_string(&index_path).unwrap();
+        let entries: Vec<serde_json::Value> = index
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        let mut buf = bytes.as_slice();
+        for (payload, entry) in payloads.iter().zip(&entries) {
+            let offset = bytes.len() - buf.remaining();
+            assert_eq!(entry["offset"], offset as u64);
+            assert_eq!(entry["type_url"], payload.type_url.as_str());
+            assert_eq!(entry["program"], program.to_string());
+            assert!(entry["slot"].is_null());
+            assert_eq!(&Any::decode_length_delimited(&mut buf).unwrap(), payload);
+            let length = bytes.len() - buf.remaining() - offset;
+            assert_eq!(entry["length"], length as u64);
+        }
+        assert!(buf.is_empty());
+        fs::remove_file(&path).ok();
+        fs::remove_file(&index_path).ok();
+    }
+}
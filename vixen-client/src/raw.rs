@@ -0,0 +1,140 @@
+use solana_sdk::{program_option::COption, program_pack::Pack, pubkey::Pubkey};
+use spl_token_2022::{
+    extension::{BaseState, BaseStateWithExtensions as _, StateWithExtensions},
+    state::{Account, Mint},
+};
+use tracing::debug;
+use yellowstone_vixen_proto::parser::{
+    token_extension_state_proto::StateOneof, ExtendedMintProto, ExtendedTokenAccountProto,
+    MintProto, TokenAccountProto, TokenExtensionStateProto,
+};
+
+use crate::subscriber::ParsedUpdate;
+
+// Unpack a payload the parser protos couldn't make sense of as the raw
+// bytes of a token account or mint, the way the token programs lay them out,
+// so it still shows up as a state update. Only the base state is filled in,
+// the extensions are left out, and anything else is None.
+pub fn unpack_token_state(bytes: &[u8]) -> Option<ParsedUpdate> {
+    let state = if let Ok(account) = StateWithExtensions::<Account>::unpack(bytes) {
+        log_extensions("account", &account);
+        StateOneof::ExtendedTokenAccount(ExtendedTokenAccountProto {
+            base_account: Some(account_proto(&account.base)),
+            extension_data_vec: vec![],
+        })
+    } else if let Ok(mint) = StateWithExtensions::<Mint>::unpack(bytes) {
+        log_extensions("mint", &mint);
+        StateOneof::ExtendedMintAccount(ExtendedMintProto {
+            base_mint: Some(mint_proto(&mint.base)),
+            extension_data_vec: vec![],
+        })
+    } else {
+        return None;
+    };
+    Some(ParsedUpdate::State(TokenExtensionStateProto {
+        state_oneof: Some(state),
+    }))
+}
+
+fn log_extensions<S: BaseState + Pack>(kind: &str, state: &StateWithExtensions<S>) {
+    if let Ok(extensions) = state.get_extension_types() {
+        if !extensions.is_empty() {
+            debug!(
+                "Unpacked raw {} bytes without their extensions: {:?}",
+                kind, extensions
+            );
+        }
+    }
+}
+
+fn account_proto(account: &Account) -> TokenAccountProto {
+    TokenAccountProto {
+        mint: account.mint.to_string(),
+        owner: account.owner.to_string(),
+        amount: account.amount,
+        delegate: optional(account.delegate),
+        state: account.state as i32,
+        is_native: account.is_native.into(),
+        delegated_amount: account.delegated_amount,
+        close_authority: optional(account.close_authority),
+    }
+}
+
+fn mint_proto(mint: &Mint) -> MintProto {
+    MintProto {
+        mint_authority: optional(mint.mint_authority),
+        supply: mint.supply,
+        decimals: mint.decimals.into(),
+        is_initialized: mint.is_initialized,
+        freeze_authority: optional(mint.freeze_authority),
+    }
+}
+
+fn optional(key: COption<Pubkey>) -> Option<String> {
+    Option::from(key).map(|key: Pubkey| key.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use spl_token_2022::state::AccountState;
+
+    use super::*;
+
+    #[test]
+    fn unpacks_accounts_and_mints() {
+        let (mint, owner, authority) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut bytes = vec![0; Account::LEN];
+        Account::pack(
+            Account {
+                mint,
+                owner,
+                amount: 42,
+                state: AccountState::Frozen,
+                ..Default::default()
+            },
+            &mut bytes,
+        )
+        .unwrap();
+        let update = unpack_token_state(&bytes).unwrap();
+        let account = update.token_account().unwrap();
+        assert_eq!(account.mint, mint.to_string());
+        assert_eq!(account.owner, owner.to_string());
+        assert_eq!(account.amount, 42);
+        assert_eq!(account.state, 2);
+        assert_eq!(account.delegate, None);
+
+        let mut bytes = vec![0; Mint::LEN];
+        Mint::pack(
+            Mint {
+                mint_authority: COption::Some(authority),
+                supply: 1_000,
+                decimals: 6,
+                is_initialized: true,
+                freeze_authority: COption::None,
+            },
+            &mut bytes,
+        )
+        .unwrap();
+        let Some(ParsedUpdate::State(TokenExtensionStateProto {
+            state_oneof: Some(StateOneof::ExtendedMintAccount(mint)),
+        })) = unpack_token_state(&bytes)
+        else {
+            panic!("Not unpacked as a mint");
+        };
+        let mint = mint.base_mint.unwrap();
+        assert_eq!(mint.mint_authority, Some(authority.to_string()));
+        assert_eq!((mint.supply, mint.decimals), (1_000, 6));
+    }
+
+    #[test]
+    fn leaves_other_bytes_alone() {
+        assert!(unpack_token_state(&[]).is_none());
+        assert!(unpack_token_state(&[7; 40]).is_none());
+        // The right length for an account, but never initialized
+        assert!(unpack_token_state(&[0; Account::LEN]).is_none());
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use color_eyre::{eyre::bail, Result};
+use solana_sdk::pubkey::Pubkey;
+use yellowstone_vixen_proto::{prost::Message as _, prost_types::Any};
+
+#[derive(clap::Args)]
+pub struct RecordArgs {
+    /// File to write the recording to
+    pub path: PathBuf,
+}
+
+#[derive(clap::Args)]
+pub struct ReplayArgs {
+    /// Recording made with the record subcommand
+    pub path: PathBuf,
+
+    /// `max` to replay as fast as possible, or how many times faster than
+    /// recorded to replay, from 0.01 to 1000, keeping the recorded gaps
+    /// between updates
+    #[arg(long, alias = "speed", value_parser = ReplaySpeed::parse, default_value = "max")]
+    pub replay_speed: ReplaySpeed,
+}
+
+// Far enough either way that a recording still makes progress, or still
+// looks like a stream rather than `max`
+const MIN_REPLAY_SPEED: f64 = 0.01;
+const MAX_REPLAY_SPEED: f64 = 1000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplaySpeed {
+    Max,
+    // Always within MIN_REPLAY_SPEED..=MAX_REPLAY_SPEED
+    Multiplier(f64),
+}
+
+impl ReplaySpeed {
+    // A multiplier outside the bounds is clamped to them rather than
+    // rejected, the replay logs what it ended up using
+    fn parse(s: &str) -> Result<Self, String> {
+        if s.eq_ignore_ascii_case("max") {
+            return Ok(ReplaySpeed::Max);
+        }
+        let multiplier: f64 = s
+            .parse()
+            .map_err(|_| format!("expected `max` or a multiplier, got {:?}", s))?;
+        if !multiplier.is_finite() || multiplier <= 0.0 {
+            return Err("the multiplier must be above 0, use `max` for no delay".to_string());
+        }
+        Ok(ReplaySpeed::Multiplier(
+            multiplier.clamp(MIN_REPLAY_SPEED, MAX_REPLAY_SPEED),
+        ))
+    }
+}
+
+impl std::fmt::Display for ReplaySpeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplaySpeed::Max => f.write_str("max speed"),
+            ReplaySpeed::Multiplier(multiplier) => write!(f, "{}x the recorded pace", multiplier),
+        }
+    }
+}
+
+// Identifies a recording and the version of its frame layout
+const MAGIC: &[u8; 8] = b"VXNREC01";
+
+// Anything bigger is a corrupt length rather than a real payload
+const MAX_FRAME_SIZE: u32 = 64 * 1024 * 1024;
+
+// One received update: when it arrived, which program's stream it came on
+// and its payload as sent by the server
+pub struct Frame {
+    pub received_at: SystemTime,
+    pub program: Pubkey,
+    pub payload: Any,
+}
+
+// Writes a recording: the magic, then per update its receive time in
+// microseconds since the epoch (u64), the program (32 bytes), and the
+// length (u32) and bytes of the encoded `Any`, integers little-endian
+pub struct Recorder {
+    writer: BufWriter<File>,
+    written: u64,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        Ok(Self { writer, written: 0 })
+    }
+
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn write(&mut self, program: &Pubkey, payload: &Any) -> Result<()> {
+        let micros = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_micros() as u64;
+        let bytes = payload.encode_to_vec();
+        self.writer.write_all(&micros.to_le_bytes())?;
+        self.writer.write_all(program.as_ref())?;
+        self.writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&bytes)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+// Reads the frames of a recording back in order
+pub struct Recording {
+    reader: BufReader<File>,
+}
+
+impl Recording {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("{} is not a vixen-client recording", path.display());
+        }
+        Ok(Self { reader })
+    }
+
+    // None at the end of the recording, an error for a truncated frame
+    pub fn next_frame(&mut self) -> Result<Option<Frame>> {
+        let mut micros = [0; 8];
+        match self.reader.read_exact(&mut micros) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let mut program = [0; 32];
+        self.reader.read_exact(&mut program)?;
+        let mut len = [0; 4];
+        self.reader.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len);
+        if len > MAX_FRAME_SIZE {
+            bail!("Recorded frame of {} bytes, the recording is corrupt", len);
+        }
+        let mut bytes = vec![0; len as usize];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(Some(Frame {
+            received_at: SystemTime::UNIX_EPOCH + Duration::from_micros(u64::from_le_bytes(micros)),
+            program: Pubkey::new_from_array(program),
+            payload: Any::decode(bytes.as_slice())?,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_replay_speeds() {
+        assert_eq!(ReplaySpeed::parse("max"), Ok(ReplaySpeed::Max));
+        assert_eq!(ReplaySpeed::parse("2.5"), Ok(ReplaySpeed::Multiplier(2.5)));
+        assert_eq!(
+            ReplaySpeed::parse("1e9"),
+            Ok(ReplaySpeed::Multiplier(MAX_REPLAY_SPEED))
+        );
+        assert_eq!(
+            ReplaySpeed::parse("0.0001"),
+            Ok(ReplaySpeed::Multiplier(MIN_REPLAY_SPEED))
+        );
+        assert!(ReplaySpeed::parse("0").is_err());
+        assert!(ReplaySpeed::parse("-1").is_err());
+        assert!(ReplaySpeed::parse("fast").is_err());
+    }
+
+    #[test]
+    fn reads_back_what_was_recorded() {
+        let path = std::env::temp_dir().join(format!("vixen-recording-{}", std::process::id()));
+        let program = Pubkey::new_unique();
+        let payload = Any {
+            type_url: "type.googleapis.com/vixen.parser.TokenExtensionStateProto".to_string(),
+            value: vec![1, 2, 3],
+        };
+        let mut recorder = Recorder::create(&path).unwrap();
+        recorder.write(&program, &payload).unwrap();
+        recorder.write(&program, &payload).unwrap();
+        recorder.flush().unwrap();
+
+        let mut recording = Recording::open(&path).unwrap();
+        for _ in 0..2 {
+            let frame = recording.next_frame().unwrap().unwrap();
+            assert_eq!(frame.program, program);
+            assert_eq!(frame.payload, payload);
+        }
+        assert!(recording.next_frame().unwrap().is_none());
+        std::fs::remove_file(path).ok();
+    }
+}
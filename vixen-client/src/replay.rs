@@ -0,0 +1,52 @@
+use std::{
+    collections::VecDeque,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use yellowstone_vixen_proto::prost_types::Any;
+
+// How many of the latest updates are remembered for spotting replays
+const RECENT_UPDATES: usize = 1024;
+
+// SubscribeRequest carries nothing but the program, so there's no slot or
+// offset to resume from after a reconnect. Instead the latest updates are
+// remembered by a hash of their payload, and right after a reconnect anything
+// the server sends that we've already seen is skipped, until the first new
+// update shows we've caught up. Outside that window repeats are kept, since
+// two identical transfers legitimately produce identical updates.
+pub struct ReplayFilter {
+    recent: VecDeque<u64>,
+    resuming: bool,
+}
+
+impl ReplayFilter {
+    pub fn new() -> Self {
+        Self {
+            recent: VecDeque::with_capacity(RECENT_UPDATES),
+            resuming: false,
+        }
+    }
+
+    pub fn reconnected(&mut self) {
+        self.resuming = !self.recent.is_empty();
+    }
+
+    // Whether the update was already handled before the stream dropped
+    pub fn is_replay(&mut self, any: &Any) -> bool {
+        let mut hasher = DefaultHasher::new();
+        any.type_url.hash(&mut hasher);
+        any.value.hash(&mut hasher);
+        let id = hasher.finish();
+        if self.resuming {
+            if self.recent.contains(&id) {
+                return true;
+            }
+            self.resuming = false;
+        }
+        if self.recent.len() == RECENT_UPDATES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(id);
+        false
+    }
+}
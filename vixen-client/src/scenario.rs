@@ -0,0 +1,883 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::{de::Error as _, Deserialize, Deserializer};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+};
+use spl_token_2022::instruction::{
+    approve_checked, burn_checked, close_account, freeze_account, thaw_account, transfer_checked,
+};
+use tracing::{info, warn};
+
+use crate::{
+    amount::UiAmount,
+    error::{bail, Context as _, Error, Result},
+    token::{
+        create_mint_with_extensions, create_token_account, send_instructions, MintExtensions,
+        SendOptions,
+    },
+    workload::{TeardownAccount, Workload, DEFAULT_DECIMALS},
+};
+
+// Far above what any single token instruction needs
+const COMPUTE_UNIT_LIMIT_BASE: u32 = 100_000;
+
+// A scripted run of token instructions for the workload to send in place of
+// the demo flow, loaded from a YAML file such as
+//
+//     steps:
+//       - step: create-mint
+//         mint: gold
+//         freeze-authority: true
+//       - step: create-account
+//         account: alice
+//         mint: gold
+//       - step: create-account
+//         account: bob
+//         mint: gold
+//       - step: mint-to
+//         account: alice
+//         amount: 100
+//       - step: transfer
+//         from: alice
+//         to: bob
+//         amount: "2.5"
+//         repeat: 4
+//
+// Mints, token accounts and delegates are named in the file rather than by
+// address: create-mint and create-account introduce a name, approve
+// introduces its delegate, and every other step refers to ones introduced
+// before it. Every step but the two creations may be repeated. Amounts are in
+// whole tokens of the account's mint, as strings so "2.5" isn't rounded
+// through a float. The payer signs for everything, owning every account and
+// holding every mint's authorities, except where a transfer or burn names a
+// delegate as its authority.
+//
+// The whole file is checked when it's loaded, so a typo in the last step
+// fails before the first transaction is sent.
+#[derive(Default)]
+pub struct Scenario {
+    mints: Vec<PlannedMint>,
+    accounts: Vec<PlannedAccount>,
+    delegates: Vec<String>,
+    steps: Vec<PlannedStep>,
+}
+
+// The steps as written in the file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    steps: Vec<StepEntry>,
+}
+
+#[derive(Deserialize)]
+struct StepEntry {
+    #[serde(flatten)]
+    step: Step,
+    #[serde(default = "once")]
+    repeat: u32,
+}
+
+fn once() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(
+    tag = "step",
+    rename_all = "kebab-case",
+    rename_all_fields = "kebab-case",
+    deny_unknown_fields
+)]
+enum Step {
+    CreateMint {
+        mint: String,
+        #[serde(default = "default_decimals")]
+        decimals: u8,
+        #[serde(default)]
+        freeze_authority: bool,
+    },
+    CreateAccount {
+        account: String,
+        mint: String,
+    },
+    MintTo {
+        account: String,
+        #[serde(deserialize_with = "amount")]
+        amount: UiAmount,
+    },
+    Transfer {
+        from: String,
+        to: String,
+        #[serde(deserialize_with = "amount")]
+        amount: UiAmount,
+        // A delegate approved on `from`, signing in place of the owner
+        authority: Option<String>,
+    },
+    Approve {
+        account: String,
+        delegate: String,
+        #[serde(deserialize_with = "amount")]
+        amount: UiAmount,
+    },
+    Burn {
+        account: String,
+        #[serde(deserialize_with = "amount")]
+        amount: UiAmount,
+        authority: Option<String>,
+    },
+    CloseAccount {
+        account: String,
+    },
+    Freeze {
+        account: String,
+    },
+    Thaw {
+        account: String,
+    },
+}
+
+fn default_decimals() -> u8 {
+    DEFAULT_DECIMALS
+}
+
+// Whole numbers may be written bare, anything with a fraction has to be a
+// string
+fn amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<UiAmount, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Written {
+        Whole(u64),
+        Fraction(f64),
+        Text(String),
+    }
+    match Written::deserialize(deserializer)? {
+        Written::Whole(amount) => Ok(UiAmount::from_base_units(amount, 0)),
+        Written::Fraction(amount) => Err(D::Error::custom(format!(
+            "write {} as a string, \"{}\", so it isn't rounded through a float",
+            amount, amount
+        ))),
+        Written::Text(text) => text.parse().map_err(D::Error::custom),
+    }
+}
+
+// A step with every name resolved to an index and every amount in base units
+struct PlannedStep {
+    op: Op,
+    repeat: u32,
+    // For the log, e.g. "transfer 2.5 from alice to bob"
+    description: String,
+}
+
+enum Op {
+    CreateMint {
+        mint: usize,
+        decimals: u8,
+        freeze_authority: bool,
+    },
+    CreateAccount {
+        account: usize,
+        mint: usize,
+    },
+    MintTo {
+        account: usize,
+        amount: u64,
+    },
+    Transfer {
+        from: usize,
+        to: usize,
+        amount: u64,
+        authority: Option<usize>,
+    },
+    Approve {
+        account: usize,
+        delegate: usize,
+        amount: u64,
+    },
+    Burn {
+        account: usize,
+        amount: u64,
+        authority: Option<usize>,
+    },
+    CloseAccount {
+        account: usize,
+    },
+    Freeze {
+        account: usize,
+    },
+    Thaw {
+        account: usize,
+    },
+}
+
+struct PlannedMint {
+    name: String,
+    decimals: u8,
+    freeze_authority: bool,
+}
+
+struct PlannedAccount {
+    name: String,
+    mint: usize,
+    // As the scenario leaves it, once every step is planned
+    closed: bool,
+    frozen: bool,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).context(Error::Config, || {
+            format!("Unable to read scenario {}", path.display())
+        })?;
+        Self::from_yaml(&text).context(Error::Config, || {
+            format!("Invalid scenario {}", path.display())
+        })
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Self> {
+        let file: ScenarioFile =
+            serde_yaml::from_str(text).map_err(|e| Error::Config(Box::new(e)))?;
+        if file.steps.is_empty() {
+            bail!(config, "the scenario has no steps");
+        }
+        // In order, so a name can only be used after the step introducing it
+        let mut scenario = Self::default();
+        for (i, entry) in file.steps.into_iter().enumerate() {
+            scenario
+                .plan(entry)
+                .context(Error::Config, || format!("step {}", i + 1))?;
+        }
+        Ok(scenario)
+    }
+
+    // Transactions the scenario sends, repeats included
+    pub fn transactions(&self) -> u64 {
+        self.steps.iter().map(|step| u64::from(step.repeat)).sum()
+    }
+
+    fn plan(&mut self, entry: StepEntry) -> Result<()> {
+        let StepEntry { step, repeat } = entry;
+        if repeat == 0 {
+            bail!(config, "repeat must be at least 1");
+        }
+        let (op, description) = match step {
+            Step::CreateMint {
+                mint,
+                decimals,
+                freeze_authority,
+            } => {
+                if decimals > 9 {
+                    bail!(config, "decimals is {}, at most 9 are supported", decimals);
+                }
+                self.check_new_name(&mint)?;
+                let description = format!("create mint {} with {} decimals", mint, decimals);
+                self.mints.push(PlannedMint {
+                    name: mint,
+                    decimals,
+                    freeze_authority,
+                });
+                let op = Op::CreateMint {
+                    mint: self.mints.len() - 1,
+                    decimals,
+                    freeze_authority,
+                };
+                (op, description)
+            }
+            Step::CreateAccount { account, mint } => {
+                self.check_new_name(&account)?;
+                let mint_index = self.mint(&mint)?;
+                let description = format!("create account {} for mint {}", account, mint);
+                self.accounts.push(PlannedAccount {
+                    name: account,
+                    mint: mint_index,
+                    closed: false,
+                    frozen: false,
+                });
+                let op = Op::CreateAccount {
+                    account: self.accounts.len() - 1,
+                    mint: mint_index,
+                };
+                (op, description)
+            }
+            Step::MintTo { account, amount } => {
+                let index = self.account(&account)?;
+                let op = Op::MintTo {
+                    account: index,
+                    amount: self.base_units(index, amount)?,
+                };
+                (op, format!("mint {} to {}", amount, account))
+            }
+            Step::Transfer {
+                from,
+                to,
+                amount,
+                authority,
+            } => {
+                let source = self.account(&from)?;
+                let destination = self.account(&to)?;
+                if self.accounts[source].mint != self.accounts[destination].mint {
+                    bail!(config, "{} and {} hold different mints", from, to);
+                }
+                let op = Op::Transfer {
+                    from: source,
+                    to: destination,
+                    amount: self.base_units(source, amount)?,
+                    authority: authority.as_deref().map(|d| self.delegate(d)).transpose()?,
+                };
+                let mut description = format!("transfer {} from {} to {}", amount, from, to);
+                if let Some(delegate) = authority {
+                    description.push_str(&format!(" as {}", delegate));
+                }
+                (op, description)
+            }
+            Step::Approve {
+                account,
+                delegate,
+                amount,
+            } => {
+                let index = self.account(&account)?;
+                let delegate_index = match self.delegates.iter().position(|d| *d == delegate) {
+                    Some(index) => index,
+                    None => {
+                        self.check_new_name(&delegate)?;
+                        self.delegates.push(delegate.clone());
+                        self.delegates.len() - 1
+                    }
+                };
+                let op = Op::Approve {
+                    account: index,
+                    delegate: delegate_index,
+                    amount: self.base_units(index, amount)?,
+                };
+                let description =
+                    format!("approve {} to spend {} from {}", delegate, amount, account);
+                (op, description)
+            }
+            Step::Burn {
+                account,
+                amount,
+                authority,
+            } => {
+                let index = self.account(&account)?;
+                let op = Op::Burn {
+                    account: index,
+                    amount: self.base_units(index, amount)?,
+                    authority: authority.as_deref().map(|d| self.delegate(d)).transpose()?,
+                };
+                let mut description = format!("burn {} from {}", amount, account);
+                if let Some(delegate) = authority {
+                    description.push_str(&format!(" as {}", delegate));
+                }
+                (op, description)
+            }
+            Step::CloseAccount { account } => {
+                let index = self.account(&account)?;
+                if repeat > 1 {
+                    bail!(config, "an account can only be closed once");
+                }
+                self.accounts[index].closed = true;
+                (
+                    Op::CloseAccount { account: index },
+                    format!("close {}", account),
+                )
+            }
+            Step::Freeze { account } => {
+                let index = self.freezable(&account)?;
+                self.accounts[index].frozen = true;
+                (Op::Freeze { account: index }, format!("freeze {}", account))
+            }
+            Step::Thaw { account } => {
+                let index = self.freezable(&account)?;
+                self.accounts[index].frozen = false;
+                (Op::Thaw { account: index }, format!("thaw {}", account))
+            }
+        };
+        if repeat > 1 && matches!(op, Op::CreateMint { .. } | Op::CreateAccount { .. }) {
+            bail!(
+                config,
+                "{} can't be repeated, each creation needs its own name",
+                description
+            );
+        }
+        self.steps.push(PlannedStep {
+            op,
+            repeat,
+            description,
+        });
+        Ok(())
+    }
+
+    // One namespace for mints, accounts and delegates, so a name always
+    // means the same thing
+    fn check_new_name(&self, name: &str) -> Result<()> {
+        let taken = self.mints.iter().any(|mint| mint.name == name)
+            || self.accounts.iter().any(|account| account.name == name)
+            || self.delegates.iter().any(|delegate| delegate == name);
+        if taken {
+            bail!(config, "{} is already the name of something else", name);
+        }
+        Ok(())
+    }
+
+    fn mint(&self, name: &str) -> Result<usize> {
+        match self.mints.iter().position(|mint| mint.name == name) {
+            Some(index) => Ok(index),
+            None => bail!(config, "no mint named {} has been created yet", name),
+        }
+    }
+
+    fn account(&self, name: &str) -> Result<usize> {
+        match self
+            .accounts
+            .iter()
+            .position(|account| account.name == name)
+        {
+            Some(index) if self.accounts[index].closed => bail!(config, "{} has been closed", name),
+            Some(index) => Ok(index),
+            None => bail!(config, "no account named {} has been created yet", name),
+        }
+    }
+
+    fn delegate(&self, name: &str) -> Result<usize> {
+        match self.delegates.iter().position(|delegate| delegate == name) {
+            Some(index) => Ok(index),
+            None => bail!(config, "{} hasn't been approved as a delegate yet", name),
+        }
+    }
+
+    fn freezable(&self, name: &str) -> Result<usize> {
+        let index = self.account(name)?;
+        let mint = &self.mints[self.accounts[index].mint];
+        if !mint.freeze_authority {
+            bail!(
+                config,
+                "mint {} was created without freeze-authority: true, so {} can't be frozen or thawed",
+                mint.name,
+                name
+            );
+        }
+        Ok(index)
+    }
+
+    fn base_units(&self, account: usize, amount: UiAmount) -> Result<u64> {
+        let mint = &self.mints[self.accounts[account].mint];
+        amount
+            .to_base_units(mint.decimals)
+            .map_err(|e| Error::config(format!("invalid amount for mint {}: {}", mint.name, e)))
+    }
+}
+
+// The addresses behind the scenario's names, and every signature it produced
+pub struct ScenarioRun {
+    pub mints: HashMap<String, Pubkey>,
+    pub accounts: HashMap<String, Pubkey>,
+    pub signatures: Vec<Signature>,
+}
+
+// Send the steps in order, each repeat in its own transaction, stopping at the
+// first failure
+pub(crate) async fn run(
+    scenario: &Scenario,
+    workload: &Workload,
+    rpc_client: &RpcClient,
+    payer: &Keypair,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<ScenarioRun> {
+    let mints: Vec<_> = scenario.mints.iter().map(|_| Keypair::new()).collect();
+    let accounts: Vec<_> = scenario.accounts.iter().map(|_| Keypair::new()).collect();
+    let delegates: Vec<_> = scenario.delegates.iter().map(|_| Keypair::new()).collect();
+    let mint_of = |account: usize| {
+        let mint = scenario.accounts[account].mint;
+        (mints[mint].pubkey(), scenario.mints[mint].decimals)
+    };
+
+    let total = scenario.steps.len();
+    let mut signatures = vec![];
+    for (i, step) in scenario.steps.iter().enumerate() {
+        for round in 1..=step.repeat {
+            if step.repeat > 1 {
+                info!(
+                    "Scenario step {}/{}: {} ({}/{})",
+                    i + 1,
+                    total,
+                    step.description,
+                    round,
+                    step.repeat
+                );
+            } else {
+                info!("Scenario step {}/{}: {}", i + 1, total, step.description);
+            }
+            let signature = match step.op {
+                Op::CreateMint {
+                    mint,
+                    decimals,
+                    freeze_authority,
+                } => {
+                    let extensions = MintExtensions {
+                        freeze_authority,
+                        ..MintExtensions::default()
+                    };
+                    create_mint_with_extensions(
+                        &mints[mint],
+                        payer,
+                        rpc_client,
+                        decimals,
+                        extensions,
+                        token_program,
+                        send_opts,
+                    )
+                    .await?
+                }
+                Op::CreateAccount { account, mint } => {
+                    create_token_account(
+                        rpc_client,
+                        payer,
+                        &mints[mint].pubkey(),
+                        &accounts[account],
+                        token_program,
+                        send_opts,
+                    )
+                    .await?;
+                    // Only the address comes back, not the signature
+                    workload.record_sent();
+                    continue;
+                }
+                ref op => {
+                    let (instruction, signer) =
+                        instruction(op, payer, &accounts, &delegates, mint_of, token_program)?;
+                    // Repeats would otherwise be byte for byte identical
+                    // whenever they share a blockhash, and dropped as
+                    // duplicates, so each asks for a different compute limit
+                    let instructions = match step.repeat {
+                        1 => vec![instruction],
+                        _ => vec![
+                            ComputeBudgetInstruction::set_compute_unit_limit(
+                                COMPUTE_UNIT_LIMIT_BASE + round,
+                            ),
+                            instruction,
+                        ],
+                    };
+                    let fee_payer = workload.fee_payer(payer);
+                    send_instructions(rpc_client, fee_payer, &[signer], &instructions, send_opts)
+                        .await
+                        .context(Error::Workload, || {
+                            format!("Scenario step {} failed", i + 1)
+                        })?
+                }
+            };
+            workload.record_sent();
+            signatures.push(signature);
+        }
+    }
+    info!(
+        "Scenario finished after {} transaction(s)",
+        scenario.transactions()
+    );
+
+    // Whatever the steps didn't close themselves, apart from a frozen
+    // account, which can't be emptied
+    if workload.tears_down() {
+        let mut teardown = vec![];
+        for (index, planned) in scenario.accounts.iter().enumerate() {
+            if planned.closed {
+                continue;
+            }
+            if planned.frozen {
+                warn!("Leaving {} open, it's frozen", planned.name);
+                continue;
+            }
+            let (mint, decimals) = mint_of(index);
+            teardown.push(TeardownAccount {
+                mint,
+                decimals,
+                account: accounts[index].pubkey(),
+                owner: payer,
+            });
+        }
+        signatures.extend(workload.teardown(rpc_client, payer, &teardown).await?);
+    }
+
+    Ok(ScenarioRun {
+        mints: scenario
+            .mints
+            .iter()
+            .map(|mint| mint.name.clone())
+            .zip(mints.iter().map(Signer::pubkey))
+            .collect(),
+        accounts: scenario
+            .accounts
+            .iter()
+            .map(|account| account.name.clone())
+            .zip(accounts.iter().map(Signer::pubkey))
+            .collect(),
+        signatures,
+    })
+}
+
+// The single instruction behind every step but the creations, with the
+// keypair that signs it as its authority
+fn instruction<'a>(
+    op: &Op,
+    payer: &'a Keypair,
+    accounts: &[Keypair],
+    delegates: &'a [Keypair],
+    mint_of: impl Fn(usize) -> (Pubkey, u8),
+    token_program: &Pubkey,
+) -> Result<(Instruction, &'a Keypair)> {
+    let authority = |delegate: Option<usize>| delegate.map_or(payer, |d| &delegates[d]);
+    let ix = match *op {
+        Op::MintTo { account, amount } => {
+            let (mint, _) = mint_of(account);
+            let ix = spl_token_2022::instruction::mint_to(
+                token_program,
+                &mint,
+                &accounts[account].pubkey(),
+                &payer.pubkey(),
+                &[],
+                amount,
+            )?;
+            (ix, payer)
+        }
+        Op::Transfer {
+            from,
+            to,
+            amount,
+            authority: delegate,
+        } => {
+            let (mint, decimals) = mint_of(from);
+            let signer = authority(delegate);
+            let ix = transfer_checked(
+                token_program,
+                &accounts[from].pubkey(),
+                &mint,
+                &accounts[to].pubkey(),
+                &signer.pubkey(),
+                &[],
+                amount,
+                decimals,
+            )?;
+            (ix, signer)
+        }
+        Op::Approve {
+            account,
+            delegate,
+            amount,
+        } => {
+            let (mint, decimals) = mint_of(account);
+            let ix = approve_checked(
+                token_program,
+                &accounts[account].pubkey(),
+                &mint,
+                &delegates[delegate].pubkey(),
+                &payer.pubkey(),
+                &[],
+                amount,
+                decimals,
+            )?;
+            (ix, payer)
+        }
+        Op::Burn {
+            account,
+            amount,
+            authority: delegate,
+        } => {
+            let (mint, decimals) = mint_of(account);
+            let signer = authority(delegate);
+            let ix = burn_checked(
+                token_program,
+                &accounts[account].pubkey(),
+                &mint,
+                &signer.pubkey(),
+                &[],
+                amount,
+                decimals,
+            )?;
+            (ix, signer)
+        }
+        // The rent goes back to the payer
+        Op::CloseAccount { account } => {
+            let ix = close_account(
+                token_program,
+                &accounts[account].pubkey(),
+                &payer.pubkey(),
+                &payer.pubkey(),
+                &[],
+            )?;
+            (ix, payer)
+        }
+        Op::Freeze { account } => {
+            let (mint, _) = mint_of(account);
+            let ix = freeze_account(
+                token_program,
+                &accounts[account].pubkey(),
+                &mint,
+                &payer.pubkey(),
+                &[],
+            )?;
+            (ix, payer)
+        }
+        Op::Thaw { account } => {
+            let (mint, _) = mint_of(account);
+            let ix = thaw_account(
+                token_program,
+                &accounts[account].pubkey(),
+                &mint,
+                &payer.pubkey(),
+                &[],
+            )?;
+            (ix, payer)
+        }
+        Op::CreateMint { .. } | Op::CreateAccount { .. } => {
+            unreachable!("creations have their own helpers")
+        }
+    };
+    Ok(ix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(yaml: &str) -> String {
+        match Scenario::from_yaml(yaml) {
+            Ok(_) => panic!("the scenario should be rejected"),
+            Err(e) => format!("{:#}", e),
+        }
+    }
+
+    const SETUP: &str = "
+steps:
+  - step: create-mint
+    mint: gold
+    decimals: 2
+  - step: create-account
+    account: alice
+    mint: gold
+  - step: create-account
+    account: bob
+    mint: gold
+";
+
+    #[test]
+    fn plans_steps_with_repeats_and_base_units() {
+        let scenario = Scenario::from_yaml(&format!(
+            "{}
+  - step: mint-to
+    account: alice
+    amount: 100
+  - step: transfer
+    from: alice
+    to: bob
+    amount: \"2.5\"
+    repeat: 4
+",
+            SETUP
+        ))
+        .unwrap();
+        assert_eq!(scenario.transactions(), 8);
+        assert!(matches!(
+            scenario.steps[4].op,
+            Op::Transfer {
+                from: 0,
+                to: 1,
+                amount: 250,
+                authority: None
+            }
+        ));
+        assert_eq!(scenario.steps[3].description, "mint 100 to alice");
+    }
+
+    #[test]
+    fn rejects_names_used_before_they_exist() {
+        let err = error(&format!(
+            "{}
+  - step: transfer
+    from: alice
+    to: carol
+    amount: 1
+",
+            SETUP
+        ));
+        assert!(err.contains("step 4"), "{}", err);
+        assert!(err.contains("no account named carol"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_using_a_closed_account() {
+        let err = error(&format!(
+            "{}
+  - step: close-account
+    account: bob
+  - step: mint-to
+    account: bob
+    amount: 1
+",
+            SETUP
+        ));
+        assert!(err.contains("bob has been closed"), "{}", err);
+    }
+
+    #[test]
+    fn freezing_needs_a_freeze_authority() {
+        let err = error(&format!(
+            "{}
+  - step: freeze
+    account: alice
+",
+            SETUP
+        ));
+        assert!(err.contains("without freeze-authority"), "{}", err);
+    }
+
+    #[test]
+    fn delegates_must_be_approved_first() {
+        let err = error(&format!(
+            "{}
+  - step: burn
+    account: alice
+    amount: 1
+    authority: spender
+",
+            SETUP
+        ));
+        assert!(err.contains("spender hasn't been approved"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_too_many_decimals() {
+        let err = error(&format!(
+            "{}
+  - step: mint-to
+    account: alice
+    amount: 1
+    mint: gold
+",
+            SETUP
+        ));
+        assert!(err.contains("unknown field `mint`"), "{}", err);
+        let err = error(&format!(
+            "{}
+  - step: mint-to
+    account: alice
+    amount: \"0.001\"
+",
+            SETUP
+        ));
+        assert!(err.contains("more than 2 decimal place(s)"), "{}", err);
+    }
+
+    #[test]
+    fn the_shipped_scenarios_are_valid() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            Scenario::load(&path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use std::{
+    panic::AssertUnwindSafe,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use color_eyre::Result;
+use futures::FutureExt as _;
+use metrics::{histogram, Histogram};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn, Instrument, Span};
+use yellowstone_vixen_proto::parser::{TokenExtensionProgramIxProto, TokenProgramIxProto};
+
+use svmkit_vixen_demo::subscriber::ParsedUpdate;
+
+use crate::{
+    checkpoint::TrackedAccounts,
+    panic_message,
+    pipeline::{queue, PipelineOptions, QueueSender},
+    pretty::PrettyFormatter,
+    state::{format_legacy_state, format_state, AccountStates, MintDecimals},
+};
+
+// Something that consumes every parsed update, e.g. the log or an output file
+#[async_trait]
+pub trait MessageHandler: Send {
+    fn name(&self) -> &str;
+
+    async fn handle(&mut self, program: &Pubkey, update: &ParsedUpdate) -> Result<()>;
+
+    // Write out anything buffered, called once the stream has closed
+    async fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// What the Kafka sink keys its records by and what it puts in them. They
+// live here rather than in the feature-gated sink so the flags always parse.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum KafkaKey {
+    /// The program the update was streamed for
+    Program,
+    /// The first account the update names, e.g. a transfer's source or a token
+    /// account's mint, falling back to the program
+    Account,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum KafkaFormat {
+    /// The same JSON record as --output json
+    Json,
+    /// The update's protobuf bytes, with its proto name in the `type` header
+    Proto,
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(not(feature = "kafka"), allow(dead_code))]
+pub struct KafkaOptions {
+    pub key: KafkaKey,
+    pub format: KafkaFormat,
+    // Updates handed to the producer before waiting on their deliveries
+    pub batch_size: usize,
+    // How long the producer waits to fill a batch before sending it
+    pub linger: Duration,
+}
+
+// Logs instructions as one line each with `pretty`, or with their debug
+// representation without, and state updates as a readable summary of the
+// account, or of what changed since its last update
+pub struct LogHandler {
+    mint_decimals: MintDecimals,
+    account_states: AccountStates,
+    pretty: Option<PrettyFormatter>,
+}
+
+impl LogHandler {
+    // Without an RPC endpoint, amounts stay in base units where the update
+    // doesn't carry the decimals, and every state update is logged in full
+    pub fn new(
+        rpc_url: Option<String>,
+        commitment: CommitmentConfig,
+        accounts: TrackedAccounts,
+        pretty: Option<PrettyFormatter>,
+    ) -> Self {
+        Self {
+            mint_decimals: MintDecimals::new(rpc_url.clone(), commitment),
+            account_states: AccountStates::new(rpc_url, commitment, accounts),
+            pretty,
+        }
+    }
+
+    // The decimals of a token account update's mint, and what changed since
+    // the account was last seen
+    async fn account_change(&mut self, update: &ParsedUpdate) -> (Option<u8>, Option<String>) {
+        let Some(account) = update.token_account() else {
+            return (None, None);
+        };
+        let decimals = self.mint_decimals.for_account(account).await;
+        (decimals, self.account_states.diff(account, decimals).await)
+    }
+}
+
+#[async_trait]
+impl MessageHandler for LogHandler {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    async fn handle(&mut self, _program: &Pubkey, update: &ParsedUpdate) -> Result<()> {
+        if let Some(pretty) = self.pretty {
+            let decimals = match PrettyFormatter::mint(update) {
+                Some(mint) if update.is_instruction() => self.mint_decimals.for_mint(&mint).await,
+                _ => None,
+            };
+            if let Some(line) = pretty.instruction(update, decimals) {
+                info!("{}", line);
+                return Ok(());
+            }
+        }
+        match update {
+            ParsedUpdate::Instruction(TokenExtensionProgramIxProto {
+                ix_oneof: Some(val),
+            }) => info!("Parsed message: {:?}", val),
+            ParsedUpdate::Instruction(_) => info!("Parsed message: {:?}", update),
+            ParsedUpdate::LegacyInstruction(TokenProgramIxProto {
+                ix_oneof: Some(val),
+            }) => info!("Parsed message: {:?}", val),
+            ParsedUpdate::LegacyInstruction(_) => info!("Parsed message: {:?}", update),
+            ParsedUpdate::State(state) => match self.account_change(update).await {
+                (_, Some(diff)) => info!("Parsed state: {}", diff),
+                (decimals, None) => info!("Parsed state: {}", format_state(state, decimals)),
+            },
+            ParsedUpdate::LegacyState(state) => match self.account_change(update).await {
+                (_, Some(diff)) => info!("Parsed state: {}", diff),
+                (decimals, None) => {
+                    info!("Parsed state: {}", format_legacy_state(state, decimals))
+                }
+            },
+            ParsedUpdate::Custom(custom) => {
+                info!("Parsed {}: {}", custom.type_name(), update.to_json()?)
+            }
+            ParsedUpdate::Unknown(any) => info!(
+                "Unknown update with type URL {:?} ({} bytes)",
+                any.type_url,
+                any.value.len()
+            ),
+        }
+        Ok(())
+    }
+}
+
+struct SinkEntry {
+    handler: Box<dyn MessageHandler>,
+    failures: u64,
+    panics: u64,
+    handled: u64,
+    total_time: Duration,
+    max_time: Duration,
+    // Registered up front so recording stays cheap on the fast path
+    latency: Histogram,
+}
+
+impl SinkEntry {
+    // A failing handler only counts against itself
+    async fn handle(&mut self, program: &Pubkey, update: &ParsedUpdate, opts: WorkerOptions) {
+        let start = Instant::now();
+        let res = if opts.catch_panics {
+            match AssertUnwindSafe(self.handler.handle(program, update))
+                .catch_unwind()
+                .await
+            {
+                Ok(res) => res,
+                Err(panic) => {
+                    self.panics += 1;
+                    error!(
+                        "Sink {} panicked handling an update: {}",
+                        self.handler.name(),
+                        panic_message(&*panic)
+                    );
+                    Ok(())
+                }
+            }
+        } else {
+            self.handler.handle(program, update).await
+        };
+        let elapsed = start.elapsed();
+        self.handled += 1;
+        self.total_time += elapsed;
+        self.max_time = self.max_time.max(elapsed);
+        self.latency.record(elapsed);
+        if elapsed > opts.slow_threshold {
+            warn!(
+                "Sink {} took {:?} to handle an update, above the {:?} threshold",
+                self.handler.name(),
+                elapsed,
+                opts.slow_threshold
+            );
+        }
+        if let Err(e) = res {
+            self.failures += 1;
+            warn!(
+                "Sink {} failed to handle update ({} failure(s) so far): {}",
+                self.handler.name(),
+                self.failures,
+                e
+            );
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct WorkerOptions {
+    slow_threshold: Duration,
+    catch_panics: bool,
+}
+
+// Carries the dispatching span so a sink's logs keep the update's context
+type SinkUpdate = Arc<(Pubkey, ParsedUpdate, Span)>;
+
+// Each sink runs on its own task behind its own queue, so a slow one only
+// holds back the stream once its queue fills, and never the other sinks
+struct SinkWorker {
+    name: String,
+    queue: QueueSender<SinkUpdate>,
+    task: JoinHandle<(SinkEntry, u64)>,
+}
+
+impl SinkWorker {
+    fn spawn(mut entry: SinkEntry, pipeline: PipelineOptions, opts: WorkerOptions) -> Self {
+        let name = entry.handler.name().to_string();
+        let (queue, mut updates) =
+            queue(&format!("sink:{}", name), pipeline, |_: &SinkUpdate| true);
+        let task = tokio::spawn(async move {
+            while let Some(update) = updates.recv().await {
+                let (program, update, span) = &*update;
+                entry
+                    .handle(program, update, opts)
+                    .instrument(span.clone())
+                    .await;
+            }
+            if let Err(e) = entry.handler.flush().await {
+                warn!("Sink {} failed to flush: {}", entry.handler.name(), e);
+            }
+            (entry, updates.dropped())
+        });
+        Self { name, queue, task }
+    }
+}
+
+struct SinkSummary {
+    entry: SinkEntry,
+    dropped: u64,
+}
+
+// Fans each update out to every configured handler. Each handler sees the
+// updates in the order they were dispatched, but at its own pace.
+pub struct Sinks {
+    workers: Vec<SinkWorker>,
+    closed: Vec<SinkSummary>,
+}
+
+impl Sinks {
+    pub fn new(
+        handlers: Vec<Box<dyn MessageHandler>>,
+        slow_threshold: Duration,
+        pipeline: PipelineOptions,
+        catch_panics: bool,
+    ) -> Self {
+        let opts = WorkerOptions {
+            slow_threshold,
+            catch_panics,
+        };
+        let workers = handlers
+            .into_iter()
+            .map(|handler| SinkEntry {
+                latency: histogram!("vixen_sink_handle_seconds", "sink" => handler.name().to_string()),
+                handler,
+                failures: 0,
+                panics: 0,
+                handled: 0,
+                total_time: Duration::ZERO,
+                max_time: Duration::ZERO,
+            })
+            .map(|entry| SinkWorker::spawn(entry, pipeline, opts))
+            .collect();
+        Self {
+            workers,
+            closed: vec![],
+        }
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.workers.iter().map(|w| w.name.as_str()).collect()
+    }
+
+    // Waits only for room in each sink's queue, not for the sinks themselves
+    pub async fn dispatch(&mut self, program: Pubkey, update: ParsedUpdate) {
+        let update = Arc::new((program, update, Span::current()));
+        for worker in &self.workers {
+            // Only a worker that panicked with --no-catch-panics stops
+            // taking updates, which should take the stream down with it
+            if worker.queue.send(update.clone()).await.is_err() {
+                panic!("Sink {} stopped after a panic", worker.name);
+            }
+        }
+    }
+
+    // Lets every sink work through its queue, then flushes it
+    pub async fn close(&mut self) {
+        for SinkWorker { name, queue, task } in self.workers.drain(..) {
+            drop(queue);
+            match task.await {
+                Ok((entry, dropped)) => self.closed.push(SinkSummary { entry, dropped }),
+                Err(e) => error!("Sink {} stopped before closing: {}", name, e),
+            }
+        }
+    }
+
+    pub fn log_summary(&self) {
+        for SinkSummary { entry, dropped } in &self.closed {
+            if entry.handled > 0 {
+                info!(
+                    "Sink {} handled {} update(s), {:?} average, {:?} max",
+                    entry.handler.name(),
+                    entry.handled,
+                    entry.total_time / entry.handled as u32,
+                    entry.max_time
+                );
+            }
+            if entry.failures > 0 {
+                warn!(
+                    "Sink {} failed on {} update(s)",
+                    entry.handler.name(),
+                    entry.failures
+                );
+            }
+            if entry.panics > 0 {
+                warn!(
+                    "Sink {} panicked on {} update(s)",
+                    entry.handler.name(),
+                    entry.panics
+                );
+            }
+            if *dropped > 0 {
+                warn!(
+                    "Sink {} fell behind and dropped {} update(s) from its queue",
+                    entry.handler.name(),
+                    dropped
+                );
+            }
+        }
+    }
+}
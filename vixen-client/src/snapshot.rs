@@ -0,0 +1,293 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use color_eyre::{eyre::WrapErr, Result};
+use serde::Serialize;
+use serde_json::Value;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, program_option::COption, pubkey::Pubkey};
+use spl_token_2022::{extension::StateWithExtensions, state::Mint};
+use svmkit_vixen_demo::{json::to_json, subscriber::ParsedUpdate};
+use tracing::{info, warn};
+use yellowstone_vixen_proto::parser::{
+    token_extension_state_proto::StateOneof,
+    token_program_state_proto::StateOneof as TokenStateOneof, ExtendedMintProto,
+    ExtendedTokenAccountProto, ExtensionDataProto, MintProto, TokenAccountProto,
+    TokenExtensionStateProto, TokenProgramStateProto,
+};
+
+use crate::{
+    sink::MessageHandler,
+    state::{account_state, AccountLookup},
+};
+
+#[derive(clap::Args)]
+pub struct SnapshotArgs {
+    /// File to write the snapshot to
+    pub path: PathBuf,
+
+    /// How long to build up state from the stream before writing it, e.g.
+    /// "1m"
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "30s")]
+    pub duration: Duration,
+
+    /// Only snapshot the stream, without running the token workload
+    #[arg(long)]
+    pub stream_only: bool,
+}
+
+// Bumped when an existing field changes, like the --output json records
+const SNAPSHOT_VERSION: u32 = 1;
+
+// Mint updates don't name their mint any more than token account updates
+// name their account. They're told apart by what stays put on a mint, its
+// authorities and decimals, and matched to the mints the token accounts name.
+type MintKey = (Option<String>, Option<String>, u64);
+
+#[derive(Default)]
+struct Tracked {
+    accounts: HashMap<Pubkey, (TokenAccountProto, Vec<Value>)>,
+    mints: HashMap<MintKey, (MintProto, Vec<Value>)>,
+    // Token account updates the lookup couldn't pin to their account
+    unresolved: u64,
+}
+
+// Keeps the last state of every mint and token account on the stream, for
+// the Snapshot to write out once the stream has run for long enough
+pub struct SnapshotCollector {
+    lookup: AccountLookup,
+    tracked: Arc<Mutex<Tracked>>,
+}
+
+pub struct Snapshot {
+    client: RpcClient,
+    tracked: Arc<Mutex<Tracked>>,
+}
+
+pub fn snapshot_collector(
+    rpc_url: &str,
+    commitment: CommitmentConfig,
+) -> (SnapshotCollector, Snapshot) {
+    let tracked = Arc::default();
+    let collector = SnapshotCollector {
+        lookup: AccountLookup::new(Some(rpc_url.to_string()), commitment),
+        tracked: Arc::clone(&tracked),
+    };
+    let snapshot = Snapshot {
+        client: RpcClient::new_with_commitment(rpc_url.to_string(), commitment),
+        tracked,
+    };
+    (collector, snapshot)
+}
+
+#[async_trait]
+impl MessageHandler for SnapshotCollector {
+    fn name(&self) -> &str {
+        "snapshot"
+    }
+
+    async fn handle(&mut self, _program: &Pubkey, update: &ParsedUpdate) -> Result<()> {
+        let (account, mint, extensions) = match update {
+            ParsedUpdate::State(TokenExtensionStateProto {
+                state_oneof: Some(state),
+            }) => match state {
+                StateOneof::ExtendedTokenAccount(ExtendedTokenAccountProto {
+                    base_account: Some(account),
+                    extension_data_vec,
+                }) => (Some(account), None, extensions_json(extension_data_vec)?),
+                StateOneof::ExtendedMintAccount(ExtendedMintProto {
+                    base_mint: Some(mint),
+                    extension_data_vec,
+                }) => (None, Some(mint), extensions_json(extension_data_vec)?),
+                _ => return Ok(()),
+            },
+            ParsedUpdate::LegacyState(TokenProgramStateProto {
+                state_oneof: Some(TokenStateOneof::TokenAccount(account)),
+            }) => (Some(account), None, vec![]),
+            ParsedUpdate::LegacyState(TokenProgramStateProto {
+                state_oneof: Some(TokenStateOneof::Mint(mint)),
+            }) => (None, Some(mint), vec![]),
+            _ => return Ok(()),
+        };
+        if let Some(mint) = mint {
+            let key = (
+                mint.mint_authority.clone(),
+                mint.freeze_authority.clone(),
+                mint.decimals,
+            );
+            self.tracked
+                .lock()
+                .unwrap()
+                .mints
+                .insert(key, (mint.clone(), extensions));
+            return Ok(());
+        }
+        let Some(account) = account else {
+            return Ok(());
+        };
+        let pubkey = self.lookup.resolve(account).await;
+        let mut tracked = self.tracked.lock().unwrap();
+        match pubkey {
+            Some(pubkey) => {
+                tracked
+                    .accounts
+                    .insert(pubkey, (account.clone(), extensions));
+            }
+            None => tracked.unresolved += 1,
+        }
+        Ok(())
+    }
+}
+
+fn extensions_json(extensions: &[ExtensionDataProto]) -> Result<Vec<Value>> {
+    Ok(extensions
+        .iter()
+        .map(to_json)
+        .collect::<svmkit_vixen_demo::Result<_>>()?)
+}
+
+#[derive(Serialize)]
+struct SnapshotFile {
+    snapshot_version: u32,
+    taken_at: String,
+    // Keyed by address, so snapshots of the same state only differ in taken_at
+    mints: BTreeMap<String, MintEntry>,
+    token_accounts: BTreeMap<String, TokenAccountEntry>,
+}
+
+#[derive(Serialize)]
+struct MintEntry {
+    supply: u64,
+    decimals: u64,
+    mint_authority: Option<String>,
+    freeze_authority: Option<String>,
+    extensions: Vec<Value>,
+}
+
+#[derive(Serialize)]
+struct TokenAccountEntry {
+    mint: String,
+    owner: String,
+    amount: u64,
+    delegate: Option<String>,
+    delegated_amount: u64,
+    state: &'static str,
+    close_authority: Option<String>,
+    extensions: Vec<Value>,
+}
+
+impl Snapshot {
+    pub async fn write(self, path: &Path) -> Result<()> {
+        let (accounts, mints, unresolved) = {
+            let mut tracked = self.tracked.lock().unwrap();
+            let tracked = std::mem::take(&mut *tracked);
+            (tracked.accounts, tracked.mints, tracked.unresolved)
+        };
+        let mint_updates = mints.len();
+        let mints = self.pin_mints(&accounts, mints).await;
+        let file = SnapshotFile {
+            snapshot_version: SNAPSHOT_VERSION,
+            taken_at: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            mints: mints
+                .into_iter()
+                .map(|(pubkey, (mint, extensions))| {
+                    let entry = MintEntry {
+                        supply: mint.supply,
+                        decimals: mint.decimals,
+                        mint_authority: mint.mint_authority,
+                        freeze_authority: mint.freeze_authority,
+                        extensions,
+                    };
+                    (pubkey.to_string(), entry)
+                })
+                .collect(),
+            token_accounts: accounts
+                .into_iter()
+                .map(|(pubkey, (account, extensions))| {
+                    let entry = TokenAccountEntry {
+                        state: account_state(account.state),
+                        mint: account.mint,
+                        owner: account.owner,
+                        amount: account.amount,
+                        delegate: account.delegate,
+                        delegated_amount: account.delegated_amount,
+                        close_authority: account.close_authority,
+                        extensions,
+                    };
+                    (pubkey.to_string(), entry)
+                })
+                .collect(),
+        };
+        fs::write(path, serde_json::to_vec_pretty(&file)?)
+            .wrap_err_with(|| format!("Unable to write the snapshot to {}", path.display()))?;
+        info!(
+            "Wrote a snapshot of {} mint(s) and {} token account(s) to {}",
+            file.mints.len(),
+            file.token_accounts.len(),
+            path.display()
+        );
+        if file.mints.len() < mint_updates {
+            warn!(
+                "Left out {} mint(s) that couldn't be matched to a mint the token accounts name",
+                mint_updates - file.mints.len()
+            );
+        }
+        if unresolved > 0 {
+            warn!(
+                "Left out {} token account update(s) that couldn't be pinned to their account",
+                unresolved
+            );
+        }
+        Ok(())
+    }
+
+    // Reads each mint the token accounts name, and gives it the stream's
+    // state with the same authorities and decimals. Mints that share those
+    // can't be told apart, so neither is given one.
+    async fn pin_mints(
+        &self,
+        accounts: &HashMap<Pubkey, (TokenAccountProto, Vec<Value>)>,
+        mut mints: HashMap<MintKey, (MintProto, Vec<Value>)>,
+    ) -> Vec<(Pubkey, (MintProto, Vec<Value>))> {
+        let named: HashSet<Pubkey> = accounts
+            .values()
+            .filter_map(|(account, _)| account.mint.parse().ok())
+            .collect();
+        let mut keys: HashMap<MintKey, Vec<Pubkey>> = HashMap::new();
+        for pubkey in named {
+            let decoded = match self.client.get_account_data(&pubkey).await {
+                Ok(data) => StateWithExtensions::<Mint>::unpack(&data)
+                    .map(|mint| mint.base)
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            match decoded {
+                Ok(mint) => {
+                    let key = (
+                        optional(mint.mint_authority),
+                        optional(mint.freeze_authority),
+                        mint.decimals.into(),
+                    );
+                    keys.entry(key).or_default().push(pubkey);
+                }
+                Err(e) => warn!("Unable to read mint {}: {}", pubkey, e),
+            }
+        }
+        keys.into_iter()
+            .filter_map(|(key, pubkeys)| match pubkeys.as_slice() {
+                [pubkey] => Some((*pubkey, mints.remove(&key)?)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn optional(key: COption<Pubkey>) -> Option<String> {
+    Option::from(key).map(|key: Pubkey| key.to_string())
+}
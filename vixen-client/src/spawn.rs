@@ -0,0 +1,185 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use svmkit_vixen_demo::subscriber::VixenSubscriber;
+use tracing::{info, warn};
+use yellowstone_vixen_proto::tonic::transport::Endpoint;
+
+use crate::redact_url;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const DEFAULT_RPC_PORT: u16 = 8899;
+
+// What to launch before the demo and where, see --spawn-validator
+pub struct SpawnOptions<'a> {
+    pub dir: &'a Path,
+    pub geyser_config: Option<&'a Path>,
+    pub vixen_server: Option<(&'a Path, &'a Path)>,
+    pub timeout: Duration,
+}
+
+// A solana-test-validator, and maybe a Vixen server in front of it, run as
+// children of the client for as long as it runs. They're killed when this is
+// dropped, however main returns.
+pub struct LocalCluster {
+    // With the file their output goes to
+    children: Vec<(&'static str, Child, PathBuf)>,
+}
+
+impl LocalCluster {
+    // Start the validator on the port of `rpc_url` with a fresh ledger in the
+    // spawn directory, then the Vixen server if asked for, waiting for each to
+    // answer before moving on
+    pub async fn start(
+        opts: SpawnOptions<'_>,
+        rpc_client: &RpcClient,
+        subscriber: &VixenSubscriber,
+    ) -> Result<Self> {
+        let rpc_port = local_port(&rpc_client.url())
+            .wrap_err("--spawn-validator starts the validator on this machine")?
+            .unwrap_or(DEFAULT_RPC_PORT);
+        std::fs::create_dir_all(opts.dir)
+            .wrap_err_with(|| format!("Unable to create spawn directory {}", opts.dir.display()))?;
+        let mut cluster = Self { children: vec![] };
+
+        let ledger = opts.dir.join("test-ledger");
+        let mut validator = Command::new("solana-test-validator");
+        validator
+            .arg("--reset")
+            .arg("--quiet")
+            .arg("--ledger")
+            .arg(&ledger)
+            .arg("--rpc-port")
+            .arg(rpc_port.to_string());
+        if let Some(config) = opts.geyser_config {
+            validator.arg("--geyser-plugin-config").arg(config);
+        }
+        cluster.spawn(
+            "solana-test-validator",
+            validator,
+            &opts.dir.join("solana-test-validator.log"),
+            "is the Solana CLI installed and on PATH?",
+        )?;
+        info!(
+            "Started solana-test-validator on port {}, its ledger is in {}",
+            rpc_port,
+            ledger.display()
+        );
+        cluster
+            .wait_until_ready("solana-test-validator", opts.timeout, || async {
+                rpc_client.get_health().await.is_ok()
+            })
+            .await?;
+        info!("solana-test-validator is ready at {}", rpc_client.url());
+
+        if let Some((binary, config)) = opts.vixen_server {
+            local_port(&subscriber.endpoint().uri().to_string())
+                .wrap_err("--spawn-vixen-server starts the Vixen server on this machine")?;
+            let mut server = Command::new(binary);
+            server.arg("--config").arg(config);
+            cluster.spawn(
+                "vixen-server",
+                server,
+                &opts.dir.join("vixen-server.log"),
+                "build it from vixen-server/ or point --vixen-server-bin at it",
+            )?;
+            cluster
+                .wait_until_ready("vixen-server", opts.timeout, || async {
+                    subscriber.connect().await.is_ok()
+                })
+                .await?;
+            info!(
+                "vixen-server is ready at {}",
+                redact_url(&subscriber.endpoint().uri().to_string())
+            );
+        }
+        Ok(cluster)
+    }
+
+    fn spawn(
+        &mut self,
+        name: &'static str,
+        mut command: Command,
+        log: &Path,
+        hint: &str,
+    ) -> Result<()> {
+        let output =
+            File::create(log).wrap_err_with(|| format!("Unable to create {}", log.display()))?;
+        let child = command
+            .stdin(Stdio::null())
+            .stdout(output.try_clone()?)
+            .stderr(output)
+            .spawn()
+            .wrap_err_with(|| format!("Unable to start {}, {}", name, hint))?;
+        self.children.push((name, child, log.to_path_buf()));
+        Ok(())
+    }
+
+    // Polls `ready` until it passes, failing early when the child exits
+    async fn wait_until_ready<F, Fut>(
+        &mut self,
+        name: &str,
+        timeout: Duration,
+        ready: F,
+    ) -> Result<()>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = bool>,
+    {
+        let start = Instant::now();
+        loop {
+            if ready().await {
+                return Ok(());
+            }
+            if let Some((_, child, log)) = self.children.iter_mut().find(|(n, ..)| *n == name) {
+                if let Some(status) = child.try_wait()? {
+                    bail!(
+                        "{} exited with {} before it was ready, see {}",
+                        name,
+                        status,
+                        log.display()
+                    );
+                }
+            }
+            if start.elapsed() > timeout {
+                bail!("{} wasn't ready after {:?}", name, timeout);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl Drop for LocalCluster {
+    fn drop(&mut self) {
+        // The Vixen server goes first, it would only complain about the
+        // validator going away under it
+        for (name, child, _) in self.children.iter_mut().rev() {
+            if let Err(e) = child.kill().and_then(|_| child.wait()) {
+                warn!("Unable to stop {}: {}", name, e);
+            } else {
+                info!("Stopped {}", name);
+            }
+        }
+    }
+}
+
+// The port of a URL that must point at this machine, if it names one
+fn local_port(url: &str) -> Result<Option<u16>> {
+    let endpoint = Endpoint::from_shared(url.to_string())
+        .wrap_err_with(|| format!("Invalid URL {}", redact_url(url)))?;
+    let uri = endpoint.uri();
+    match uri.host() {
+        Some("localhost" | "127.0.0.1" | "[::1]" | "0.0.0.0") => {}
+        _ => bail!("{} isn't a local address", redact_url(url)),
+    }
+    Ok(uri.port_u16())
+}
@@ -0,0 +1,272 @@
+use std::{collections::HashMap, fmt::Write as _};
+
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_request::TokenAccountsFilter};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use spl_token_2022::{extension::StateWithExtensions, state::Mint};
+use yellowstone_vixen_proto::parser::{
+    token_extension_state_proto::StateOneof,
+    token_program_state_proto::StateOneof as TokenStateOneof, AccountStateProto, ExtendedMintProto,
+    ExtendedTokenAccountProto, MintProto, MultisigProto, TokenAccountProto,
+    TokenExtensionStateProto, TokenProgramStateProto,
+};
+
+use svmkit_vixen_demo::amount::UiAmount;
+
+use crate::checkpoint::TrackedAccounts;
+
+// A concise line for a state update. Token accounts only carry their mint's
+// address, so `mint_decimals` supplies its decimals for formatting the amount;
+// without them the amount is shown in base units.
+pub fn format_state(state: &TokenExtensionStateProto, mint_decimals: Option<u8>) -> String {
+    match &state.state_oneof {
+        Some(StateOneof::ExtendedTokenAccount(ExtendedTokenAccountProto {
+            base_account: Some(account),
+            extension_data_vec,
+        })) => format_token_account(account, extension_data_vec.len(), mint_decimals),
+        Some(StateOneof::ExtendedMintAccount(ExtendedMintProto {
+            base_mint: Some(mint),
+            extension_data_vec,
+        })) => format_mint(mint, extension_data_vec.len()),
+        Some(StateOneof::Multisig(multisig)) => format_multisig(multisig),
+        other => format!("{:?}", other),
+    }
+}
+
+// The same for the original token program, which has no extensions
+pub fn format_legacy_state(state: &TokenProgramStateProto, mint_decimals: Option<u8>) -> String {
+    match &state.state_oneof {
+        Some(TokenStateOneof::TokenAccount(account)) => {
+            format_token_account(account, 0, mint_decimals)
+        }
+        Some(TokenStateOneof::Mint(mint)) => format_mint(mint, 0),
+        Some(TokenStateOneof::Multisig(multisig)) => format_multisig(multisig),
+        None => "empty state".to_string(),
+    }
+}
+
+fn format_token_account(
+    account: &TokenAccountProto,
+    extension_count: usize,
+    mint_decimals: Option<u8>,
+) -> String {
+    let amount = match mint_decimals {
+        Some(decimals) => UiAmount::from_base_units(account.amount, decimals).to_string(),
+        None => format!("{} base units", account.amount),
+    };
+    format!(
+        "Token account: owner {}, mint {}, amount {}{}",
+        account.owner,
+        account.mint,
+        amount,
+        extensions(extension_count)
+    )
+}
+
+fn format_mint(mint: &MintProto, extension_count: usize) -> String {
+    let supply = u8::try_from(mint.decimals)
+        .map(|decimals| UiAmount::from_base_units(mint.supply, decimals).to_string())
+        .unwrap_or_else(|_| format!("{} base units", mint.supply));
+    format!(
+        "Mint: supply {}, {} decimals, mint authority {}{}",
+        supply,
+        mint.decimals,
+        mint.mint_authority.as_deref().unwrap_or("none"),
+        extensions(extension_count)
+    )
+}
+
+fn format_multisig(MultisigProto { m, n, .. }: &MultisigProto) -> String {
+    format!("Multisig: {} of {} signers", m, n)
+}
+
+fn extensions(count: usize) -> String {
+    match count {
+        0 => String::new(),
+        count => format!(", {} extension(s)", count),
+    }
+}
+
+// Decimals of the mints seen in token account updates, fetched over RPC the
+// first time each one shows up. Without an RPC endpoint amounts stay in base
+// units.
+pub struct MintDecimals {
+    client: Option<RpcClient>,
+    known: HashMap<String, u8>,
+}
+
+impl MintDecimals {
+    pub fn new(rpc_url: Option<String>, commitment: CommitmentConfig) -> Self {
+        Self {
+            client: rpc_url.map(|url| RpcClient::new_with_commitment(url, commitment)),
+            known: HashMap::new(),
+        }
+    }
+
+    // Only successful lookups are remembered, a mint that can't be read yet
+    // is tried again on its next update
+    pub async fn for_account(&mut self, account: &TokenAccountProto) -> Option<u8> {
+        self.for_mint(&account.mint).await
+    }
+
+    pub async fn for_mint(&mut self, mint: &str) -> Option<u8> {
+        if let Some(decimals) = self.known.get(mint) {
+            return Some(*decimals);
+        }
+        let client = self.client.as_ref()?;
+        let address: Pubkey = mint.parse().ok()?;
+        let data = client.get_account_data(&address).await.ok()?;
+        let decimals = StateWithExtensions::<Mint>::unpack(&data)
+            .ok()?
+            .base
+            .decimals;
+        self.known.insert(mint.to_string(), decimals);
+        Some(decimals)
+    }
+}
+
+// Which account a token account update is for. State updates don't name
+// their account, so it's looked up over RPC by owner and mint, and only an
+// update that matches exactly one account can be pinned to it. Without an RPC
+// endpoint none can be.
+pub struct AccountLookup {
+    client: Option<RpcClient>,
+    // What each owner and mint looked up to, so a busy account costs one
+    // lookup rather than one per update. None when the owner has several
+    // accounts for the mint, which more accounts can't resolve.
+    lookups: HashMap<(String, String), Option<Pubkey>>,
+}
+
+impl AccountLookup {
+    // At the commitment the stream's accounts are read at, or one it already
+    // shows may not be found yet
+    pub fn new(rpc_url: Option<String>, commitment: CommitmentConfig) -> Self {
+        Self {
+            client: rpc_url.map(|url| RpcClient::new_with_commitment(url, commitment)),
+            lookups: HashMap::new(),
+        }
+    }
+
+    // An account that isn't found yet, or a failed lookup, is tried again on
+    // the next update
+    pub async fn resolve(&mut self, account: &TokenAccountProto) -> Option<Pubkey> {
+        let key = (account.owner.clone(), account.mint.clone());
+        if let Some(pubkey) = self.lookups.get(&key) {
+            return *pubkey;
+        }
+        let client = self.client.as_ref()?;
+        let owner: Pubkey = account.owner.parse().ok()?;
+        let mint: Pubkey = account.mint.parse().ok()?;
+        let accounts = client
+            .get_token_accounts_by_owner(&owner, TokenAccountsFilter::Mint(mint))
+            .await
+            .ok()?;
+        let pubkey = match accounts.as_slice() {
+            [] => return None,
+            [keyed] => Some(keyed.pubkey.parse().ok()?),
+            _ => None,
+        };
+        self.lookups.insert(key, pubkey);
+        pubkey
+    }
+}
+
+// The last state of every token account seen on the stream, so a new one can
+// be logged as what changed. An update the lookup can't pin to its account is
+// logged in full. With --checkpoint the states are kept across restarts.
+pub struct AccountStates {
+    lookup: AccountLookup,
+    last: TrackedAccounts,
+}
+
+impl AccountStates {
+    pub fn new(
+        rpc_url: Option<String>,
+        commitment: CommitmentConfig,
+        last: TrackedAccounts,
+    ) -> Self {
+        Self {
+            lookup: AccountLookup::new(rpc_url, commitment),
+            last,
+        }
+    }
+
+    // None for an account seen for the first time, or that can't be told
+    // apart from the owner's other accounts for the mint. An owner change
+    // shows up once the account is found under its new owner.
+    pub async fn diff(
+        &mut self,
+        account: &TokenAccountProto,
+        decimals: Option<u8>,
+    ) -> Option<String> {
+        let pubkey = self.lookup.resolve(account).await?;
+        let previous = self.last.lock().unwrap().insert(pubkey, account.clone())?;
+        Some(format!(
+            "Token account {}: {}",
+            pubkey,
+            describe_changes(&previous, account, decimals)
+        ))
+    }
+}
+
+fn describe_changes(
+    previous: &TokenAccountProto,
+    current: &TokenAccountProto,
+    decimals: Option<u8>,
+) -> String {
+    let amount = |amount: u64| match decimals {
+        Some(decimals) => UiAmount::from_base_units(amount, decimals).to_string(),
+        None => format!("{} base units", amount),
+    };
+    let mut changes = vec![];
+    if current.amount != previous.amount {
+        let (sign, delta) = if current.amount > previous.amount {
+            ('+', current.amount - previous.amount)
+        } else {
+            ('-', previous.amount - current.amount)
+        };
+        changes.push(format!(
+            "balance {}{} (now {})",
+            sign,
+            amount(delta),
+            amount(current.amount)
+        ));
+    }
+    if current.owner != previous.owner {
+        changes.push(format!("owner {} -> {}", previous.owner, current.owner));
+    }
+    if current.delegate != previous.delegate
+        || current.delegated_amount != previous.delegated_amount
+    {
+        let mut change = format!(
+            "delegate {} -> {}",
+            previous.delegate.as_deref().unwrap_or("none"),
+            current.delegate.as_deref().unwrap_or("none")
+        );
+        if current.delegate.is_some() {
+            write!(change, " for {}", amount(current.delegated_amount)).unwrap();
+        }
+        changes.push(change);
+    }
+    if current.state != previous.state {
+        changes.push(format!(
+            "state {} -> {}",
+            account_state(previous.state),
+            account_state(current.state)
+        ));
+    }
+    if current.close_authority != previous.close_authority {
+        changes.push(format!(
+            "close authority {} -> {}",
+            previous.close_authority.as_deref().unwrap_or("none"),
+            current.close_authority.as_deref().unwrap_or("none")
+        ));
+    }
+    if changes.is_empty() {
+        return "no change".to_string();
+    }
+    changes.join(", ")
+}
+
+pub fn account_state(state: i32) -> &'static str {
+    AccountStateProto::try_from(state).map_or("Unknown", |state| state.as_str_name())
+}
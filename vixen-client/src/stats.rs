@@ -0,0 +1,208 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use svmkit_vixen_demo::{subscriber::ParsedUpdate, wire::WireStats};
+use tracing::info;
+
+use crate::filter::variant_name;
+
+// Types named in each periodic line, the final summary names them all
+const TOP_TYPES: usize = 5;
+
+// Ahead of every gRPC message: the compressed flag and the length
+const GRPC_PREFIX: u64 = 5;
+
+#[derive(Default)]
+struct Counts {
+    messages: u64,
+    bytes: u64,
+    // Whole messages as gRPC frames them uncompressed, for what compression
+    // saves
+    framed: u64,
+    decode_failures: u64,
+    // By oneof variant, e.g. `token_program_ix.transfer_checked`
+    types: HashMap<String, u64>,
+}
+
+impl Counts {
+    fn log(&self, label: &str, over: Duration, type_limit: usize) {
+        let secs = over.as_secs_f64().max(f64::EPSILON);
+        let failure_rate = if self.messages == 0 {
+            0.0
+        } else {
+            self.decode_failures as f64 / self.messages as f64 * 100.0
+        };
+        info!(
+            "{} over {:.1?}: {} update(s), {:.1} updates/s, {:.1} KiB/s, {:.2}% failed to decode; {}",
+            label,
+            over,
+            self.messages,
+            self.messages as f64 / secs,
+            self.bytes as f64 / 1024.0 / secs,
+            failure_rate,
+            self.top_types(type_limit)
+        );
+    }
+
+    // How the bytes that came off the connection compare to the same
+    // messages uncompressed
+    fn log_wire(&self, label: &str, over: Duration, wire_bytes: u64, encoding: Option<String>) {
+        let secs = over.as_secs_f64().max(f64::EPSILON);
+        let saved = if self.framed == 0 {
+            0.0
+        } else {
+            (1.0 - wire_bytes as f64 / self.framed as f64) * 100.0
+        };
+        info!(
+            "{} over {:.1?}: {:.1} KiB/s {}, {:.1}% less than the {:.1} KiB/s uncompressed",
+            label,
+            over,
+            wire_bytes as f64 / 1024.0 / secs,
+            match encoding {
+                Some(encoding) => format!("compressed with {}", encoding),
+                None => "uncompressed".to_string(),
+            },
+            saved,
+            self.framed as f64 / 1024.0 / secs
+        );
+    }
+
+    fn top_types(&self, limit: usize) -> String {
+        if self.types.is_empty() {
+            return "nothing decoded".to_string();
+        }
+        let mut types: Vec<_> = self.types.iter().collect();
+        types.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        let mut shown: Vec<_> = types
+            .iter()
+            .take(limit)
+            .map(|(name, count)| format!("{} {}", name, count))
+            .collect();
+        if types.len() > limit {
+            shown.push(format!("{} other type(s)", types.len() - limit));
+        }
+        shown.join(", ")
+    }
+}
+
+// Rates of the updates coming off the stream, logged every --stats-interval
+// for the window since the last report, and once more for the whole run when
+// the stream closes
+pub struct StreamThroughput {
+    pub interval: Duration,
+    started: Instant,
+    window_started: Instant,
+    window: Counts,
+    total: Counts,
+    // With --grpc-compression, and the wire bytes when the window started
+    wire: Option<(WireStats, u64)>,
+}
+
+impl StreamThroughput {
+    pub fn new(interval: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            interval,
+            started: now,
+            window_started: now,
+            window: Counts::default(),
+            total: Counts::default(),
+            wire: None,
+        }
+    }
+
+    // Report what the stream takes on the wire as well, before it's
+    // decompressed, for the subscriber's connections
+    pub fn watch_wire(&mut self, wire: WireStats) {
+        let bytes = wire.bytes();
+        self.wire = Some((wire, bytes));
+    }
+
+    pub fn received(&mut self, bytes: usize) {
+        for counts in [&mut self.window, &mut self.total] {
+            counts.messages += 1;
+            counts.bytes += bytes as u64;
+        }
+    }
+
+    // Every message off the stream, replays and empty ones included, by its
+    // encoded size
+    pub fn framed(&mut self, message_bytes: usize) {
+        for counts in [&mut self.window, &mut self.total] {
+            counts.framed += message_bytes as u64 + GRPC_PREFIX;
+        }
+    }
+
+    pub fn decoded(&mut self, update: &ParsedUpdate) {
+        let name = variant_name(update).unwrap_or_else(|| update.type_name());
+        *self.window.types.entry(name.clone()).or_default() += 1;
+        *self.total.types.entry(name).or_default() += 1;
+    }
+
+    pub fn decode_failed(&mut self) {
+        self.window.decode_failures += 1;
+        self.total.decode_failures += 1;
+    }
+
+    pub fn report(&mut self) {
+        let over = self.window_started.elapsed();
+        self.window.log("Stream throughput", over, TOP_TYPES);
+        if let Some((wire, window_started)) = &mut self.wire {
+            let bytes = wire.bytes();
+            self.window.log_wire(
+                "Stream on the wire",
+                over,
+                bytes - *window_started,
+                wire.encoding(),
+            );
+            *window_started = bytes;
+        }
+        self.window = Counts::default();
+        self.window_started = Instant::now();
+    }
+
+    pub fn summary(&self) {
+        let over = self.started.elapsed();
+        self.total.log("Stream totals", over, usize::MAX);
+        if let Some((wire, _)) = &self.wire {
+            self.total.log_wire(
+                "Stream totals on the wire",
+                over,
+                wire.bytes(),
+                wire.encoding(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_types_are_the_most_common_first() {
+        let counts = Counts {
+            types: [
+                ("burn", 1),
+                ("transfer_checked", 5),
+                ("mint_to", 2),
+                ("approve", 2),
+            ]
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            counts.top_types(3),
+            "transfer_checked 5, approve 2, mint_to 2, 1 other type(s)"
+        );
+        assert_eq!(
+            counts.top_types(usize::MAX),
+            "transfer_checked 5, approve 2, mint_to 2, burn 1"
+        );
+        assert_eq!(Counts::default().top_types(3), "nothing decoded");
+    }
+}
@@ -0,0 +1,730 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::ControlFlow,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures::{future, Stream, StreamExt as _};
+use metrics::counter;
+use rand::Rng as _;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+use yellowstone_vixen_proto::{
+    parser::{
+        token_extension_program_ix_proto::IxOneof, token_extension_state_proto::StateOneof,
+        token_program_ix_proto::IxOneof as TokenIx,
+        token_program_state_proto::StateOneof as TokenStateOneof, ExtendedTokenAccountProto,
+        TokenAccountProto, TokenExtensionProgramIxProto, TokenExtensionStateProto,
+        TokenProgramIxProto, TokenProgramStateProto,
+    },
+    prost::{DecodeError, Message, Name},
+    prost_types::Any,
+    stream::{program_streams_client::ProgramStreamsClient, SubscribeRequest, SubscribeUpdate},
+    tonic::{
+        codec::CompressionEncoding,
+        service::{interceptor::InterceptedService, Interceptor},
+        transport::{self, Endpoint},
+        Request, Status, Streaming,
+    },
+};
+
+use crate::{
+    error::{bail, Result},
+    wire::{CountedChannel, WireStats},
+};
+
+// tonic's own default, far above any token update
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+// A message from the stream, decoded as the proto its type URL names, or
+// passed on as is when no decoder is registered for that type
+#[derive(Debug)]
+pub enum ParsedUpdate {
+    Instruction(TokenExtensionProgramIxProto),
+    State(TokenExtensionStateProto),
+    // The same from the original SPL Token program
+    LegacyInstruction(TokenProgramIxProto),
+    LegacyState(TokenProgramStateProto),
+    // A type registered with `DecoderRegistry::register`, e.g. one of the
+    // Orca or Raydium protos
+    Custom(CustomUpdate),
+    Unknown(Any),
+}
+
+// A payload decoded as a message type the library doesn't know itself. It
+// keeps the payload alongside, so the outputs can still render it through the
+// descriptor set or fall back to its bytes.
+pub struct CustomUpdate {
+    any: Any,
+    message: Box<dyn std::any::Any + Send + Sync>,
+}
+
+impl CustomUpdate {
+    // The message as the type it was registered as, None when asked for
+    // another
+    pub fn downcast_ref<M: 'static>(&self) -> Option<&M> {
+        self.message.downcast_ref()
+    }
+
+    pub fn payload(&self) -> &Any {
+        &self.any
+    }
+
+    pub fn type_name(&self) -> &str {
+        type_name(&self.any.type_url)
+    }
+}
+
+impl fmt::Debug for CustomUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomUpdate")
+            .field("type_name", &self.type_name())
+            .field("bytes", &self.any.value.len())
+            .finish()
+    }
+}
+
+impl ParsedUpdate {
+    pub fn is_instruction(&self) -> bool {
+        matches!(
+            self,
+            ParsedUpdate::Instruction(_) | ParsedUpdate::LegacyInstruction(_)
+        )
+    }
+
+    pub fn is_state(&self) -> bool {
+        matches!(self, ParsedUpdate::State(_) | ParsedUpdate::LegacyState(_))
+    }
+
+    // An instruction both programs have, like `transfer_checked`, whichever
+    // of them it went through
+    pub fn token_instruction(&self) -> Option<&TokenIx> {
+        match self {
+            ParsedUpdate::Instruction(TokenExtensionProgramIxProto {
+                ix_oneof: Some(IxOneof::TokenProgramIx(ix)),
+            })
+            | ParsedUpdate::LegacyInstruction(ix) => ix.ix_oneof.as_ref(),
+            _ => None,
+        }
+    }
+
+    // The base state of a token account update from either program
+    pub fn token_account(&self) -> Option<&TokenAccountProto> {
+        match self {
+            ParsedUpdate::State(TokenExtensionStateProto {
+                state_oneof:
+                    Some(StateOneof::ExtendedTokenAccount(ExtendedTokenAccountProto {
+                        base_account: Some(account),
+                        ..
+                    })),
+            })
+            | ParsedUpdate::LegacyState(TokenProgramStateProto {
+                state_oneof: Some(TokenStateOneof::TokenAccount(account)),
+            }) => Some(account),
+            _ => None,
+        }
+    }
+}
+
+type Decoder = fn(&Any) -> Result<ParsedUpdate, DecodeError>;
+
+// The full proto name at the end of a type URL
+fn type_name(type_url: &str) -> &str {
+    type_url.rsplit('/').next().unwrap_or_default()
+}
+
+fn decode_custom<M: Message + Default + Send + Sync + 'static>(
+    any: &Any,
+) -> Result<ParsedUpdate, DecodeError> {
+    let message = M::decode(any.value.as_slice())?;
+    Ok(ParsedUpdate::Custom(CustomUpdate {
+        any: any.clone(),
+        message: Box::new(message),
+    }))
+}
+
+// Decoders keyed by the full proto name at the end of the `Any` type URL.
+// Bytes can happen to decode under the wrong message type, so the type URL
+// picks the decoder rather than trying each one in turn.
+#[derive(Clone)]
+pub struct DecoderRegistry {
+    decoders: HashMap<String, Decoder>,
+}
+
+impl DecoderRegistry {
+    pub fn empty() -> Self {
+        Self {
+            decoders: HashMap::new(),
+        }
+    }
+
+    // Instructions from both token programs
+    pub fn with_instructions(mut self) -> Self {
+        self.decoders
+            .insert(TokenExtensionProgramIxProto::full_name(), |any| {
+                TokenExtensionProgramIxProto::decode(any.value.as_slice())
+                    .map(ParsedUpdate::Instruction)
+            });
+        self.decoders
+            .insert(TokenProgramIxProto::full_name(), |any| {
+                TokenProgramIxProto::decode(any.value.as_slice())
+                    .map(ParsedUpdate::LegacyInstruction)
+            });
+        self
+    }
+
+    pub fn with_states(mut self) -> Self {
+        self.decoders
+            .insert(TokenExtensionStateProto::full_name(), |any| {
+                TokenExtensionStateProto::decode(any.value.as_slice()).map(ParsedUpdate::State)
+            });
+        self.decoders
+            .insert(TokenProgramStateProto::full_name(), |any| {
+                TokenProgramStateProto::decode(any.value.as_slice()).map(ParsedUpdate::LegacyState)
+            });
+        self
+    }
+
+    // Decode payloads of another message type too, e.g.
+    // `.register::<PoolStateProto>()` when the server runs the Raydium
+    // parser. They come out as `ParsedUpdate::Custom`, to be downcast back to
+    // `M`. Registering one of the token protos replaces its own decoder.
+    pub fn register<M: Message + Name + Default + Send + Sync + 'static>(mut self) -> Self {
+        self.decoders.insert(M::full_name(), decode_custom::<M>);
+        self
+    }
+
+    // Fails only when the payload doesn't decode as the type it claims to be
+    pub fn decode(&self, any: &Any) -> Result<ParsedUpdate, DecodeError> {
+        match self.decoders.get(type_name(&any.type_url)) {
+            Some(decode) => decode(any),
+            None => Ok(ParsedUpdate::Unknown(any.clone())),
+        }
+    }
+}
+
+// The instruction and state protos of both token programs
+impl Default for DecoderRegistry {
+    fn default() -> Self {
+        Self::empty().with_instructions().with_states()
+    }
+}
+
+// Runs on every request the subscriber sends, e.g. to attach an auth header.
+// Shared by every connection of a subscriber and its clones.
+#[derive(Clone, Default)]
+pub struct RequestInterceptor(Option<Arc<Mutex<dyn Interceptor + Send>>>);
+
+impl Interceptor for RequestInterceptor {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        match &self.0 {
+            Some(interceptor) => interceptor.lock().unwrap().call(request),
+            None => Ok(request),
+        }
+    }
+}
+
+pub type StreamsClient =
+    ProgramStreamsClient<InterceptedService<CountedChannel, RequestInterceptor>>;
+
+// Why a subscription attempt ended
+#[derive(Debug)]
+pub enum SubscriptionFailure {
+    // Never reached the server
+    Connect(transport::Error),
+    // Connected, but the server refused the subscription
+    Subscribe(Status),
+    // The stream failed after it was established
+    Stream(Status),
+    // The server closed the stream
+    Ended,
+    // Closed by the client after `ReconnectPolicy::drop_after`
+    Dropped,
+    // Closed by the client after `ReconnectPolicy::stale_after` without an
+    // update while the chain went on `slots` slot(s)
+    Stale { silent_for: Duration, slots: u64 },
+}
+
+impl fmt::Display for SubscriptionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubscriptionFailure::Connect(e) => e.fmt(f),
+            SubscriptionFailure::Subscribe(status) | SubscriptionFailure::Stream(status) => {
+                f.write_str(status.message())
+            }
+            SubscriptionFailure::Ended => f.write_str("stream ended"),
+            SubscriptionFailure::Dropped => f.write_str("stream dropped on purpose"),
+            SubscriptionFailure::Stale { silent_for, slots } => write!(
+                f,
+                "no update for {:.1}s while the chain advanced {} slot(s)",
+                silent_for.as_secs_f64(),
+                slots
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SubscriptionFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SubscriptionFailure::Connect(e) => Some(e),
+            SubscriptionFailure::Subscribe(status) | SubscriptionFailure::Stream(status) => {
+                Some(status)
+            }
+            SubscriptionFailure::Ended
+            | SubscriptionFailure::Dropped
+            | SubscriptionFailure::Stale { .. } => None,
+        }
+    }
+}
+
+// How `VixenSubscriber::run` backs off between attempts and when it gives up
+#[derive(Clone, Copy, Debug)]
+pub struct ReconnectPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // Failed attempts in a row before giving up; connecting resets the count
+    pub max_failures: u32,
+    // Failed attempts in a row on one endpoint before moving on to the next,
+    // once a first connection has been made. Until then each failure moves
+    // on, so the first endpoint that answers is used.
+    pub failover_after: u32,
+    // Close every connection once it has been up this long and reconnect, to
+    // exercise the reconnect path
+    pub drop_after: Option<Duration>,
+    // Resubscribe when the stream has sent nothing for this long while the
+    // chain kept producing slots, a connection that's up but no longer
+    // delivering. Needs `VixenSubscriber::with_slots` to read the slot from,
+    // and is off when zero.
+    pub stale_after: Option<Duration>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_failures: 10,
+            failover_after: 3,
+            drop_after: None,
+            stale_after: None,
+        }
+    }
+}
+
+// Where `VixenSubscriber::run` reads how far the chain has got, to tell a
+// stream that stopped delivering from a chain that stopped producing. None
+// when it can't tell right now.
+#[async_trait]
+pub trait SlotSource: Send + Sync {
+    async fn slot(&self) -> Option<u64>;
+}
+
+#[async_trait]
+impl SlotSource for RpcClient {
+    async fn slot(&self) -> Option<u64> {
+        self.get_slot()
+            .await
+            .inspect_err(|e| debug!("Unable to read the slot for the stream watchdog: {}", e))
+            .ok()
+    }
+}
+
+// What `VixenSubscriber::run` does as the subscription goes. Breaking from
+// `connected` or `update` stops it cleanly.
+#[async_trait]
+pub trait SubscriptionHooks: Send {
+    // After every successful (re)subscription, before its first update
+    async fn connected(&mut self, _attempt: u32) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    async fn update(&mut self, update: SubscribeUpdate) -> ControlFlow<()>;
+
+    // After every failed attempt, with the delay before the next one, or None
+    // when giving up
+    fn failed(
+        &mut self,
+        _attempt: u32,
+        _failure: &SubscriptionFailure,
+        _retry_in: Option<Duration>,
+    ) {
+    }
+}
+
+// Subscribes to a Vixen server's program streams, either once with the
+// decoded updates as a `Stream` or reconnecting with the raw updates handed
+// to hooks, for embedding the subscription in other demos
+#[derive(Clone)]
+pub struct VixenSubscriber {
+    // The primary first, then the ones `run` fails over to in turn
+    endpoints: Vec<Endpoint>,
+    interceptor: RequestInterceptor,
+    max_message_size: usize,
+    decoders: DecoderRegistry,
+    // Offered to the server for the stream
+    compression: Vec<CompressionEncoding>,
+    // What requests are sent with, which the server must accept
+    send_compression: Option<CompressionEncoding>,
+    wire: WireStats,
+    slots: Option<Arc<dyn SlotSource>>,
+}
+
+impl VixenSubscriber {
+    pub fn new(endpoint: Endpoint) -> Self {
+        Self {
+            endpoints: vec![endpoint],
+            interceptor: RequestInterceptor::default(),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            decoders: DecoderRegistry::default(),
+            compression: vec![],
+            send_compression: None,
+            wire: WireStats::default(),
+            slots: None,
+        }
+    }
+
+    pub fn with_decoders(mut self, decoders: DecoderRegistry) -> Self {
+        self.decoders = decoders;
+        self
+    }
+
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    // Ask the server to compress the stream with any of these, e.g. over a
+    // WAN link, where the uncompressed Token-2022 states are heavy
+    pub fn with_compression(mut self, encodings: Vec<CompressionEncoding>) -> Self {
+        self.compression = encodings;
+        self
+    }
+
+    // Compress the subscription requests too. A server that doesn't accept
+    // the encoding refuses them, and they're tiny, so it's only worth it for
+    // a proxy that insists.
+    pub fn with_send_compression(mut self, encoding: Option<CompressionEncoding>) -> Self {
+        self.send_compression = encoding;
+        self
+    }
+
+    // Where `run` reads the slot from for `ReconnectPolicy::stale_after`
+    pub fn with_slots(mut self, slots: Arc<dyn SlotSource>) -> Self {
+        self.slots = Some(slots);
+        self
+    }
+
+    // The bytes received so far and the encoding negotiated, across every
+    // connection this subscriber and its clones make
+    pub fn wire_stats(&self) -> &WireStats {
+        &self.wire
+    }
+
+    pub fn with_interceptor(mut self, interceptor: impl Interceptor + Send + 'static) -> Self {
+        self.interceptor = RequestInterceptor(Some(Arc::new(Mutex::new(interceptor))));
+        self
+    }
+
+    // Servers serving the same stream, for `run` to fail over to when the
+    // primary stops answering
+    pub fn with_fallback_endpoints(
+        mut self,
+        endpoints: impl IntoIterator<Item = Endpoint>,
+    ) -> Self {
+        self.endpoints.extend(endpoints);
+        self
+    }
+
+    // The primary endpoint
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoints[0]
+    }
+
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.endpoints
+    }
+
+    // To the primary endpoint
+    pub async fn connect(&self) -> Result<StreamsClient, transport::Error> {
+        self.connect_to(self.endpoint()).await
+    }
+
+    async fn connect_to(&self, endpoint: &Endpoint) -> Result<StreamsClient, transport::Error> {
+        let channel = CountedChannel::new(endpoint.connect().await?, self.wire.clone());
+        let mut client = ProgramStreamsClient::with_interceptor(channel, self.interceptor.clone())
+            .max_decoding_message_size(self.max_message_size);
+        for encoding in &self.compression {
+            client = client.accept_compressed(*encoding);
+        }
+        if let Some(encoding) = self.send_compression {
+            client = client.send_compressed(encoding);
+        }
+        Ok(client)
+    }
+
+    // One attempt at connecting and subscribing, handing back the updates as
+    // sent, for callers that keep the raw payloads
+    pub async fn subscribe_raw(
+        &self,
+        program: &Pubkey,
+    ) -> Result<Streaming<SubscribeUpdate>, SubscriptionFailure> {
+        self.subscribe_to(self.endpoint(), program).await
+    }
+
+    async fn subscribe_to(
+        &self,
+        endpoint: &Endpoint,
+        program: &Pubkey,
+    ) -> Result<Streaming<SubscribeUpdate>, SubscriptionFailure> {
+        let mut client = self
+            .connect_to(endpoint)
+            .await
+            .map_err(SubscriptionFailure::Connect)?;
+        let req = SubscribeRequest {
+            program: program.to_string(),
+        };
+        let res = client
+            .subscribe(req)
+            .await
+            .map_err(SubscriptionFailure::Subscribe)?;
+        if !self.compression.is_empty() {
+            let encoding = res
+                .metadata()
+                .get("grpc-encoding")
+                .and_then(|encoding| encoding.to_str().ok())
+                .filter(|encoding| *encoding != "identity")
+                .map(str::to_string);
+            match &encoding {
+                Some(encoding) => info!("Stream for {} compressed with {}", program, encoding),
+                None => warn!(
+                    "Stream for {} is uncompressed, the server accepted none of the encodings offered",
+                    program
+                ),
+            }
+            self.wire.negotiated(encoding);
+        }
+        Ok(res.into_inner())
+    }
+
+    // One connection per call, with no reconnect: the stream ends when the
+    // server closes it or it fails. Payloads of an unregistered type come
+    // through as `ParsedUpdate::Unknown`, ones that fail to decode as their
+    // type are logged and skipped.
+    pub async fn subscribe(&self, program: &Pubkey) -> Result<impl Stream<Item = ParsedUpdate>> {
+        let stream = self.subscribe_raw(program).await?;
+        let decoders = self.decoders.clone();
+        Ok(stream
+            .take_while(|update| {
+                if let Err(status) = update {
+                    warn!("Vixen stream failed: {}", status);
+                }
+                future::ready(update.is_ok())
+            })
+            .filter_map(move |update| {
+                let parsed = update
+                    .ok()
+                    .and_then(|update| update.parsed)
+                    .and_then(|any| {
+                        decoders
+                            .decode(&any)
+                            .inspect_err(|e| {
+                                warn!(
+                                    "Skipping {} payload that failed to decode: {}",
+                                    any.type_url, e
+                                )
+                            })
+                            .ok()
+                    });
+                future::ready(parsed)
+            }))
+    }
+
+    // Stays subscribed to a program, reconnecting with backoff until
+    // shutdown, until a hook breaks, or until `policy.max_failures` attempts
+    // in a row fail, which is the only error. With fallback endpoints it
+    // moves on to the next after `policy.failover_after` failures in a row on
+    // one, and stays on whichever it reaches.
+    pub async fn run(
+        &self,
+        program: &Pubkey,
+        policy: ReconnectPolicy,
+        hooks: &mut impl SubscriptionHooks,
+        shutdown: &CancellationToken,
+    ) -> Result<()> {
+        let mut attempt = 0;
+        let mut failures = 0;
+        let mut backoff = policy.initial_backoff;
+        let mut current = 0;
+        // On the current endpoint
+        let mut endpoint_failures = 0;
+        let mut ever_connected = false;
+        loop {
+            attempt += 1;
+            let endpoint = &self.endpoints[current];
+            let subscribed = tokio::select! {
+                subscribed = self.subscribe_to(endpoint, program) => subscribed,
+                _ = shutdown.cancelled() => return Ok(()),
+            };
+            let failure = match subscribed {
+                Ok(mut stream) => {
+                    info!(
+                        "Connected to Vixen gRPC server at {}",
+                        endpoint_host(endpoint)
+                    );
+                    failures = 0;
+                    endpoint_failures = 0;
+                    ever_connected = true;
+                    backoff = policy.initial_backoff;
+                    if hooks.connected(attempt).await.is_break() {
+                        return Ok(());
+                    }
+                    let dropped = async {
+                        match policy.drop_after {
+                            Some(after) => tokio::time::sleep(after).await,
+                            None => future::pending().await,
+                        }
+                    };
+                    tokio::pin!(dropped);
+                    let mut last_update = Instant::now();
+                    // Only what this host's monotonic clock says and the
+                    // chain's own slots are compared, never timestamps from
+                    // elsewhere, so skewed clocks can't make it trip
+                    // A zero stale_after would check without ever waiting
+                    let mut watchdog = policy
+                        .stale_after
+                        .filter(|after| !after.is_zero())
+                        .zip(self.slots.as_ref())
+                        .map(|(after, slots)| {
+                            let mut ticks = tokio::time::interval(after);
+                            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                            (ticks, after, slots)
+                        });
+                    // The slot at the last check, and when it was read
+                    let mut baseline: Option<(Instant, u64)> = None;
+                    loop {
+                        let check = async {
+                            match &mut watchdog {
+                                Some((ticks, ..)) => ticks.tick().await,
+                                None => future::pending().await,
+                            }
+                        };
+                        let message = tokio::select! {
+                            message = stream.message() => message,
+                            _ = &mut dropped => break SubscriptionFailure::Dropped,
+                            checked_at = check => {
+                                let Some((_, after, slots)) = &watchdog else {
+                                    continue;
+                                };
+                                let slot = tokio::select! {
+                                    slot = tokio::time::timeout(*after, slots.slot()) => {
+                                        slot.ok().flatten()
+                                    }
+                                    _ = shutdown.cancelled() => return Ok(()),
+                                };
+                                if let (Some((since, from)), Some(slot)) = (baseline, slot) {
+                                    if last_update < since && slot > from {
+                                        break SubscriptionFailure::Stale {
+                                            silent_for: last_update.elapsed(),
+                                            slots: slot - from,
+                                        };
+                                    }
+                                }
+                                baseline = slot.map(|slot| (checked_at.into_std(), slot));
+                                continue;
+                            }
+                            _ = shutdown.cancelled() => return Ok(()),
+                        };
+                        last_update = Instant::now();
+                        match message {
+                            Ok(Some(update)) => {
+                                if hooks.update(update).await.is_break() {
+                                    return Ok(());
+                                }
+                            }
+                            Ok(None) => break SubscriptionFailure::Ended,
+                            Err(status) => break SubscriptionFailure::Stream(status),
+                        }
+                    }
+                }
+                Err(failure) => failure,
+            };
+            // Not the server's doing, so it neither counts as a failure nor
+            // waits to reconnect
+            if let SubscriptionFailure::Dropped = failure {
+                hooks.failed(attempt, &failure, Some(Duration::ZERO));
+                info!("Dropped the Vixen stream on purpose, reconnecting");
+                continue;
+            }
+            // Nor does a stale one, the server accepted it and may well
+            // deliver on the next
+            if let SubscriptionFailure::Stale { .. } = failure {
+                hooks.failed(attempt, &failure, Some(Duration::ZERO));
+                counter!("vixen_stale_resubscribes_total").increment(1);
+                warn!(
+                    "Vixen stream for {} at {} went stale, {}, resubscribing",
+                    program,
+                    endpoint_host(endpoint),
+                    failure
+                );
+                continue;
+            }
+            failures += 1;
+            endpoint_failures += 1;
+            let failover_after = if ever_connected {
+                policy.failover_after.max(1)
+            } else {
+                1
+            };
+            if self.endpoints.len() > 1 && endpoint_failures >= failover_after {
+                current = (current + 1) % self.endpoints.len();
+                endpoint_failures = 0;
+                counter!("vixen_grpc_failovers_total").increment(1);
+                warn!(
+                    "Vixen stream failed {} time(s) in a row at {}, failing over to {}",
+                    failover_after,
+                    endpoint_host(endpoint),
+                    endpoint_host(&self.endpoints[current])
+                );
+            }
+            if failures >= policy.max_failures {
+                hooks.failed(attempt, &failure, None);
+                bail!(
+                    transport,
+                    "Giving up on the Vixen stream for {} after {} consecutive failure(s): {}",
+                    program,
+                    failures,
+                    failure
+                );
+            }
+            // Up to half of the backoff is random so clients dropped by the
+            // same server restart don't all reconnect at once
+            let delay = backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0));
+            hooks.failed(attempt, &failure, Some(delay));
+            warn!(
+                "Vixen stream attempt {} failed ({}/{} consecutive failures), reconnecting in {:?}: {}",
+                attempt, failures, policy.max_failures, delay, failure
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = shutdown.cancelled() => return Ok(()),
+            }
+            backoff = (backoff * 2).min(policy.max_backoff);
+        }
+    }
+}
+
+// Host and port only, as the URL may carry credentials
+fn endpoint_host(endpoint: &Endpoint) -> String {
+    let uri = endpoint.uri();
+    match uri.port_u16() {
+        Some(port) => format!("{}:{}", uri.host().unwrap_or_default(), port),
+        None => uri.host().unwrap_or_default().to_string(),
+    }
+}
@@ -0,0 +1,1115 @@
+use std::{fmt, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+use metrics::counter;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::RpcRequestAirdropConfig,
+    rpc_custom_error::{
+        JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED, JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    },
+    rpc_request::RpcError,
+};
+use solana_sdk::{
+    account::Account,
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::{
+    extension::{
+        interest_bearing_mint,
+        memo_transfer::instruction::enable_required_transfer_memos,
+        metadata_pointer,
+        transfer_fee::{
+            instruction::{harvest_withheld_tokens_to_mint, initialize_transfer_fee_config},
+            TransferFee, TransferFeeAmount,
+        },
+        BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+    },
+    instruction::{
+        burn_checked, close_account, initialize_account, initialize_mint, initialize_multisig,
+        reallocate,
+    },
+    state::{Account as TokenAccount, Mint, Multisig},
+};
+use tracing::{info, warn};
+
+use crate::{
+    amount::UiAmount,
+    chaos::ChaosOptions,
+    cluster::{detect_cluster, Cluster},
+    error::{bail, Context as _, Error, Result},
+    explain::explain_transaction,
+    program::MEMO_PROGRAM_ID,
+};
+
+// Options controlling how the demo transactions are submitted and read back
+#[derive(Clone, Copy)]
+pub struct SendOptions {
+    pub explain: bool,
+    pub confirm_attempts: u32,
+    pub confirm_interval: Duration,
+    pub read_attempts: u32,
+    pub airdrop_timeout: Duration,
+    pub send_attempts: u32,
+    // For a transaction from its first send to its confirmation, retries
+    // included
+    pub send_timeout: Duration,
+    // Log each transaction instead of sending it
+    pub dry_run: bool,
+    // Faults to inject into every send, see --chaos
+    pub chaos: Option<ChaosOptions>,
+    // What the airdrop and the transactions are confirmed at, the client's
+    // commitment when None. Reads always go at the client's.
+    pub airdrop_commitment: Option<CommitmentConfig>,
+    pub tx_commitment: Option<CommitmentConfig>,
+    // The cluster the RPC node was detected as, so airdrops don't ask again;
+    // detected for each airdrop when None
+    pub cluster: Option<Cluster>,
+}
+
+// The same defaults as the vixen-client flags
+impl Default for SendOptions {
+    fn default() -> Self {
+        Self {
+            explain: false,
+            confirm_attempts: 30,
+            confirm_interval: Duration::from_secs(1),
+            read_attempts: 10,
+            airdrop_timeout: Duration::from_secs(30),
+            send_attempts: 3,
+            send_timeout: Duration::from_secs(120),
+            dry_run: false,
+            chaos: None,
+            airdrop_commitment: None,
+            tx_commitment: None,
+            cluster: None,
+        }
+    }
+}
+
+impl SendOptions {
+    pub fn tx_commitment(&self, client: &RpcClient) -> CommitmentConfig {
+        self.tx_commitment.unwrap_or_else(|| client.commitment())
+    }
+}
+
+pub async fn airdrop_new_address(
+    pubkey: Pubkey,
+    lamports: u64,
+    rpc_client: &RpcClient,
+    send_opts: SendOptions,
+) -> Result<()> {
+    let cluster = match send_opts.cluster {
+        Some(cluster) => Ok(cluster),
+        None => detect_cluster(rpc_client).await,
+    };
+    if let Ok(Cluster::Mainnet) = cluster {
+        warn!(
+            "Connected to mainnet, where airdrops are not available; the airdrop will likely fail"
+        );
+    }
+    let commitment = send_opts
+        .airdrop_commitment
+        .unwrap_or_else(|| rpc_client.commitment());
+    // Faucets rate limit and return the odd transient error, so the request
+    // itself gets the same number of attempts as a transaction
+    let mut attempt = 1;
+    let signature = loop {
+        let res = rpc_client
+            .request_airdrop_with_config(
+                &pubkey,
+                lamports,
+                RpcRequestAirdropConfig {
+                    recent_blockhash: None,
+                    commitment: Some(commitment),
+                },
+            )
+            .await;
+        match res {
+            Ok(signature) => break signature,
+            Err(e) if attempt < send_opts.send_attempts => {
+                counter!("vixen_airdrop_retries_total").increment(1);
+                warn!(
+                    "Airdrop request failed (attempt {}/{}), retrying: {}",
+                    attempt, send_opts.send_attempts, e
+                );
+                tokio::time::sleep(send_opts.confirm_interval).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+    // Airdrops can take a while to land, finalized ones especially, so they
+    // get their own time budget polled at the usual interval
+    let attempts = send_opts
+        .airdrop_timeout
+        .div_duration_f64(send_opts.confirm_interval)
+        .ceil() as u32;
+    let airdrop_opts = SendOptions {
+        confirm_attempts: attempts.max(1),
+        ..send_opts
+    };
+    confirm_signature(rpc_client, &signature, commitment, airdrop_opts)
+        .await
+        .context(Error::Rpc, || {
+            format!(
+                "Airdrop to {} not confirmed after {:?}",
+                pubkey, send_opts.airdrop_timeout
+            )
+        })
+}
+
+// Fund an address with a plain SOL transfer from a keypair of our own, for
+// clusters whose faucet is disabled or rate limited. Checks the faucet can
+// cover it first, so an empty one fails with its balance rather than an
+// opaque simulation error.
+pub async fn transfer_from_faucet(
+    faucet: &Keypair,
+    pubkey: Pubkey,
+    lamports: u64,
+    rpc_client: &RpcClient,
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    let balance = rpc_client.get_balance(&faucet.pubkey()).await?;
+    // Plus the transfer's own fee, one signature at the base fee
+    if balance < lamports.saturating_add(5_000) {
+        bail!(
+            workload,
+            "Faucet {} holds {} lamports, not enough to send {} to {}",
+            faucet.pubkey(),
+            balance,
+            lamports,
+            pubkey
+        );
+    }
+    let transfer = system_instruction::transfer(&faucet.pubkey(), &pubkey, lamports);
+    let signature = send_instructions(rpc_client, faucet, &[], &[transfer], send_opts).await?;
+    info!(
+        "Sent {} lamports to {} from faucet {}",
+        lamports,
+        pubkey,
+        faucet.pubkey()
+    );
+    Ok(signature)
+}
+
+// Send the transaction and poll for confirmation ourselves, so the number of
+// attempts and the delay between them are under our control. A transient
+// failure to send resends the same transaction, which can only land once.
+pub async fn send_and_confirm(
+    rpc_client: &RpcClient,
+    tx: &Transaction,
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    if send_opts.explain {
+        explain_transaction(tx);
+    }
+    if let Some(chaos) = send_opts.chaos {
+        chaos.delay().await;
+    }
+    let mut attempt = 1;
+    let signature = loop {
+        match rpc_client.send_transaction(tx).await {
+            Ok(signature) => break signature,
+            Err(e) if is_transient(&e) && attempt < send_opts.send_attempts => {
+                counter!("vixen_transaction_retries_total").increment(1);
+                warn!(
+                    "Sending failed (attempt {}/{}), resending: {}",
+                    attempt, send_opts.send_attempts, e
+                );
+                tokio::time::sleep(send_opts.confirm_interval).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+    counter!("vixen_transactions_submitted_total").increment(1);
+    if let Some(chaos) = send_opts.chaos {
+        chaos.resend(rpc_client, tx, &signature).await;
+    }
+    let commitment = send_opts.tx_commitment(rpc_client);
+    confirm_signature(rpc_client, &signature, commitment, send_opts).await?;
+    Ok(signature)
+}
+
+// Build, sign and send a transaction paid for by `payer`. `signers` lists any
+// other keypairs that must sign, repeating the payer is harmless.
+pub async fn send_instructions(
+    client: &RpcClient,
+    payer: &Keypair,
+    signers: &[&Keypair],
+    instructions: &[Instruction],
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    let mut all_signers = vec![payer];
+    for signer in signers {
+        if !all_signers.iter().any(|s| s.pubkey() == signer.pubkey()) {
+            all_signers.push(signer);
+        }
+    }
+    if send_opts.dry_run {
+        return log_dry_run(client, payer, &all_signers, instructions).await;
+    }
+    submit_with_retry(client, payer, &all_signers, instructions, send_opts).await
+}
+
+// Sign with the latest blockhash, send and confirm at `tx_commitment`,
+// re-signing with a fresh blockhash once the last one can no
+// longer land. Every workload transaction goes through here, with up to
+// `send_attempts` attempts per step and `send_timeout` for the whole of it.
+pub async fn submit_with_retry(
+    client: &RpcClient,
+    payer: &Keypair,
+    signers: &[&Keypair],
+    instructions: &[Instruction],
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    let submit = async {
+        let mut attempt = 1;
+        loop {
+            let recent_blockhash = match client.get_latest_blockhash().await {
+                Ok(blockhash) => blockhash,
+                Err(e) if is_transient(&e) && attempt < send_opts.send_attempts => {
+                    warn!(
+                        "Unable to fetch a blockhash (attempt {}/{}), retrying: {}",
+                        attempt, send_opts.send_attempts, e
+                    );
+                    tokio::time::sleep(send_opts.confirm_interval).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let tx = Transaction::new_signed_with_payer(
+                instructions,
+                Some(&payer.pubkey()),
+                signers,
+                recent_blockhash,
+            );
+            let err = match send_and_confirm(client, &tx, send_opts).await {
+                Ok(signature) => return Ok(signature),
+                Err(err) => err,
+            };
+            if !blockhash_expired(client, &err, &recent_blockhash).await {
+                return Err(err);
+            }
+            if attempt >= send_opts.send_attempts {
+                return Err(err).context(Error::Rpc, || {
+                    format!(
+                        "Transaction failed after {} attempt(s) with a fresh blockhash",
+                        attempt
+                    )
+                });
+            }
+            counter!("vixen_transaction_retries_total").increment(1);
+            warn!(
+                "Blockhash expired (attempt {}/{}), re-signing with a fresh one: {}",
+                attempt, send_opts.send_attempts, err
+            );
+            tokio::time::sleep(send_opts.confirm_interval).await;
+            attempt += 1;
+        }
+    };
+    match tokio::time::timeout(send_opts.send_timeout, submit).await {
+        Ok(res) => res,
+        Err(_) => bail!(
+            rpc,
+            "Transaction not confirmed within the {:?} send timeout",
+            send_opts.send_timeout
+        ),
+    }
+}
+
+// Failures that say nothing about the transaction itself: the node couldn't
+// be reached, or is still catching up with the cluster
+fn is_transient(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) => true,
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => matches!(
+            *code,
+            JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+                | JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+        ),
+        _ => false,
+    }
+}
+
+// The blockhash only completes the message, so any will do when the node
+// can't be reached. The returned signature is a placeholder.
+async fn log_dry_run(
+    client: &RpcClient,
+    payer: &Keypair,
+    signers: &[&Keypair],
+    instructions: &[Instruction],
+) -> Result<Signature> {
+    let recent_blockhash = client.get_latest_blockhash().await.unwrap_or_default();
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        signers,
+        recent_blockhash,
+    );
+    explain_transaction(&tx);
+    let message = tx.message.serialize();
+    info!(
+        "Dry run, not sending the {} byte message: {}",
+        message.len(),
+        BASE64.encode(&message)
+    );
+    match client.get_fee_for_message(&tx.message).await {
+        Ok(fee) => info!("Estimated fee: {} lamports", fee),
+        Err(e) => info!("Fee estimate unavailable: {}", e),
+    }
+    Ok(Signature::default())
+}
+
+// Re-signing is only safe when the first transaction can no longer land:
+// either the node rejected its blockhash outright, or it went unconfirmed
+// until the blockhash expired
+async fn blockhash_expired(client: &RpcClient, err: &Error, blockhash: &Hash) -> bool {
+    if let Some(client_err) = err.downcast_ref::<ClientError>() {
+        return client_err.get_transaction_error() == Some(TransactionError::BlockhashNotFound);
+    }
+    err.downcast_ref::<NotConfirmed>().is_some()
+        && matches!(
+            client
+                .is_blockhash_valid(blockhash, CommitmentConfig::processed())
+                .await,
+            Ok(false)
+        )
+}
+
+#[derive(Debug)]
+struct NotConfirmed {
+    signature: Signature,
+    attempts: u32,
+    interval: Duration,
+}
+
+impl fmt::Display for NotConfirmed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Transaction {} was not confirmed after {} attempt(s) {:?} apart",
+            self.signature, self.attempts, self.interval
+        )
+    }
+}
+
+impl std::error::Error for NotConfirmed {}
+
+async fn confirm_signature(
+    rpc_client: &RpcClient,
+    signature: &Signature,
+    commitment: CommitmentConfig,
+    send_opts: SendOptions,
+) -> Result<()> {
+    for _ in 0..send_opts.confirm_attempts {
+        // Unlike confirm_transaction, the status tells a rejected transaction
+        // apart from one that just hasn't landed yet
+        let statuses = match rpc_client.get_signature_statuses(&[*signature]).await {
+            Ok(statuses) => statuses.value,
+            // A poll that didn't get an answer just counts as an attempt
+            Err(e) if is_transient(&e) => {
+                warn!("Unable to poll the status of {}: {}", signature, e);
+                vec![]
+            }
+            Err(e) => return Err(e.into()),
+        };
+        // No entry is no better than a null one, it hasn't landed yet
+        if let Some(status) = statuses.first().and_then(Option::as_ref) {
+            if let Some(err) = &status.err {
+                bail!(workload, "Transaction {} failed: {}", signature, err);
+            }
+            if status.satisfies_commitment(commitment) {
+                return Ok(());
+            }
+        }
+        tokio::time::sleep(send_opts.confirm_interval).await;
+    }
+    Err(Error::Rpc(Box::new(NotConfirmed {
+        signature: *signature,
+        attempts: send_opts.confirm_attempts,
+        interval: send_opts.confirm_interval,
+    })))
+}
+
+pub async fn create_mint(
+    mint_keypair: &Keypair,
+    kp: &Keypair,
+    rpc_client: &RpcClient,
+    decimals: u8,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    create_mint_account(
+        mint_keypair,
+        kp,
+        rpc_client,
+        decimals,
+        vec![],
+        None,
+        token_program,
+        send_opts,
+    )
+    .await
+}
+
+// Basis points and cap of a Token-2022 transfer fee, in base units
+#[derive(Clone, Copy, Debug)]
+pub struct TransferFeeParams {
+    pub basis_points: u16,
+    pub maximum_fee: u64,
+}
+
+impl TransferFeeParams {
+    // The fee the program will withhold from a transfer of `amount`, which
+    // transfer_checked_with_fee must state exactly
+    pub fn fee_for(&self, amount: u64) -> Option<u64> {
+        TransferFee {
+            epoch: 0.into(),
+            maximum_fee: self.maximum_fee.into(),
+            transfer_fee_basis_points: self.basis_points.into(),
+        }
+        .calculate_fee(amount)
+    }
+}
+
+// Create a mint with the transfer fee config extension, with the payer as
+// both the fee config and withdraw authority
+pub async fn create_mint_with_transfer_fee(
+    mint_keypair: &Keypair,
+    kp: &Keypair,
+    rpc_client: &RpcClient,
+    decimals: u8,
+    fee: TransferFeeParams,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    let extensions = MintExtensions {
+        transfer_fee: Some(fee),
+        ..MintExtensions::default()
+    };
+    create_mint_with_extensions(
+        mint_keypair,
+        kp,
+        rpc_client,
+        decimals,
+        extensions,
+        token_program,
+        send_opts,
+    )
+    .await
+}
+
+// The Token-2022 mint extensions the demo knows how to set up, with the payer
+// as every authority they name
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MintExtensions {
+    pub transfer_fee: Option<TransferFeeParams>,
+    // Annual rate in basis points, negative rates included
+    pub interest_rate: Option<i16>,
+    // Points at the mint itself, where the token metadata extension would live
+    pub metadata_pointer: bool,
+    // Not an extension, but set up with them: lets the payer freeze and thaw
+    // the mint's token accounts
+    pub freeze_authority: bool,
+}
+
+impl MintExtensions {
+    pub fn types(&self) -> Vec<ExtensionType> {
+        let mut types = vec![];
+        if self.transfer_fee.is_some() {
+            types.push(ExtensionType::TransferFeeConfig);
+        }
+        if self.interest_rate.is_some() {
+            types.push(ExtensionType::InterestBearingConfig);
+        }
+        if self.metadata_pointer {
+            types.push(ExtensionType::MetadataPointer);
+        }
+        types
+    }
+}
+
+pub async fn create_mint_with_extensions(
+    mint_keypair: &Keypair,
+    kp: &Keypair,
+    rpc_client: &RpcClient,
+    decimals: u8,
+    extensions: MintExtensions,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    let mint_pubkey = mint_keypair.pubkey();
+    let mut initializers = vec![];
+    if let Some(fee) = extensions.transfer_fee {
+        let ix = initialize_transfer_fee_config(
+            token_program,
+            &mint_pubkey,
+            Some(&kp.pubkey()),
+            Some(&kp.pubkey()),
+            fee.basis_points,
+            fee.maximum_fee,
+        )?;
+        info!(
+            "Mint will charge a {} basis point transfer fee, capped at {} base units",
+            fee.basis_points, fee.maximum_fee
+        );
+        initializers.push((ExtensionType::TransferFeeConfig, ix));
+    }
+    if let Some(rate) = extensions.interest_rate {
+        let ix = interest_bearing_mint::instruction::initialize(
+            token_program,
+            &mint_pubkey,
+            Some(kp.pubkey()),
+            rate,
+        )?;
+        info!("Mint will accrue interest at {} basis points a year", rate);
+        initializers.push((ExtensionType::InterestBearingConfig, ix));
+    }
+    if extensions.metadata_pointer {
+        let ix = metadata_pointer::instruction::initialize(
+            token_program,
+            &mint_pubkey,
+            Some(kp.pubkey()),
+            Some(mint_pubkey),
+        )?;
+        info!("Mint will point to itself for its metadata");
+        initializers.push((ExtensionType::MetadataPointer, ix));
+    }
+    if extensions.freeze_authority {
+        info!("Mint will let the payer freeze its token accounts");
+    }
+    create_mint_account(
+        mint_keypair,
+        kp,
+        rpc_client,
+        decimals,
+        initializers,
+        extensions.freeze_authority.then(|| kp.pubkey()),
+        token_program,
+        send_opts,
+    )
+    .await
+}
+
+// Extensions are initialized between allocating the account, which has to be
+// sized for them, and initializing the mint itself
+#[allow(clippy::too_many_arguments)]
+async fn create_mint_account(
+    mint_keypair: &Keypair,
+    kp: &Keypair,
+    rpc_client: &RpcClient,
+    decimals: u8,
+    extensions: Vec<(ExtensionType, Instruction)>,
+    freeze_authority: Option<Pubkey>,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    let mint_pubkey = mint_keypair.pubkey();
+    let (extension_types, extension_ixs): (Vec<_>, Vec<_>) = extensions.into_iter().unzip();
+    let space = ExtensionType::try_calculate_account_len::<Mint>(&extension_types)?;
+
+    // Calculate minimum balance for rent exemption
+    let rent = rent_exempt_balance(rpc_client, space, send_opts).await?;
+    info!("Mint Address {}", mint_keypair.pubkey());
+    // Create the mint account
+    let create_account_ix = system_instruction::create_account(
+        &kp.pubkey(),
+        &mint_pubkey,
+        rent,
+        space as u64,
+        token_program,
+    );
+
+    // Initialize the mint
+    let initialize_mint_ix = initialize_mint(
+        token_program,
+        &mint_pubkey,
+        &kp.pubkey(), // Mint authority
+        freeze_authority.as_ref(),
+        decimals,
+    )?;
+
+    // Build and send the transaction
+    let mut instructions = vec![create_account_ix];
+    instructions.extend(extension_ixs);
+    instructions.push(initialize_mint_ix);
+    let signature =
+        send_instructions(rpc_client, kp, &[mint_keypair], &instructions, send_opts).await?;
+    info!("Mint created with signature: {}", signature);
+    Ok(signature)
+}
+
+// Derive each owner's associated token account for the mint, like a wallet
+// would, creating only the ones that don't exist yet
+pub async fn create_associated_token_accounts(
+    client: &RpcClient,
+    payer: &Keypair,
+    mint_pubkey: &Pubkey,
+    owners: &[Pubkey; 2],
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<(Pubkey, Pubkey)> {
+    let [ata1, ata2] = owners.map(|owner| {
+        get_associated_token_address_with_program_id(&owner, mint_pubkey, token_program)
+    });
+
+    let mut instructions = vec![];
+    for (owner, ata) in owners.iter().zip([ata1, ata2]) {
+        if !send_opts.dry_run
+            && client
+                .get_account_with_commitment(&ata, client.commitment())
+                .await?
+                .value
+                .is_some()
+        {
+            info!("Associated token account {} already exists", ata);
+            continue;
+        }
+        // The idempotent variant also covers an account created after the check
+        instructions.push(create_associated_token_account_idempotent(
+            &payer.pubkey(),
+            owner,
+            mint_pubkey,
+            token_program,
+        ));
+    }
+    if instructions.is_empty() {
+        return Ok((ata1, ata2));
+    }
+
+    let signature = send_instructions(client, payer, &[], &instructions, send_opts).await?;
+    info!(
+        "Transaction signature for {} associated token account creation(s): {}",
+        instructions.len(),
+        signature
+    );
+
+    Ok((ata1, ata2))
+}
+
+// Create two token accounts for the mint, each in its own transaction so the
+// two are submitted and confirmed concurrently
+pub async fn create_token_accounts(
+    client: &RpcClient,
+    payer: &Keypair,
+    mint_pubkey: &Pubkey,
+    token_accounts: &[Keypair; 2],
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<(Pubkey, Pubkey)> {
+    let [token_account1, token_account2] = token_accounts;
+    let space = token_account_space(client, mint_pubkey, send_opts).await?;
+
+    // Get minimum balance for rent exemption
+    let rent = rent_exempt_balance(client, space, send_opts).await?;
+
+    let (signature1, signature2) = tokio::try_join!(
+        send_token_account_creation(
+            client,
+            payer,
+            mint_pubkey,
+            token_account1,
+            (space, rent),
+            token_program,
+            send_opts,
+        ),
+        send_token_account_creation(
+            client,
+            payer,
+            mint_pubkey,
+            token_account2,
+            (space, rent),
+            token_program,
+            send_opts,
+        ),
+    )?;
+    info!(
+        "Transaction signatures for the 2 token account creations: {}, {}",
+        signature1, signature2
+    );
+
+    Ok((token_account1.pubkey(), token_account2.pubkey()))
+}
+
+// Make the account reject transfers in that don't come with a memo, growing
+// it for the memo transfer extension first
+pub async fn require_transfer_memos(
+    client: &RpcClient,
+    payer: &Keypair,
+    account: &Pubkey,
+    owner: &Keypair,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    let reallocate_ix = reallocate(
+        token_program,
+        account,
+        &payer.pubkey(),
+        &owner.pubkey(),
+        &[],
+        &[ExtensionType::MemoTransfer],
+    )?;
+    let enable_ix = enable_required_transfer_memos(token_program, account, &owner.pubkey(), &[])?;
+    let signature = send_instructions(
+        client,
+        payer,
+        &[owner],
+        &[reallocate_ix, enable_ix],
+        send_opts,
+    )
+    .await?;
+    info!(
+        "Token Account {} now requires memos on incoming transfers: {}",
+        account, signature
+    );
+    Ok(signature)
+}
+
+// A signerless memo, for the transfers into accounts requiring one
+pub fn memo_instruction(memo: &str) -> Instruction {
+    Instruction::new_with_bytes(MEMO_PROGRAM_ID, memo.as_bytes(), vec![])
+}
+
+// Create one more token account for the mint, owned by the payer
+pub async fn create_token_account(
+    client: &RpcClient,
+    payer: &Keypair,
+    mint_pubkey: &Pubkey,
+    token_account: &Keypair,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<Pubkey> {
+    let space = token_account_space(client, mint_pubkey, send_opts).await?;
+    let rent = rent_exempt_balance(client, space, send_opts).await?;
+    let signature = send_token_account_creation(
+        client,
+        payer,
+        mint_pubkey,
+        token_account,
+        (space, rent),
+        token_program,
+        send_opts,
+    )
+    .await?;
+    info!(
+        "Transaction signature for token account {} creation: {}",
+        token_account.pubkey(),
+        signature
+    );
+    Ok(token_account.pubkey())
+}
+
+// Allocate the account with the given space and rent, then initialize it
+// with the payer as owner for simplicity
+async fn send_token_account_creation(
+    client: &RpcClient,
+    payer: &Keypair,
+    mint_pubkey: &Pubkey,
+    token_account: &Keypair,
+    (space, rent): (usize, u64),
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    let create_account_ix = system_instruction::create_account(
+        &payer.pubkey(),
+        &token_account.pubkey(),
+        rent,
+        space as u64,
+        token_program,
+    );
+    let init_account_ix = initialize_account(
+        token_program,
+        &token_account.pubkey(),
+        mint_pubkey,
+        &payer.pubkey(),
+    )?;
+    send_instructions(
+        client,
+        payer,
+        &[token_account],
+        &[create_account_ix, init_account_ix],
+        send_opts,
+    )
+    .await
+}
+
+// Mint extensions like the transfer fee need matching space in every token
+// account. A dry run never created the mint, so there's nothing to read and
+// the accounts are sized as for a plain one.
+async fn token_account_space(
+    client: &RpcClient,
+    mint_pubkey: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<usize> {
+    let mint_extensions = if send_opts.dry_run {
+        vec![]
+    } else {
+        let mint_account = fetch_created_account(client, mint_pubkey, send_opts).await?;
+        StateWithExtensions::<Mint>::unpack(&mint_account.data)?.get_extension_types()?
+    };
+    Ok(ExtensionType::try_calculate_account_len::<TokenAccount>(
+        &ExtensionType::get_required_init_account_extensions(&mint_extensions),
+    )?)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn mint_to(
+    client: &RpcClient,
+    payer: &Keypair,
+    authority: &Keypair,
+    mint_pubkey: &Pubkey,
+    token_account_pubkey: &Pubkey,
+    amount: u64,
+    decimals: u8,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    // Create the mint_to instruction
+    let mint_to_ix = spl_token_2022::instruction::mint_to(
+        token_program,
+        mint_pubkey,
+        token_account_pubkey,
+        &authority.pubkey(),
+        &[],
+        amount,
+    )?;
+
+    let signature =
+        send_instructions(client, payer, &[authority], &[mint_to_ix], send_opts).await?;
+    info!(
+        "Minted {} tokens to account {} with signature {}",
+        UiAmount::from_base_units(amount, decimals),
+        token_account_pubkey,
+        signature
+    );
+
+    Ok(signature)
+}
+
+// Create an m-of-n multisig over `signers`, which can then hold an authority
+// in place of a single keypair
+pub async fn create_multisig(
+    client: &RpcClient,
+    payer: &Keypair,
+    multisig: &Keypair,
+    signers: &[Pubkey],
+    m: u8,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    let rent = rent_exempt_balance(client, Multisig::LEN, send_opts).await?;
+    let create_account_ix = system_instruction::create_account(
+        &payer.pubkey(),
+        &multisig.pubkey(),
+        rent,
+        Multisig::LEN as u64,
+        token_program,
+    );
+    let signers: Vec<_> = signers.iter().collect();
+    let init_multisig_ix = initialize_multisig(token_program, &multisig.pubkey(), &signers, m)?;
+    let signature = send_instructions(
+        client,
+        payer,
+        &[multisig],
+        &[create_account_ix, init_multisig_ix],
+        send_opts,
+    )
+    .await?;
+    info!(
+        "Created {}-of-{} multisig {} with signature {}",
+        m,
+        signers.len(),
+        multisig.pubkey(),
+        signature
+    );
+    Ok(signature)
+}
+
+// Burn from a token account owned by the payer
+#[allow(clippy::too_many_arguments)]
+pub async fn burn(
+    client: &RpcClient,
+    payer: &Keypair,
+    mint_pubkey: &Pubkey,
+    token_account_pubkey: &Pubkey,
+    amount: u64,
+    decimals: u8,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<Signature> {
+    let burn_ix = burn_checked(
+        token_program,
+        token_account_pubkey,
+        mint_pubkey,
+        &payer.pubkey(),
+        &[],
+        amount,
+        decimals,
+    )?;
+
+    let signature = send_instructions(client, payer, &[], &[burn_ix], send_opts).await?;
+    info!(
+        "Burned {} tokens from account {} with signature {}",
+        UiAmount::from_base_units(amount, decimals),
+        token_account_pubkey,
+        signature
+    );
+
+    Ok(signature)
+}
+
+// Empty a token account and close it, its rent going back to the payer. An
+// account can only be closed holding nothing, so what's left is burned first
+// and, with a transfer fee, the fees withheld in it harvested to the mint.
+// Returns the signature and the lamports reclaimed.
+#[allow(clippy::too_many_arguments)]
+pub async fn burn_and_close(
+    client: &RpcClient,
+    payer: &Keypair,
+    mint_pubkey: &Pubkey,
+    token_account_pubkey: &Pubkey,
+    owner: &Keypair,
+    decimals: u8,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<(Signature, u64)> {
+    let account_info = fetch_created_account(client, token_account_pubkey, send_opts).await?;
+    let token_account = StateWithExtensions::<TokenAccount>::unpack(&account_info.data)?;
+    let mut instructions = vec![];
+    let withheld = token_account
+        .get_extension::<TransferFeeAmount>()
+        .map_or(0, |fee| u64::from(fee.withheld_amount));
+    if withheld > 0 {
+        instructions.push(harvest_withheld_tokens_to_mint(
+            token_program,
+            mint_pubkey,
+            &[token_account_pubkey],
+        )?);
+    }
+    let amount = token_account.base.amount;
+    if amount > 0 {
+        instructions.push(burn_checked(
+            token_program,
+            token_account_pubkey,
+            mint_pubkey,
+            &owner.pubkey(),
+            &[],
+            amount,
+            decimals,
+        )?);
+    }
+    instructions.push(close_account(
+        token_program,
+        token_account_pubkey,
+        &payer.pubkey(),
+        &owner.pubkey(),
+        &[],
+    )?);
+
+    let signature = send_instructions(client, payer, &[owner], &instructions, send_opts).await?;
+    info!(
+        "Closed token account {} after burning {} tokens, reclaiming {} lamports of rent: {}",
+        token_account_pubkey,
+        UiAmount::from_base_units(amount, decimals),
+        account_info.lamports,
+        signature
+    );
+
+    Ok((signature, account_info.lamports))
+}
+
+pub async fn fetch_mint_authority(
+    client: &RpcClient,
+    mint_pubkey: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<Option<Pubkey>> {
+    let account_info = fetch_created_account(client, mint_pubkey, send_opts).await?;
+    let mint = StateWithExtensions::<Mint>::unpack(&account_info.data)?;
+    Ok(mint.base.mint_authority.into())
+}
+
+pub async fn fetch_token_balance(
+    client: &RpcClient,
+    token_account_pubkey: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<u64> {
+    let account_info = fetch_created_account(client, token_account_pubkey, send_opts).await?;
+    let token_account = StateWithExtensions::<TokenAccount>::unpack(&account_info.data)?;
+    Ok(token_account.base.amount)
+}
+
+// A token account's balance with the slot the node read it at, or None once
+// the account is gone. Unlike fetch_token_balance it doesn't wait for the
+// account to show up, it's for checking balances rather than reading back
+// accounts just created.
+pub async fn fetch_token_balance_at_slot(
+    client: &RpcClient,
+    token_account_pubkey: &Pubkey,
+) -> Result<Option<(u64, u64)>> {
+    let response = client
+        .get_account_with_commitment(token_account_pubkey, client.commitment())
+        .await?;
+    let Some(account) = response.value else {
+        return Ok(None);
+    };
+    let token_account = StateWithExtensions::<TokenAccount>::unpack(&account.data)?;
+    Ok(Some((token_account.base.amount, response.context.slot)))
+}
+
+// A dry run works without a validator, using the default rent parameters
+pub(crate) async fn rent_exempt_balance(
+    client: &RpcClient,
+    space: usize,
+    send_opts: SendOptions,
+) -> Result<u64> {
+    if send_opts.dry_run {
+        return Ok(Rent::default().minimum_balance(space));
+    }
+    Ok(client.get_minimum_balance_for_rent_exemption(space).await?)
+}
+
+// Reads that follow right after a creation or mint can race the node and see
+// no account yet, so treat "not found" as transient for a few attempts. RPC
+// errors are still returned straight away.
+async fn fetch_created_account(
+    client: &RpcClient,
+    pubkey: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<Account> {
+    for attempt in 1..=send_opts.read_attempts {
+        if let Some(account) = client
+            .get_account_with_commitment(pubkey, client.commitment())
+            .await?
+            .value
+        {
+            return Ok(account);
+        }
+        if attempt < send_opts.read_attempts {
+            tokio::time::sleep(send_opts.confirm_interval).await;
+        }
+    }
+    bail!(
+        rpc,
+        "Account {} still not found after {} attempt(s)",
+        pubkey,
+        send_opts.read_attempts
+    )
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use color_eyre::Result;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Style, Stylize as _},
+    text::Line,
+    widgets::{Block, List, Paragraph, Row, Sparkline, Table},
+    DefaultTerminal, Frame,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use svmkit_vixen_demo::{
+    amount::UiAmount, latency::TransferTracker, program::program_label, subscriber::ParsedUpdate,
+    workload::Workload,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{filter::variant_name, sink::MessageHandler, state::MintDecimals};
+
+// Often enough to look live without redrawing for every update
+const FRAME_INTERVAL: Duration = Duration::from_millis(250);
+
+// One bar per second in the throughput panel
+const THROUGHPUT_SECONDS: usize = 120;
+
+const RECENT_INSTRUCTIONS: usize = 50;
+
+#[derive(Default)]
+struct DashboardState {
+    // Updates received in each of the last seconds, newest last
+    per_second: VecDeque<u64>,
+    current_second: u64,
+    total: u64,
+    instructions: VecDeque<String>,
+    // Latest balance of each token account, by owner and mint as state
+    // updates don't name the account
+    balances: BTreeMap<(String, String), String>,
+}
+
+// What the stream has shown so far, written by the sink and read by the
+// render thread
+#[derive(Clone)]
+pub struct Dashboard {
+    started: Instant,
+    state: Arc<Mutex<DashboardState>>,
+}
+
+impl Dashboard {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            state: Arc::default(),
+        }
+    }
+
+    // Draws on the terminal's alternate screen from its own thread until `q`,
+    // Esc or Ctrl-C, which cancel `shutdown`, or until shutdown comes from
+    // elsewhere. Raw mode swallows the Ctrl-C signal, hence checking the key.
+    pub fn spawn(
+        &self,
+        workload: Workload,
+        transfers: TransferTracker,
+        shutdown: CancellationToken,
+    ) -> JoinHandle<io::Result<()>> {
+        let dashboard = self.clone();
+        std::thread::spawn(move || {
+            let mut terminal = ratatui::init();
+            let res = dashboard.run(&mut terminal, &workload, &transfers, &shutdown);
+            ratatui::restore();
+            res
+        })
+    }
+
+    fn run(
+        &self,
+        terminal: &mut DefaultTerminal,
+        workload: &Workload,
+        transfers: &TransferTracker,
+        shutdown: &CancellationToken,
+    ) -> io::Result<()> {
+        while !shutdown.is_cancelled() {
+            terminal.draw(|frame| self.draw(frame, workload, transfers))?;
+            if !event::poll(FRAME_INTERVAL)? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                let quit = matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                    || (key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL));
+                if quit && key.kind == KeyEventKind::Press {
+                    shutdown.cancel();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn tick(&self, state: &mut DashboardState) {
+        let second = self.started.elapsed().as_secs();
+        if state.per_second.is_empty() {
+            state.per_second.push_back(0);
+            state.current_second = second;
+        }
+        while state.current_second < second {
+            state.per_second.push_back(0);
+            state.current_second += 1;
+        }
+        while state.per_second.len() > THROUGHPUT_SECONDS {
+            state.per_second.pop_front();
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame, workload: &Workload, transfers: &TransferTracker) {
+        let mut state = self.state.lock().unwrap();
+        self.tick(&mut state);
+        let [throughput, middle, status] = Layout::vertical([
+            Constraint::Length(7),
+            Constraint::Min(8),
+            Constraint::Length(5),
+        ])
+        .areas(frame.area());
+        let [instructions, balances] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                .areas(middle);
+
+        // The current second is still filling up, so the rate is the last
+        // complete one
+        let filling = state.per_second.len().saturating_sub(1);
+        let completed: Vec<u64> = state.per_second.range(..filling).copied().collect();
+        let rate = completed.last().copied().unwrap_or_default();
+        let width = throughput.width.saturating_sub(2) as usize;
+        let bars = &completed[completed.len().saturating_sub(width)..];
+        frame.render_widget(
+            Sparkline::default()
+                .block(Block::bordered().title(format!(
+                    " Stream: {} update(s)/s, {} total ",
+                    rate, state.total
+                )))
+                .data(bars)
+                .style(Style::new().green()),
+            throughput,
+        );
+
+        let height = instructions.height.saturating_sub(2) as usize;
+        frame.render_widget(
+            List::new(state.instructions.iter().rev().take(height).cloned())
+                .block(Block::bordered().title(" Latest instructions ")),
+            instructions,
+        );
+
+        frame.render_widget(
+            Table::new(
+                state.balances.iter().map(|((owner, mint), amount)| {
+                    Row::new([short(owner), short(mint), amount.clone()])
+                }),
+                [
+                    Constraint::Length(11),
+                    Constraint::Length(11),
+                    Constraint::Min(10),
+                ],
+            )
+            .header(Row::new(["Owner", "Mint", "Balance"]).bold())
+            .block(Block::bordered().title(" Token account balances ")),
+            balances,
+        );
+
+        let report = transfers.report();
+        let latency = match (report.percentile(50.0), report.max()) {
+            (Some(p50), Some(max)) => format!("p50 {:?}, max {:?}", p50, max),
+            _ => "none seen yet".to_string(),
+        };
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(format!(
+                    "{} transaction(s) sent, {} transfer(s) seen on the stream, {} not yet",
+                    workload.transactions_sent(),
+                    report.matched,
+                    report.unmatched
+                )),
+                Line::from(format!("Send-to-stream latency: {}", latency)),
+                Line::from("q to quit".dim()),
+            ])
+            .block(Block::bordered().title(format!(
+                " Workload, up {}s ",
+                self.started.elapsed().as_secs()
+            ))),
+            status,
+        );
+    }
+}
+
+// Feeds the dashboard in place of the log handler
+pub struct TuiHandler {
+    dashboard: Dashboard,
+    mint_decimals: MintDecimals,
+}
+
+impl TuiHandler {
+    pub fn new(
+        dashboard: Dashboard,
+        rpc_url: Option<String>,
+        commitment: CommitmentConfig,
+    ) -> Self {
+        Self {
+            dashboard,
+            mint_decimals: MintDecimals::new(rpc_url, commitment),
+        }
+    }
+}
+
+#[async_trait]
+impl MessageHandler for TuiHandler {
+    fn name(&self) -> &str {
+        "tui"
+    }
+
+    async fn handle(&mut self, program: &Pubkey, update: &ParsedUpdate) -> Result<()> {
+        let decimals = match update.token_account() {
+            Some(account) => self.mint_decimals.for_account(account).await,
+            None => None,
+        };
+        let dashboard = &self.dashboard;
+        let mut state = dashboard.state.lock().unwrap();
+        dashboard.tick(&mut state);
+        state.total += 1;
+        if let Some(count) = state.per_second.back_mut() {
+            *count += 1;
+        }
+        if update.is_instruction() {
+            let name = variant_name(update).unwrap_or_else(|| update.type_name());
+            let line = format!(
+                "{:>5}s  {}  {}",
+                dashboard.started.elapsed().as_secs(),
+                program_label(program),
+                name
+            );
+            state.instructions.push_back(line);
+            if state.instructions.len() > RECENT_INSTRUCTIONS {
+                state.instructions.pop_front();
+            }
+        }
+        if let Some(account) = update.token_account() {
+            let amount = match decimals {
+                Some(decimals) => UiAmount::from_base_units(account.amount, decimals).to_string(),
+                None => format!("{} base units", account.amount),
+            };
+            state
+                .balances
+                .insert((account.owner.clone(), account.mint.clone()), amount);
+        }
+        Ok(())
+    }
+}
+
+pub fn short(key: &str) -> String {
+    match (key.get(..4), key.get(key.len().saturating_sub(4)..)) {
+        (Some(start), Some(end)) if key.len() > 11 => format!("{}…{}", start, end),
+        _ => key.to_string(),
+    }
+}
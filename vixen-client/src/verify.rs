@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use color_eyre::Result;
+use metrics::counter;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use svmkit_vixen_demo::{subscriber::ParsedUpdate, token::fetch_token_balance_at_slot};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{sink::MessageHandler, state::AccountLookup};
+
+#[derive(clap::Args)]
+pub struct VerifyArgs {
+    /// How often to compare the balances seen on the stream with the
+    /// validator's, e.g. "10s"
+    #[arg(long, value_parser = parse_interval, default_value = "10s")]
+    pub interval: Duration,
+
+    /// How long an account must go without a stream update before it's
+    /// compared, so a transfer still on its way isn't reported
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "2s")]
+    pub settle: Duration,
+
+    /// Only check the stream's balances, without running the token workload
+    #[arg(long)]
+    pub stream_only: bool,
+}
+
+// A check every 0s would never wait between them
+fn parse_interval(arg: &str) -> Result<Duration, String> {
+    match humantime::parse_duration(arg) {
+        Ok(interval) if interval.is_zero() => Err("must be more than 0s".to_string()),
+        Ok(interval) => Ok(interval),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// The last balance the stream showed for an account
+struct StreamBalance {
+    amount: u64,
+    received: Instant,
+    // What RPC said at the last check, when it disagreed
+    mismatch: Option<u64>,
+    reported: bool,
+}
+
+type Balances = Arc<Mutex<HashMap<Pubkey, StreamBalance>>>;
+
+// Keeps the balance of every token account update it can pin to its
+// account, for the BalanceChecker to compare with RPC
+pub struct BalanceVerifier {
+    lookup: AccountLookup,
+    balances: Balances,
+}
+
+// Compares the stream's balances with the validator's every interval. An
+// account only counts as diverged when two checks in a row disagree with no
+// stream update in between, since either side may briefly be ahead.
+pub struct BalanceChecker {
+    client: RpcClient,
+    balances: Balances,
+    interval: Duration,
+    settle: Duration,
+    report: VerifyReport,
+}
+
+#[derive(Default)]
+pub struct VerifyReport {
+    pub checks: u64,
+    pub divergences: u64,
+    pub closed: u64,
+}
+
+pub fn balance_verifier(
+    rpc_url: &str,
+    commitment: CommitmentConfig,
+    args: &VerifyArgs,
+) -> (BalanceVerifier, BalanceChecker) {
+    let balances = Balances::default();
+    let verifier = BalanceVerifier {
+        lookup: AccountLookup::new(Some(rpc_url.to_string()), commitment),
+        balances: balances.clone(),
+    };
+    let checker = BalanceChecker {
+        client: RpcClient::new_with_commitment(rpc_url.to_string(), commitment),
+        balances,
+        interval: args.interval,
+        settle: args.settle,
+        report: VerifyReport::default(),
+    };
+    (verifier, checker)
+}
+
+#[async_trait]
+impl MessageHandler for BalanceVerifier {
+    fn name(&self) -> &str {
+        "verify"
+    }
+
+    async fn handle(&mut self, _program: &Pubkey, update: &ParsedUpdate) -> Result<()> {
+        let Some(account) = update.token_account() else {
+            return Ok(());
+        };
+        let Some(pubkey) = self.lookup.resolve(account).await else {
+            return Ok(());
+        };
+        // A new balance starts its checks over
+        self.balances.lock().unwrap().insert(
+            pubkey,
+            StreamBalance {
+                amount: account.amount,
+                received: Instant::now(),
+                mismatch: None,
+                reported: false,
+            },
+        );
+        Ok(())
+    }
+}
+
+impl BalanceChecker {
+    // Until shutdown, with a last check after it so the final balances are
+    // compared too
+    pub async fn run(mut self, shutdown: CancellationToken) -> VerifyReport {
+        let mut ticks = tokio::time::interval(self.interval);
+        ticks.tick().await;
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => self.check().await,
+                _ = shutdown.cancelled() => break,
+            }
+        }
+        self.check().await;
+        let accounts = self.balances.lock().unwrap().len();
+        info!(
+            "Verified {} token account(s) against RPC in {} check(s): {} divergence(s), {} closed",
+            accounts, self.report.checks, self.report.divergences, self.report.closed
+        );
+        self.report
+    }
+
+    async fn check(&mut self) {
+        let settled: Vec<_> = self
+            .balances
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, balance)| balance.received.elapsed() >= self.settle)
+            .map(|(pubkey, balance)| (*pubkey, balance.amount))
+            .collect();
+        for (pubkey, amount) in settled {
+            let fetched = match fetch_token_balance_at_slot(&self.client, &pubkey).await {
+                Ok(fetched) => fetched,
+                Err(e) => {
+                    warn!("Unable to fetch the balance of {}: {}", pubkey, e);
+                    continue;
+                }
+            };
+            self.report.checks += 1;
+            counter!("vixen_verify_checks_total").increment(1);
+            let mut balances = self.balances.lock().unwrap();
+            let Some((rpc_amount, slot)) = fetched else {
+                // Closing an account sends no state update to compare
+                balances.remove(&pubkey);
+                self.report.closed += 1;
+                continue;
+            };
+            let Some(balance) = balances.get_mut(&pubkey) else {
+                continue;
+            };
+            // Updated while the balance was being fetched
+            if balance.amount != amount {
+                continue;
+            }
+            if rpc_amount == amount {
+                if balance.reported {
+                    info!(
+                        "Token account {} agrees with RPC again at slot {}",
+                        pubkey, slot
+                    );
+                }
+                balance.mismatch = None;
+                balance.reported = false;
+                continue;
+            }
+            if balance.mismatch == Some(rpc_amount) && !balance.reported {
+                // The stream carries no slot, so the stream side can only be
+                // placed by when its update arrived
+                warn!(
+                    "Token account {} diverged: the stream's last update, {:?} ago, has a \
+                     balance of {} but RPC has {} at slot {}",
+                    pubkey,
+                    balance.received.elapsed(),
+                    amount,
+                    rpc_amount,
+                    slot
+                );
+                balance.reported = true;
+                self.report.divergences += 1;
+                counter!("vixen_verify_divergences_total").increment(1);
+            }
+            balance.mismatch = Some(rpc_amount);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    io::Write as _,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, KeyInit, Nonce};
+use rand::Rng as _;
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::{read_keypair_file, write_keypair_file, Keypair};
+use tracing::info;
+
+use crate::error::{bail, Context as _, Error, Result};
+
+// Payer keypairs for the workload to spread its transactions over. The
+// first is the primary, which funds and owns everything the workload
+// creates; every wallet takes turns paying the fees of the transactions that
+// don't create anything.
+pub struct Wallets {
+    keypairs: Vec<Keypair>,
+    next: AtomicUsize,
+}
+
+impl Wallets {
+    // Fresh keypairs that are gone once the run is over
+    pub fn generate(count: usize) -> Self {
+        Self::from_keypairs((0..count.max(1)).map(|_| Keypair::new()).collect())
+    }
+
+    // Reuse the wallets saved in `dir` by an earlier run, so they don't need
+    // funding all over again, and create the rest. They're saved as
+    // wallet-<i>.json in the Solana CLI's keypair format, or given a
+    // passphrase as wallet-<i>.enc.json, encrypted under a key derived from
+    // it. Wallets beyond `count` are left alone.
+    pub fn load_or_create(dir: &Path, count: usize, passphrase: Option<&str>) -> Result<Self> {
+        std::fs::create_dir_all(dir).context(Error::Config, || {
+            format!("Unable to create wallet directory {}", dir.display())
+        })?;
+        let mut keypairs = vec![];
+        let mut created = 0;
+        for index in 0..count.max(1) {
+            let (path, other) = wallet_paths(dir, index, passphrase.is_some());
+            // Either format would do, but two sets in one directory would
+            // silently swap the payers depending on the flags
+            if other.exists() {
+                bail!(
+                    config,
+                    "{} is saved {}, pass the same --wallet-passphrase as when it was created",
+                    other.display(),
+                    if passphrase.is_some() {
+                        "unencrypted"
+                    } else {
+                        "encrypted"
+                    }
+                );
+            }
+            let keypair = if path.exists() {
+                read_wallet(&path, passphrase)?
+            } else {
+                let keypair = Keypair::new();
+                write_wallet(&path, &keypair, passphrase)?;
+                created += 1;
+                keypair
+            };
+            keypairs.push(keypair);
+        }
+        info!(
+            "Using {} wallet(s) from {}, {} of them new",
+            keypairs.len(),
+            dir.display(),
+            created
+        );
+        Ok(Self::from_keypairs(keypairs))
+    }
+
+    fn from_keypairs(keypairs: Vec<Keypair>) -> Self {
+        Self {
+            keypairs,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn primary(&self) -> &Keypair {
+        &self.keypairs[0]
+    }
+
+    pub fn all(&self) -> &[Keypair] {
+        &self.keypairs
+    }
+
+    // The next wallet in turn, shared by every caller
+    pub fn next_fee_payer(&self) -> &Keypair {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.keypairs.len();
+        &self.keypairs[index]
+    }
+}
+
+fn wallet_paths(dir: &Path, index: usize, encrypted: bool) -> (PathBuf, PathBuf) {
+    let plain = dir.join(format!("wallet-{}.json", index));
+    let sealed = dir.join(format!("wallet-{}.enc.json", index));
+    if encrypted {
+        (sealed, plain)
+    } else {
+        (plain, sealed)
+    }
+}
+
+// A keypair sealed with ChaCha20-Poly1305 under a key derived from the
+// passphrase with Argon2id, with its own salt and nonce
+#[derive(Serialize, Deserialize)]
+struct EncryptedWallet {
+    kdf: String,
+    cipher: String,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+const KDF: &str = "argon2id";
+const CIPHER: &str = "chacha20poly1305";
+
+fn read_wallet(path: &Path, passphrase: Option<&str>) -> Result<Keypair> {
+    let Some(passphrase) = passphrase else {
+        return read_keypair_file(path).map_err(|e| {
+            Error::config(format!("Unable to read wallet {}: {}", path.display(), e))
+        });
+    };
+    let text = std::fs::read_to_string(path).context(Error::Config, || {
+        format!("Unable to read wallet {}", path.display())
+    })?;
+    let wallet: EncryptedWallet = serde_json::from_str(&text).context(Error::Config, || {
+        format!("Invalid wallet {}", path.display())
+    })?;
+    if wallet.kdf != KDF || wallet.cipher != CIPHER {
+        bail!(
+            config,
+            "Wallet {} uses {} and {}, only {} and {} are supported",
+            path.display(),
+            wallet.kdf,
+            wallet.cipher,
+            KDF,
+            CIPHER
+        );
+    }
+    let decode = |field: &str| {
+        BASE64.decode(field).context(Error::Config, || {
+            format!("Invalid wallet {}", path.display())
+        })
+    };
+    let salt = decode(&wallet.salt)?;
+    let nonce = decode(&wallet.nonce)?;
+    if nonce.len() != 12 {
+        bail!(
+            config,
+            "Invalid wallet {}: the nonce isn't 12 bytes",
+            path.display()
+        );
+    }
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?.into());
+    let bytes = cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            decode(&wallet.ciphertext)?.as_slice(),
+        )
+        .map_err(|_| {
+            Error::config(format!(
+                "Unable to decrypt wallet {}, is the passphrase right?",
+                path.display()
+            ))
+        })?;
+    Keypair::from_bytes(&bytes).context(Error::Config, || {
+        format!("Invalid wallet {}", path.display())
+    })
+}
+
+fn write_wallet(path: &Path, keypair: &Keypair, passphrase: Option<&str>) -> Result<()> {
+    let Some(passphrase) = passphrase else {
+        write_keypair_file(keypair, path).map_err(|e| {
+            Error::config(format!("Unable to write wallet {}: {}", path.display(), e))
+        })?;
+        return Ok(());
+    };
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill(&mut salt);
+    rand::thread_rng().fill(&mut nonce);
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?.into());
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), keypair.to_bytes().as_slice())
+        .map_err(|_| Error::config(format!("Unable to encrypt wallet {}", path.display())))?;
+    let wallet = EncryptedWallet {
+        kdf: KDF.to_string(),
+        cipher: CIPHER.to_string(),
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    // Readable by the owner only, like the Solana CLI's keypair files
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).context(Error::Config, || {
+        format!("Unable to write wallet {}", path.display())
+    })?;
+    file.write_all(serde_json::to_string(&wallet)?.as_bytes())
+        .context(Error::Config, || {
+            format!("Unable to write wallet {}", path.display())
+        })?;
+    Ok(())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::config(format!("Unable to derive the wallet key: {}", e)))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signer::Signer;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("vixen-wallets-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    fn pubkeys(wallets: &Wallets) -> Vec<String> {
+        wallets
+            .all()
+            .iter()
+            .map(|kp| kp.pubkey().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn reloads_plain_wallets_and_adds_missing_ones() {
+        let dir = temp_dir("plain");
+        let first = Wallets::load_or_create(&dir, 2, None).unwrap();
+        let second = Wallets::load_or_create(&dir, 3, None).unwrap();
+        assert_eq!(pubkeys(&first), pubkeys(&second)[..2]);
+        // Readable by the Solana CLI
+        read_keypair_file(dir.join("wallet-2.json")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn encrypted_wallets_need_the_same_passphrase() {
+        let dir = temp_dir("encrypted");
+        let first = Wallets::load_or_create(&dir, 1, Some("correct horse")).unwrap();
+        let again = Wallets::load_or_create(&dir, 1, Some("correct horse")).unwrap();
+        assert_eq!(pubkeys(&first), pubkeys(&again));
+        let err = Wallets::load_or_create(&dir, 1, Some("battery staple"))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("passphrase"), "{}", err);
+        let err = Wallets::load_or_create(&dir, 1, None).err().unwrap();
+        assert!(err.to_string().contains("saved encrypted"), "{}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fee_payers_take_turns() {
+        let wallets = Wallets::generate(3);
+        let turns: Vec<_> = (0..4).map(|_| wallets.next_fee_payer().pubkey()).collect();
+        let all: Vec<_> = wallets.all().iter().map(Signer::pubkey).collect();
+        assert_eq!(turns, [all[0], all[1], all[2], all[0]]);
+    }
+}
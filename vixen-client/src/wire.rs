@@ -0,0 +1,106 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+
+use http_body::{Body, Frame, SizeHint};
+use yellowstone_vixen_proto::tonic::{
+    body::{boxed, BoxBody},
+    codegen::{http, Bytes, Service},
+    transport::{self, Channel},
+    Status,
+};
+
+// What the stream costs on the wire: the bytes of every response body as
+// received, before tonic decompresses them, and the encoding the server
+// answered the last subscription with. Shared by every connection of a
+// subscriber's clones.
+#[derive(Clone, Default)]
+pub struct WireStats {
+    bytes: Arc<AtomicU64>,
+    encoding: Arc<Mutex<Option<String>>>,
+}
+
+impl WireStats {
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    // None while uncompressed, e.g. when the server doesn't support any of
+    // the encodings offered
+    pub fn encoding(&self) -> Option<String> {
+        self.encoding.lock().unwrap().clone()
+    }
+
+    pub(crate) fn negotiated(&self, encoding: Option<String>) {
+        *self.encoding.lock().unwrap() = encoding;
+    }
+}
+
+// A channel whose responses count their bytes towards `WireStats`
+#[derive(Clone)]
+pub struct CountedChannel {
+    channel: Channel,
+    wire: WireStats,
+}
+
+impl CountedChannel {
+    pub(crate) fn new(channel: Channel, wire: WireStats) -> Self {
+        Self { channel, wire }
+    }
+}
+
+impl Service<http::Request<BoxBody>> for CountedChannel {
+    type Response = http::Response<BoxBody>;
+    type Error = transport::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.channel.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        let response = self.channel.call(request);
+        let bytes = Arc::clone(&self.wire.bytes);
+        Box::pin(async move {
+            let response = response.await?;
+            Ok(response.map(|inner| boxed(CountedBody { inner, bytes })))
+        })
+    }
+}
+
+struct CountedBody {
+    inner: BoxBody,
+    bytes: Arc<AtomicU64>,
+}
+
+impl Body for CountedBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Status>>> {
+        let frame = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &frame {
+            if let Some(data) = frame.data_ref() {
+                self.bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+            }
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
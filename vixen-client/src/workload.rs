@@ -0,0 +1,1536 @@
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use futures::future::try_join_all;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    native_token::LAMPORTS_PER_SOL,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, write_keypair_file, Keypair, Signature},
+    signer::Signer,
+};
+use spl_token_2022::{
+    extension::{transfer_fee::instruction::transfer_checked_with_fee, ExtensionType},
+    instruction::{approve_checked, mint_to_checked, revoke, set_authority, AuthorityType},
+    state::{Account as TokenAccount, Mint, Multisig},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, info_span, Instrument as _};
+
+use crate::{
+    amount::UiAmount,
+    batch::{self, BatchItem},
+    bench::{self, Bench, BenchConfig},
+    cluster::Cluster,
+    error::{bail, Context as _, Error, Result},
+    latency::TransferTracker,
+    load::{self, LoadConfig, TransferLeg},
+    scenario::{self, Scenario, ScenarioRun},
+    token::{
+        airdrop_new_address, burn, burn_and_close, create_associated_token_accounts,
+        create_mint_with_extensions, create_multisig, create_token_account, create_token_accounts,
+        fetch_mint_authority, fetch_token_balance, memo_instruction, mint_to,
+        require_transfer_memos, send_instructions, transfer_from_faucet, MintExtensions,
+        SendOptions, TransferFeeParams,
+    },
+    wallet::Wallets,
+};
+
+pub const DEFAULT_DECIMALS: u8 = 6;
+// In whole tokens
+pub const DEFAULT_MINT_AMOUNT: u64 = 10_000;
+pub const DEFAULT_TRANSFER_AMOUNT: u64 = 1_000;
+pub const DEFAULT_BURN_AMOUNT: u64 = 100;
+// A reused payer is topped up when it drops below this
+const MIN_PAYER_BALANCE: u64 = LAMPORTS_PER_SOL / 2;
+// Signatures across the demo transactions, with room to spare: the mint, the
+// token accounts and their memo requirement, minting, the authority rotation,
+// the transfer and the burn
+const DEMO_SIGNATURES: u64 = 16;
+// The multisig's creation, the handover and its mint, and the delegate's
+// approval, transfer and revocation
+const MULTISIG_SIGNATURES: u64 = 6;
+const DELEGATE_SIGNATURES: u64 = 4;
+const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+// Signers of the mint authority multisig out of three
+const MULTISIG_THRESHOLD: u8 = 2;
+const TRANSFER_MEMO: &str = "svmkit vixen demo transfer";
+
+// The memo tagging each transfer when transfer ids are on: `vixen-demo-`, a
+// random tag for the run and the transfer's number, e.g. vixen-demo-1a2b3c4d-7
+#[derive(Clone)]
+struct TransferIds {
+    tag: String,
+    next: Arc<AtomicU64>,
+}
+
+impl TransferIds {
+    fn new() -> Self {
+        Self {
+            tag: format!("vixen-demo-{:08x}", rand::random::<u32>()),
+            next: Arc::default(),
+        }
+    }
+
+    fn next(&self) -> String {
+        format!("{}-{}", self.tag, self.next.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+// The mint / transfer workflow the demo runs against a validator, with amounts
+// in base units. Start from `Workload::new` and adjust it with the `with_*`
+// methods, e.g.
+//
+//     let run = Workload::new("http://localhost:8899")
+//         .with_decimals(9)
+//         .with_transfer_amount(5_000_000_000)
+//         .run(std::future::ready(Ok(())))
+//         .await?;
+#[derive(Clone)]
+pub struct Workload {
+    rpc_url: String,
+    commitment: CommitmentConfig,
+    decimals: u8,
+    mint_amount: u64,
+    transfer_amount: u64,
+    burn_amount: u64,
+    airdrop_lamports: u64,
+    transfer_fee: Option<TransferFeeParams>,
+    interest_rate: Option<i16>,
+    metadata_pointer: bool,
+    memo_transfers: bool,
+    transfer_ids: Option<TransferIds>,
+    teardown: bool,
+    associated_token_accounts: bool,
+    rotate_authority: bool,
+    multisig_authority: bool,
+    delegated_transfer: bool,
+    keypair_path: Option<PathBuf>,
+    // Funds the payer and wallets in place of the cluster's faucet
+    faucet: Option<Arc<Keypair>>,
+    // In place of the single payer, which becomes the primary wallet
+    wallets: Option<Arc<Wallets>>,
+    // Used to build every token instruction the workload sends
+    token_program: Pubkey,
+    load: Option<LoadConfig>,
+    // Sent in place of the demo flow by `run_configured`
+    scenario: Option<Arc<Scenario>>,
+    send_opts: SendOptions,
+    shutdown: CancellationToken,
+    // Shared between clones so it can still be read once a run has been
+    // stopped part way
+    transactions_sent: Arc<AtomicU64>,
+    transfer_tracker: Option<TransferTracker>,
+}
+
+impl Workload {
+    // The demo's defaults: a fresh payer funded with 1 SOL, plain Token-2022
+    // accounts and no extensions
+    pub fn new(rpc_url: impl Into<String>) -> Self {
+        let unit = 10u64.pow(DEFAULT_DECIMALS.into());
+        Self {
+            rpc_url: rpc_url.into(),
+            commitment: CommitmentConfig::confirmed(),
+            decimals: DEFAULT_DECIMALS,
+            mint_amount: DEFAULT_MINT_AMOUNT * unit,
+            transfer_amount: DEFAULT_TRANSFER_AMOUNT * unit,
+            burn_amount: DEFAULT_BURN_AMOUNT * unit,
+            airdrop_lamports: LAMPORTS_PER_SOL,
+            transfer_fee: None,
+            interest_rate: None,
+            metadata_pointer: false,
+            memo_transfers: false,
+            transfer_ids: None,
+            teardown: false,
+            associated_token_accounts: false,
+            rotate_authority: false,
+            multisig_authority: false,
+            delegated_transfer: false,
+            keypair_path: None,
+            faucet: None,
+            wallets: None,
+            token_program: spl_token_2022::id(),
+            load: None,
+            scenario: None,
+            send_opts: SendOptions::default(),
+            shutdown: CancellationToken::new(),
+            transactions_sent: Arc::default(),
+            transfer_tracker: None,
+        }
+    }
+
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    // The amounts are in base units, so set them after the decimals they're
+    // counted in
+    pub fn with_decimals(mut self, decimals: u8) -> Self {
+        self.decimals = decimals;
+        self
+    }
+
+    pub fn with_mint_amount(mut self, amount: u64) -> Self {
+        self.mint_amount = amount;
+        self
+    }
+
+    pub fn with_transfer_amount(mut self, amount: u64) -> Self {
+        self.transfer_amount = amount;
+        self
+    }
+
+    pub fn with_burn_amount(mut self, amount: u64) -> Self {
+        self.burn_amount = amount;
+        self
+    }
+
+    pub fn with_airdrop_lamports(mut self, lamports: u64) -> Self {
+        self.airdrop_lamports = lamports;
+        self
+    }
+
+    pub fn with_transfer_fee(mut self, fee: Option<TransferFeeParams>) -> Self {
+        self.transfer_fee = fee;
+        self
+    }
+
+    // Annual rate in basis points, for the interest-bearing extension
+    pub fn with_interest_rate(mut self, rate: Option<i16>) -> Self {
+        self.interest_rate = rate;
+        self
+    }
+
+    pub fn with_metadata_pointer(mut self, enabled: bool) -> Self {
+        self.metadata_pointer = enabled;
+        self
+    }
+
+    // Have every token account require a memo on incoming transfers, which
+    // the workload's own transfers then carry
+    pub fn with_memo_transfers(mut self, enabled: bool) -> Self {
+        self.memo_transfers = enabled;
+        self
+    }
+
+    // Send every transfer with a memo carrying an id of its own, which the
+    // transfer tracker logs once it has paired the transfer with its update
+    pub fn with_transfer_ids(mut self, enabled: bool) -> Self {
+        self.transfer_ids = enabled.then(TransferIds::new);
+        self
+    }
+
+    // Once the run is done, burn what's left in its token accounts and close
+    // them, so repeated runs don't leave accounts and their rent behind on a
+    // long-lived cluster
+    pub fn with_teardown(mut self, enabled: bool) -> Self {
+        self.teardown = enabled;
+        self
+    }
+
+    pub fn with_associated_token_accounts(mut self, enabled: bool) -> Self {
+        self.associated_token_accounts = enabled;
+        self
+    }
+
+    pub fn with_rotate_authority(mut self, enabled: bool) -> Self {
+        self.rotate_authority = enabled;
+        self
+    }
+
+    // Hand the mint authority to a 2-of-3 multisig and mint again with two of
+    // its signers. It takes the authority from the payer, like the rotation,
+    // so only one of them can run.
+    pub fn with_multisig_authority(mut self, enabled: bool) -> Self {
+        self.multisig_authority = enabled;
+        self
+    }
+
+    // After the burn, approve a delegate for one more transfer, have it sign
+    // that transfer in place of the owner, then revoke it
+    pub fn with_delegated_transfer(mut self, enabled: bool) -> Self {
+        self.delegated_transfer = enabled;
+        self
+    }
+
+    // Reuse the payer saved at this path, creating it on the first run
+    pub fn with_keypair_path(mut self, path: Option<PathBuf>) -> Self {
+        self.keypair_path = path;
+        self
+    }
+
+    // Send the payer and wallets SOL from this keypair rather than asking for
+    // an airdrop. The payer is topped up to whatever the run needs, even
+    // beyond the airdrop amount.
+    pub fn with_faucet(mut self, faucet: Option<Keypair>) -> Self {
+        self.faucet = faucet.map(Arc::new);
+        self
+    }
+
+    pub fn with_wallets(mut self, wallets: Option<Wallets>) -> Self {
+        self.wallets = wallets.map(Arc::new);
+        self
+    }
+
+    pub fn with_token_program(mut self, program: Pubkey) -> Self {
+        self.token_program = program;
+        self
+    }
+
+    // Keep bouncing transfers between the two accounts once the workflow is done
+    pub fn with_load(mut self, load: Option<LoadConfig>) -> Self {
+        self.load = load;
+        self
+    }
+
+    pub fn with_scenario(mut self, scenario: Option<Scenario>) -> Self {
+        self.scenario = scenario.map(Arc::new);
+        self
+    }
+
+    pub fn with_send_options(mut self, send_opts: SendOptions) -> Self {
+        self.send_opts = send_opts;
+        self
+    }
+
+    // The cluster already detected, for the airdrops to warn on mainnet
+    // without asking the RPC node again
+    pub fn with_cluster(mut self, cluster: Cluster) -> Self {
+        self.send_opts.cluster = Some(cluster);
+        self
+    }
+
+    // Cancelling the token stops the demo flow between transactions, or the
+    // load between transfers
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    // Hand every transfer to the tracker, to pair it with its update on the
+    // stream
+    pub fn with_transfer_tracker(mut self, tracker: TransferTracker) -> Self {
+        self.transfer_tracker = Some(tracker);
+        self
+    }
+
+    // Transactions sent so far by any run of this workload, load included
+    pub fn transactions_sent(&self) -> u64 {
+        self.transactions_sent.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_sent(&self) {
+        self.transactions_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Who pays the fee of a transaction that creates nothing: the wallets in
+    // turn, or `payer` itself without any
+    pub(crate) fn fee_payer<'a>(&'a self, payer: &'a Keypair) -> &'a Keypair {
+        match &self.wallets {
+            Some(wallets) => wallets.next_fee_payer(),
+            None => payer,
+        }
+    }
+
+    pub(crate) fn shutdown(&self) -> &CancellationToken {
+        &self.shutdown
+    }
+
+    pub(crate) fn send_opts(&self) -> SendOptions {
+        self.send_opts
+    }
+
+    // The id for the next transfer's memo, with transfer ids on
+    pub(crate) fn next_transfer_id(&self) -> Option<String> {
+        self.transfer_ids.as_ref().map(TransferIds::next)
+    }
+
+    pub(crate) fn transfer_sent(
+        &self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        signature: Signature,
+        sent_at: Instant,
+        id: Option<String>,
+    ) {
+        if let Some(tracker) = &self.transfer_tracker {
+            tracker.sent(
+                source,
+                destination,
+                self.transfer_amount,
+                signature,
+                sent_at,
+                id,
+            );
+        }
+    }
+
+    // `stream_ready` resolves once the updates this workload produces can be
+    // observed, it's awaited after the airdrop so the two overlap. Fails if
+    // the shutdown token is cancelled before the demo flow is done, while a
+    // cancelled load still reports what it sent.
+    pub async fn run(&self, stream_ready: impl Future<Output = Result<()>>) -> Result<WorkloadRun> {
+        let keypairs = WorkloadKeypairs::generate(self.payer()?);
+        keypairs.ensure_distinct()?;
+        info!("Public key: {}", keypairs.payer.pubkey());
+        let rpc_client = RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment);
+        // Dropping the flow abandons whatever it was waiting on, a
+        // transaction already sent may still land
+        let mut run = tokio::select! {
+            run = self.run_demo(&rpc_client, &keypairs, stream_ready) => run?,
+            _ = self.shutdown.cancelled() => bail!(workload, "Stopped by shutdown before the demo finished"),
+        };
+        run.log(self.decimals);
+
+        let kp = &keypairs.payer;
+        let [pk1, pk2] = run.accounts;
+        let mut pool = vec![(pk1, kp), (pk2, keypairs.pk2_owner(self))];
+        if let Some(load) = self.load {
+            let extra = tokio::select! {
+                extra = self.extend_pool(&rpc_client, kp, &run.mint, &pk1, load) => extra?,
+                _ = self.shutdown.cancelled() => bail!(workload, "Stopped by shutdown before the load started"),
+            };
+            pool.extend(extra.into_iter().map(|account| (account, kp)));
+            // Round the pool, each account passing the amount on to the next
+            let legs: Vec<_> = (0..pool.len())
+                .map(|i| {
+                    let (source, owner) = pool[i];
+                    TransferLeg {
+                        source,
+                        destination: pool[(i + 1) % pool.len()].0,
+                        owner,
+                    }
+                })
+                .collect();
+            load::run(
+                &rpc_client,
+                kp,
+                &run.mint,
+                &legs,
+                self,
+                load,
+                self.send_opts,
+            )
+            .await?;
+        }
+
+        // A stopped run leaves its accounts as they are
+        if self.teardown && !self.shutdown.is_cancelled() {
+            let accounts: Vec<_> = pool
+                .iter()
+                .map(|&(account, owner)| TeardownAccount {
+                    mint: run.mint,
+                    decimals: self.decimals,
+                    account,
+                    owner,
+                })
+                .collect();
+            let signatures = tokio::select! {
+                signatures = self.teardown(&rpc_client, kp, &accounts) => signatures?,
+                _ = self.shutdown.cancelled() => bail!(workload, "Stopped by shutdown before the teardown finished"),
+            };
+            run.signatures.extend(signatures);
+        }
+
+        Ok(run)
+    }
+
+    fn funding(&self) -> &'static str {
+        match self.faucet {
+            Some(_) => "faucet transfer",
+            None => "airdrop",
+        }
+    }
+
+    pub(crate) fn tears_down(&self) -> bool {
+        self.teardown
+    }
+
+    // Empty and close each account, one transaction apiece, handing each to
+    // the transfer tracker first so its CloseAccount is looked for on the
+    // stream
+    pub(crate) async fn teardown(
+        &self,
+        rpc_client: &RpcClient,
+        payer: &Keypair,
+        accounts: &[TeardownAccount<'_>],
+    ) -> Result<Vec<Signature>> {
+        if self.send_opts.dry_run {
+            info!("Dry run, skipping the teardown");
+            return Ok(vec![]);
+        }
+        let mut signatures = vec![];
+        let mut reclaimed = 0;
+        for account in accounts {
+            if let Some(tracker) = &self.transfer_tracker {
+                tracker.closing(&account.account);
+            }
+            let (signature, lamports) = burn_and_close(
+                rpc_client,
+                payer,
+                &account.mint,
+                &account.account,
+                account.owner,
+                account.decimals,
+                &self.token_program,
+                self.send_opts,
+            )
+            .instrument(info_span!("teardown"))
+            .await?;
+            self.record(&mut signatures, signature);
+            reclaimed += lamports;
+        }
+        info!(
+            "Tore down {} token account(s), reclaiming {} lamports of rent to the payer",
+            accounts.len(),
+            reclaimed
+        );
+        Ok(signatures)
+    }
+
+    // Funds the payer and waits for `stream_ready` like `run`, then sends the
+    // scenario's steps instead of the demo flow
+    pub async fn run_scenario(
+        &self,
+        scenario: &Scenario,
+        stream_ready: impl Future<Output = Result<()>>,
+    ) -> Result<ScenarioRun> {
+        let payer = self.payer()?;
+        info!("Public key: {}", payer.pubkey());
+        let rpc_client = RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment);
+        let play = async {
+            if self.send_opts.dry_run {
+                info!("Dry run, skipping the airdrop");
+            } else {
+                fund_payer(&rpc_client, &payer, self).await?;
+            }
+            stream_ready.await?;
+            scenario::run(
+                scenario,
+                self,
+                &rpc_client,
+                &payer,
+                &self.token_program,
+                self.send_opts,
+            )
+            .await
+        };
+        tokio::select! {
+            run = play => run,
+            _ = self.shutdown.cancelled() => bail!(workload, "Stopped by shutdown before the scenario finished"),
+        }
+    }
+
+    // The scenario given to `with_scenario`, otherwise the demo flow and any
+    // load after it, for callers that only need to know it succeeded
+    pub async fn run_configured(
+        &self,
+        stream_ready: impl Future<Output = Result<()>>,
+    ) -> Result<()> {
+        match &self.scenario {
+            Some(scenario) => self.run_scenario(scenario, stream_ready).await.map(|_| ()),
+            None => self.run(stream_ready).await.map(|_| ()),
+        }
+    }
+
+    // Funds the payer like `run`, then creates the bench's mints, each with
+    // its token accounts minted `mint_amount`, and its nonce accounts, ready
+    // for `Bench::run`
+    pub async fn prepare_bench(&self, config: BenchConfig) -> Result<Bench> {
+        if config.mints == 0 || config.nonces == 0 || config.batch == 0 {
+            bail!(
+                config,
+                "The bench needs at least one mint, nonce account and transfer per transaction"
+            );
+        }
+        if config.accounts < load::MIN_POOL_ACCOUNTS {
+            bail!(
+                config,
+                "The bench needs at least {} token accounts per mint to transfer between",
+                load::MIN_POOL_ACCOUNTS
+            );
+        }
+        let payer = self.payer()?;
+        info!("Public key: {}", payer.pubkey());
+        let client = RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment);
+        let prepare = async {
+            fund_payer(&client, &payer, self)
+                .instrument(info_span!("airdrop"))
+                .await?;
+            // The mints don't depend on each other, nor do the nonce accounts
+            let mints =
+                (0..config.mints).map(|_| self.add_bench_mint(&client, &payer, config.accounts));
+            let mints = try_join_all(mints).await?;
+            let nonces =
+                bench::create_nonce_accounts(&client, &payer, config.nonces, self.send_opts)
+                    .await?;
+            Ok::<_, Error>((mints, nonces))
+        };
+        let (mints, nonces) = tokio::select! {
+            prepared = prepare => prepared?,
+            _ = self.shutdown.cancelled() => bail!(workload, "Stopped by shutdown before the bench started"),
+        };
+        info!(
+            "Bench set up: {} mint(s) with {} token account(s) each, {} nonce account(s)",
+            mints.len(),
+            config.accounts,
+            nonces.len()
+        );
+        Ok(Bench {
+            client,
+            payer,
+            workload: self.clone(),
+            config,
+            mints,
+            nonces,
+        })
+    }
+
+    // A mint with the workload's extensions and `accounts` token accounts,
+    // each minted `mint_amount`
+    async fn add_bench_mint(
+        &self,
+        client: &RpcClient,
+        payer: &Keypair,
+        accounts: usize,
+    ) -> Result<(Pubkey, Vec<Pubkey>)> {
+        let mint = Keypair::new();
+        create_mint_with_extensions(
+            &mint,
+            payer,
+            client,
+            self.decimals,
+            self.mint_extensions(),
+            &self.token_program,
+            self.send_opts,
+        )
+        .await?;
+        self.record_sent();
+        let mint = mint.pubkey();
+        let accounts = (0..accounts).map(|_| async move {
+            let account = create_token_account(
+                client,
+                payer,
+                &mint,
+                &Keypair::new(),
+                &self.token_program,
+                self.send_opts,
+            )
+            .await?;
+            self.record_sent();
+            mint_to(
+                client,
+                payer,
+                payer,
+                &mint,
+                &account,
+                self.mint_amount,
+                self.decimals,
+                &self.token_program,
+                self.send_opts,
+            )
+            .await?;
+            self.record_sent();
+            Ok::<_, Error>(account)
+        });
+        Ok((mint, try_join_all(accounts).await?))
+    }
+
+    fn payer(&self) -> Result<Keypair> {
+        if let Some(wallets) = &self.wallets {
+            return Ok(wallets.primary().insecure_clone());
+        }
+        match &self.keypair_path {
+            Some(path) => load_or_create_keypair(path),
+            None => Ok(Keypair::new()),
+        }
+    }
+
+    async fn run_demo(
+        &self,
+        rpc_client: &RpcClient,
+        keypairs: &WorkloadKeypairs,
+        stream_ready: impl Future<Output = Result<()>>,
+    ) -> Result<WorkloadRun> {
+        let send_opts = self.send_opts;
+        let kp = &keypairs.payer;
+        if self.rotate_authority && self.multisig_authority {
+            bail!(
+                config,
+                "The mint authority can be rotated or handed to a multisig, not both"
+            );
+        }
+        if send_opts.dry_run {
+            info!("Dry run, skipping the airdrop");
+        } else {
+            fund_payer(rpc_client, kp, self)
+                .instrument(info_span!("airdrop"))
+                .await?;
+        }
+        stream_ready.await?;
+        let mint_keypair = &keypairs.mint;
+        let mut signatures = vec![];
+        let signature = create_mint_with_extensions(
+            mint_keypair,
+            kp,
+            rpc_client,
+            self.decimals,
+            self.mint_extensions(),
+            &self.token_program,
+            send_opts,
+        )
+        .instrument(info_span!("create_mint"))
+        .await?;
+        self.record(&mut signatures, signature);
+        let (pk1, pk2) = if self.associated_token_accounts {
+            create_associated_token_accounts(
+                rpc_client,
+                kp,
+                &mint_keypair.pubkey(),
+                &[kp.pubkey(), keypairs.recipient.pubkey()],
+                &self.token_program,
+                send_opts,
+            )
+            .await?
+        } else {
+            create_token_accounts(
+                rpc_client,
+                kp,
+                &mint_keypair.pubkey(),
+                &keypairs.token_accounts,
+                &self.token_program,
+                send_opts,
+            )
+            .await?
+        };
+        info!("Token Account 1 created: {}", pk1);
+        info!("Token Account 2 created: {}", pk2);
+        let accounts = [pk1, pk2];
+        if self.memo_transfers {
+            // The two accounts don't depend on each other
+            let owners = [kp, keypairs.pk2_owner(self)];
+            let requirements = accounts.iter().zip(owners).map(|(account, owner)| {
+                require_transfer_memos(
+                    rpc_client,
+                    kp,
+                    account,
+                    owner,
+                    &self.token_program,
+                    send_opts,
+                )
+            });
+            for signature in try_join_all(requirements).await? {
+                self.record(&mut signatures, signature);
+            }
+        }
+        if let Some(tracker) = &self.transfer_tracker {
+            tracker.watch(&accounts);
+        }
+
+        let signature = mint_to(
+            rpc_client,
+            kp,
+            kp,
+            &mint_keypair.pubkey(),
+            &pk1,
+            self.mint_amount,
+            self.decimals,
+            &self.token_program,
+            send_opts,
+        )
+        .instrument(info_span!("mint_to"))
+        .await?;
+        self.record(&mut signatures, signature);
+
+        if let Some(balances) = fetch_balances(rpc_client, &accounts, send_opts).await? {
+            log_balances(&accounts, balances, "balance", self.decimals);
+        }
+
+        if self.rotate_authority {
+            let rotation = rotate_mint_authority(
+                rpc_client,
+                kp,
+                &mint_keypair.pubkey(),
+                &pk1,
+                self.mint_amount,
+                self.decimals,
+                &self.token_program,
+                send_opts,
+            )
+            .await?;
+            for signature in rotation {
+                self.record(&mut signatures, signature);
+            }
+        }
+        if self.multisig_authority {
+            let handover = multisig_mint_authority(
+                rpc_client,
+                kp,
+                &mint_keypair.pubkey(),
+                &pk1,
+                self.mint_amount,
+                self.decimals,
+                &self.token_program,
+                send_opts,
+            )
+            .instrument(info_span!("multisig"))
+            .await?;
+            for signature in handover {
+                self.record(&mut signatures, signature);
+            }
+        }
+
+        if !send_opts.dry_run {
+            let source_balance = fetch_token_balance(rpc_client, &pk1, send_opts).await?;
+            if source_balance < self.transfer_amount {
+                bail!(
+                    workload,
+                    "Token Account {} holds {} but the transfer needs {}, \
+                     raise the mint amount or lower the transfer amount",
+                    pk1,
+                    UiAmount::from_base_units(source_balance, self.decimals),
+                    UiAmount::from_base_units(self.transfer_amount, self.decimals)
+                );
+            }
+        }
+        if let Some(fee) = self
+            .transfer_fee
+            .and_then(|fee_params| fee_params.fee_for(self.transfer_amount))
+        {
+            info!(
+                "Transferring with a withheld fee of {}",
+                UiAmount::from_base_units(fee, self.decimals)
+            );
+        }
+        let id = self.next_transfer_id();
+        let instructions = transfer_instructions(
+            self,
+            &mint_keypair.pubkey(),
+            &pk1,
+            &pk2,
+            &kp.pubkey(),
+            id.as_deref(),
+        )?;
+        let sent_at = Instant::now();
+        let fee_payer = self.fee_payer(kp);
+        let signature = send_instructions(rpc_client, fee_payer, &[kp], &instructions, send_opts)
+            .instrument(info_span!("transfer"))
+            .await?;
+        info!("Transfer transaction signature: {}", signature);
+        self.transfer_sent(&pk1, &pk2, signature, sent_at, id);
+        self.record(&mut signatures, signature);
+
+        let signature = burn(
+            rpc_client,
+            kp,
+            &mint_keypair.pubkey(),
+            &pk1,
+            self.burn_amount,
+            self.decimals,
+            &self.token_program,
+            send_opts,
+        )
+        .instrument(info_span!("burn"))
+        .await?;
+
+        self.record(&mut signatures, signature);
+
+        if self.delegated_transfer {
+            let delegation = self
+                .delegated_transfer(rpc_client, kp, &mint_keypair.pubkey(), &pk1, &pk2)
+                .instrument(info_span!("delegate"))
+                .await?;
+            for signature in delegation {
+                self.record(&mut signatures, signature);
+            }
+        }
+
+        Ok(WorkloadRun {
+            mint: mint_keypair.pubkey(),
+            accounts,
+            final_balances: fetch_balances(rpc_client, &accounts, send_opts).await?,
+            signatures,
+        })
+    }
+
+    // Approve a delegate on `source` for the transfer amount, send one
+    // transfer signed by it rather than the owner, and revoke it again
+    async fn delegated_transfer(
+        &self,
+        rpc_client: &RpcClient,
+        kp: &Keypair,
+        mint: &Pubkey,
+        source: &Pubkey,
+        destination: &Pubkey,
+    ) -> Result<[Signature; 3]> {
+        let send_opts = self.send_opts;
+        if !send_opts.dry_run {
+            let balance = fetch_token_balance(rpc_client, source, send_opts).await?;
+            if balance < self.transfer_amount {
+                bail!(
+                    workload,
+                    "Token Account {} holds {} after the burn but the delegated transfer needs {}, \
+                     raise the mint amount or lower the transfer or burn amount",
+                    source,
+                    UiAmount::from_base_units(balance, self.decimals),
+                    UiAmount::from_base_units(self.transfer_amount, self.decimals)
+                );
+            }
+        }
+        let delegate = Keypair::new();
+        let approve_ix = approve_checked(
+            &self.token_program,
+            source,
+            mint,
+            &delegate.pubkey(),
+            &kp.pubkey(),
+            &[],
+            self.transfer_amount,
+            self.decimals,
+        )?;
+        let approved = send_instructions(rpc_client, kp, &[], &[approve_ix], send_opts).await?;
+        info!(
+            "Approved {} to spend {} from {} with signature {}",
+            delegate.pubkey(),
+            UiAmount::from_base_units(self.transfer_amount, self.decimals),
+            source,
+            approved
+        );
+
+        let id = self.next_transfer_id();
+        let instructions = transfer_instructions(
+            self,
+            mint,
+            source,
+            destination,
+            &delegate.pubkey(),
+            id.as_deref(),
+        )?;
+        let sent_at = Instant::now();
+        let transferred =
+            send_instructions(rpc_client, kp, &[&delegate], &instructions, send_opts).await?;
+        info!(
+            "Delegated transfer signed by {} with signature {}",
+            delegate.pubkey(),
+            transferred
+        );
+        self.transfer_sent(source, destination, transferred, sent_at, id);
+
+        let revoke_ix = revoke(&self.token_program, source, &kp.pubkey(), &[])?;
+        let revoked = send_instructions(rpc_client, kp, &[], &[revoke_ix], send_opts).await?;
+        info!(
+            "Revoked the delegate on {} with signature {}",
+            source, revoked
+        );
+        Ok([approved, transferred, revoked])
+    }
+
+    // Create the pool accounts beyond the demo's two and fund each from the
+    // first with two transfers, so a fee withheld on the way in still leaves
+    // enough to pass on
+    async fn extend_pool(
+        &self,
+        rpc_client: &RpcClient,
+        kp: &Keypair,
+        mint: &Pubkey,
+        funder: &Pubkey,
+        load: LoadConfig,
+    ) -> Result<Vec<Pubkey>> {
+        let extra = load.accounts.saturating_sub(load::MIN_POOL_ACCOUNTS);
+        if extra == 0 {
+            return Ok(vec![]);
+        }
+        let Some(needed) = (extra as u64)
+            .checked_mul(2)
+            .and_then(|transfers| transfers.checked_add(1))
+            .and_then(|transfers| self.transfer_amount.checked_mul(transfers))
+        else {
+            bail!(
+                config,
+                "A pool of {} accounts (--load-accounts) funded with --transfer-amount {} \
+                 needs more tokens than a u64 holds, lower either of them",
+                load.accounts,
+                UiAmount::from_base_units(self.transfer_amount, self.decimals)
+            );
+        };
+        let balance = fetch_token_balance(rpc_client, funder, self.send_opts).await?;
+        if balance < needed {
+            bail!(
+                workload,
+                "Token Account {} holds {} but a pool of {} accounts needs {}, \
+                 raise the mint amount or lower the transfer amount",
+                funder,
+                UiAmount::from_base_units(balance, self.decimals),
+                load.accounts,
+                UiAmount::from_base_units(needed, self.decimals)
+            );
+        }
+        // Each account only waits on its own creation, so they're all set up
+        // concurrently, then funded in as few transactions as fit
+        let setups = (0..extra).map(|_| self.add_pool_account(rpc_client, kp, mint));
+        let accounts = try_join_all(setups).await?;
+        let mut funding = vec![];
+        let mut ids = vec![];
+        for account in &accounts {
+            for _ in 0..2 {
+                let id = self.next_transfer_id();
+                funding.push(BatchItem {
+                    instructions: transfer_instructions(
+                        self,
+                        mint,
+                        funder,
+                        account,
+                        &kp.pubkey(),
+                        id.as_deref(),
+                    )?,
+                    signers: vec![],
+                });
+                ids.push((account, id));
+            }
+        }
+        let sent = batch::send_batched(rpc_client, kp, &funding, self.send_opts).await?;
+        let mut last = None;
+        for ((account, id), (signature, sent_at)) in ids.into_iter().zip(sent) {
+            if last != Some(signature) {
+                self.record_sent();
+                last = Some(signature);
+            }
+            self.transfer_sent(funder, account, signature, sent_at, id);
+        }
+        if let Some(tracker) = &self.transfer_tracker {
+            tracker.watch(&accounts);
+        }
+        info!("Load pool extended with {} token account(s)", extra);
+        Ok(accounts)
+    }
+
+    // One pool account, with the memo requirement if transfers carry one
+    async fn add_pool_account(
+        &self,
+        rpc_client: &RpcClient,
+        kp: &Keypair,
+        mint: &Pubkey,
+    ) -> Result<Pubkey> {
+        let account = create_token_account(
+            rpc_client,
+            kp,
+            mint,
+            &Keypair::new(),
+            &self.token_program,
+            self.send_opts,
+        )
+        .await?;
+        self.record_sent();
+        if self.memo_transfers {
+            require_transfer_memos(
+                rpc_client,
+                kp,
+                &account,
+                kp,
+                &self.token_program,
+                self.send_opts,
+            )
+            .await?;
+            self.record_sent();
+        }
+        Ok(account)
+    }
+
+    fn mint_extensions(&self) -> MintExtensions {
+        MintExtensions {
+            transfer_fee: self.transfer_fee,
+            interest_rate: self.interest_rate,
+            metadata_pointer: self.metadata_pointer,
+            freeze_authority: false,
+        }
+    }
+
+    // Keep the signature for the run's outcome and count the transaction
+    fn record(&self, signatures: &mut Vec<Signature>, signature: Signature) {
+        self.record_sent();
+        signatures.push(signature);
+    }
+}
+
+// A token account for the teardown to empty and close
+pub(crate) struct TeardownAccount<'a> {
+    pub(crate) mint: Pubkey,
+    pub(crate) decimals: u8,
+    pub(crate) account: Pubkey,
+    pub(crate) owner: &'a Keypair,
+}
+
+// What the workload did, so callers can check the outcome instead of
+// reading the log. A dry run reads nothing back and has no final balances.
+pub struct WorkloadRun {
+    pub mint: Pubkey,
+    pub accounts: [Pubkey; 2],
+    pub final_balances: Option<[u64; 2]>,
+    pub signatures: Vec<Signature>,
+}
+
+impl WorkloadRun {
+    fn log(&self, decimals: u8) {
+        if let Some(balances) = self.final_balances {
+            log_balances(&self.accounts, balances, "updated balance", decimals);
+        }
+        info!(
+            "Demo on mint {} finished after {} transaction(s)",
+            self.mint,
+            self.signatures.len()
+        );
+    }
+}
+
+// Every keypair the workload signs with
+struct WorkloadKeypairs {
+    payer: Keypair,
+    mint: Keypair,
+    token_accounts: [Keypair; 2],
+    // Owns the second associated token account, the payer owns the first
+    recipient: Keypair,
+}
+
+impl WorkloadKeypairs {
+    fn generate(payer: Keypair) -> Self {
+        Self {
+            payer,
+            mint: Keypair::new(),
+            token_accounts: [Keypair::new(), Keypair::new()],
+            recipient: Keypair::new(),
+        }
+    }
+
+    // Associated accounts are owned by the payer and the recipient, plain
+    // ones by the payer alone
+    fn pk2_owner(&self, workload: &Workload) -> &Keypair {
+        if workload.associated_token_accounts {
+            &self.recipient
+        } else {
+            &self.payer
+        }
+    }
+
+    // A key shared between two roles produces a malformed transaction with a
+    // cryptic signing error, so reject it before anything is sent
+    fn ensure_distinct(&self) -> Result<()> {
+        let roles = [
+            ("payer", self.payer.pubkey()),
+            ("mint", self.mint.pubkey()),
+            ("token account 1", self.token_accounts[0].pubkey()),
+            ("token account 2", self.token_accounts[1].pubkey()),
+            ("recipient", self.recipient.pubkey()),
+        ];
+        for (i, (role, pubkey)) in roles.iter().enumerate() {
+            if let Some((other, _)) = roles[i + 1..].iter().find(|(_, pk)| pk == pubkey) {
+                bail!(
+                    config,
+                    "Invalid keypair configuration: the {} and {} keypairs are the same ({})",
+                    role,
+                    other,
+                    pubkey
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+// Like the Solana CLI's id.json: reuse the keypair if the file exists,
+// otherwise generate one and save it for the next run
+fn load_or_create_keypair(path: &Path) -> Result<Keypair> {
+    if path.exists() {
+        let keypair = read_keypair_file(path).map_err(|e| {
+            Error::config(format!(
+                "Unable to read keypair from {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        info!("Loaded payer keypair from {}", path.display());
+        return Ok(keypair);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context(Error::Config, || {
+            format!("Unable to create {}", parent.display())
+        })?;
+    }
+    let keypair = Keypair::new();
+    write_keypair_file(&keypair, path).map_err(|e| {
+        Error::config(format!(
+            "Unable to write keypair to {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    info!("Saved new payer keypair to {}", path.display());
+    Ok(keypair)
+}
+
+// Fund the payer, a reused one may have enough left from an earlier run
+async fn fund_payer(rpc_client: &RpcClient, kp: &Keypair, workload: &Workload) -> Result<()> {
+    let payer_balance = rpc_client.get_balance(&kp.pubkey()).await?;
+    let required = required_lamports(rpc_client, workload).await?;
+    // A faucet keypair can cover the whole shortfall, where an airdrop is
+    // limited to what the cluster's faucet hands out
+    let shortfall = required.saturating_sub(payer_balance);
+    if payer_balance < MIN_PAYER_BALANCE || (workload.faucet.is_some() && shortfall > 0) {
+        fund(
+            rpc_client,
+            kp.pubkey(),
+            workload.airdrop_lamports.max(shortfall),
+            workload,
+        )
+        .await?;
+    } else {
+        info!(
+            "Payer already holds {} lamports, skipping the {}",
+            payer_balance,
+            workload.funding()
+        );
+    }
+    if let Some(wallets) = &workload.wallets {
+        // The others only ever pay fees, and don't depend on each other
+        try_join_all(
+            wallets.all()[1..]
+                .iter()
+                .map(|wallet| fund_fee_payer(rpc_client, wallet, workload)),
+        )
+        .await?;
+    }
+    // Better to stop here than have create_mint fail with an opaque
+    // insufficient funds error from the RPC node
+    let payer_balance = rpc_client.get_balance(&kp.pubkey()).await?;
+    if payer_balance < required {
+        bail!(
+            workload,
+            "Payer {} holds {} lamports but the demo needs about {} for rent and fees, \
+             fund it or use a fresh keypair",
+            kp.pubkey(),
+            payer_balance,
+            required
+        );
+    }
+    Ok(())
+}
+
+async fn fund_fee_payer(rpc_client: &RpcClient, kp: &Keypair, workload: &Workload) -> Result<()> {
+    let balance = rpc_client.get_balance(&kp.pubkey()).await?;
+    if balance >= MIN_PAYER_BALANCE {
+        info!(
+            "Wallet {} already holds {} lamports, skipping the {}",
+            kp.pubkey(),
+            balance,
+            workload.funding()
+        );
+        return Ok(());
+    }
+    fund(rpc_client, kp.pubkey(), workload.airdrop_lamports, workload).await
+}
+
+// From the faucet keypair when there is one, otherwise by airdrop
+async fn fund(
+    rpc_client: &RpcClient,
+    pubkey: Pubkey,
+    lamports: u64,
+    workload: &Workload,
+) -> Result<()> {
+    match &workload.faucet {
+        Some(faucet) => {
+            transfer_from_faucet(faucet, pubkey, lamports, rpc_client, workload.send_opts).await?;
+            Ok(())
+        }
+        None => airdrop_new_address(pubkey, lamports, rpc_client, workload.send_opts).await,
+    }
+}
+
+// Dry runs leave nothing on chain to read back
+async fn fetch_balances(
+    client: &RpcClient,
+    accounts: &[Pubkey; 2],
+    send_opts: SendOptions,
+) -> Result<Option<[u64; 2]>> {
+    if send_opts.dry_run {
+        return Ok(None);
+    }
+    let [pk1, pk2] = accounts;
+    let balances = tokio::try_join!(
+        fetch_token_balance(client, pk1, send_opts),
+        fetch_token_balance(client, pk2, send_opts),
+    )?;
+    Ok(Some(balances.into()))
+}
+
+fn log_balances(accounts: &[Pubkey; 2], balances: [u64; 2], label: &str, decimals: u8) {
+    for (account, balance) in accounts.iter().zip(balances) {
+        info!(
+            "Token Account {} {}: {}",
+            account,
+            label,
+            UiAmount::from_base_units(balance, decimals)
+        );
+    }
+}
+
+// Rent for the mint and every token account, sized for the mint's extensions,
+// plus signature fees for the demo transactions and any load traffic
+async fn required_lamports(client: &RpcClient, workload: &Workload) -> Result<u64> {
+    let mint_extensions = workload.mint_extensions().types();
+    let mint_len = ExtensionType::try_calculate_account_len::<Mint>(&mint_extensions)?;
+    let mut account_extensions =
+        ExtensionType::get_required_init_account_extensions(&mint_extensions);
+    if workload.memo_transfers {
+        account_extensions.push(ExtensionType::MemoTransfer);
+    }
+    let account_len =
+        ExtensionType::try_calculate_account_len::<TokenAccount>(&account_extensions)?;
+    let accounts = workload.load.map_or(2, |load| load.accounts.max(2)) as u64;
+    let (mint_rent, account_rent) = tokio::try_join!(
+        client.get_minimum_balance_for_rent_exemption(mint_len),
+        client.get_minimum_balance_for_rent_exemption(account_len),
+    )?;
+    let mut rent = mint_rent + accounts * account_rent;
+    let mut signatures = DEMO_SIGNATURES;
+    if workload.multisig_authority {
+        rent += client
+            .get_minimum_balance_for_rent_exemption(Multisig::LEN)
+            .await?;
+        signatures += MULTISIG_SIGNATURES;
+    }
+    if workload.delegated_transfer {
+        signatures += DELEGATE_SIGNATURES;
+    }
+    // Signed by the payer and the account's owner
+    if workload.teardown {
+        signatures += accounts * 2;
+    }
+    // Each load transaction is signed by the payer and maybe the owner of
+    // every account its transfers come out of. Each extra pool account takes
+    // two signatures to create and at most one to fund.
+    let load_signatures = workload.load.map_or(0, |load| {
+        (load.tps as f64 * load.duration.as_secs_f64()).ceil() as u64 * (load.batch as u64 + 1)
+            + (accounts - 2) * 3
+    });
+    Ok(rent + (signatures + load_signatures) * LAMPORTS_PER_SIGNATURE)
+}
+
+// The transfer, preceded by its id's memo when it has one, or by the memo the
+// destination requires when memo transfers are on. The id's memo is right in
+// front of the transfer, so it satisfies the requirement as well.
+pub(crate) fn transfer_instructions(
+    workload: &Workload,
+    mint: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    id: Option<&str>,
+) -> Result<Vec<Instruction>> {
+    let mut instructions = vec![];
+    match id {
+        Some(id) => instructions.push(memo_instruction(id)),
+        None if workload.memo_transfers => instructions.push(memo_instruction(TRANSFER_MEMO)),
+        None => {}
+    }
+    instructions.push(transfer_instruction(
+        workload,
+        mint,
+        source,
+        destination,
+        authority,
+    )?);
+    Ok(instructions)
+}
+
+// transfer_checked, or its transfer-fee variant carrying the expected fee when
+// the mint withholds one
+fn transfer_instruction(
+    workload: &Workload,
+    mint: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+) -> Result<Instruction> {
+    let amount = workload.transfer_amount;
+    let instruction = match workload.transfer_fee {
+        Some(fee_params) => {
+            let fee = fee_params.fee_for(amount).ok_or_else(|| {
+                Error::workload(format!("Transfer fee for {} overflowed", amount))
+            })?;
+            transfer_checked_with_fee(
+                &workload.token_program,
+                source,
+                mint,
+                destination,
+                authority,
+                &[],
+                amount,
+                workload.decimals,
+                fee,
+            )?
+        }
+        None => spl_token_2022::instruction::transfer_checked(
+            &workload.token_program,
+            source,
+            mint,
+            destination,
+            authority,
+            &[],
+            amount,
+            workload.decimals,
+        )?,
+    };
+    Ok(instruction)
+}
+
+// Hand mint authority to a fresh keypair, check the chain agrees, then mint
+// again with the new authority to prove the rotation took effect
+#[allow(clippy::too_many_arguments)]
+async fn rotate_mint_authority(
+    client: &RpcClient,
+    payer: &Keypair,
+    mint_pubkey: &Pubkey,
+    token_account_pubkey: &Pubkey,
+    amount: u64,
+    decimals: u8,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<[Signature; 2]> {
+    let new_authority = Keypair::new();
+    let set_authority_ix = set_authority(
+        token_program,
+        mint_pubkey,
+        Some(&new_authority.pubkey()),
+        AuthorityType::MintTokens,
+        &payer.pubkey(),
+        &[],
+    )?;
+    let rotated = send_instructions(client, payer, &[], &[set_authority_ix], send_opts).await?;
+    info!(
+        "Mint authority rotated from {} to {} with signature {}",
+        payer.pubkey(),
+        new_authority.pubkey(),
+        rotated
+    );
+
+    if !send_opts.dry_run {
+        let authority = fetch_mint_authority(client, mint_pubkey, send_opts).await?;
+        if authority != Some(new_authority.pubkey()) {
+            bail!(
+                workload,
+                "Mint {} has authority {:?} after rotation, expected {}",
+                mint_pubkey,
+                authority,
+                new_authority.pubkey()
+            );
+        }
+        info!(
+            "Verified on-chain mint authority is {}",
+            new_authority.pubkey()
+        );
+    }
+
+    let minted = mint_to(
+        client,
+        payer,
+        &new_authority,
+        mint_pubkey,
+        token_account_pubkey,
+        amount,
+        decimals,
+        token_program,
+        send_opts,
+    )
+    .await?;
+    Ok([rotated, minted])
+}
+
+// Hand the mint authority to a 2-of-3 multisig, then mint the same amount
+// again signed by two of its three signers
+#[allow(clippy::too_many_arguments)]
+async fn multisig_mint_authority(
+    client: &RpcClient,
+    payer: &Keypair,
+    mint_pubkey: &Pubkey,
+    token_account_pubkey: &Pubkey,
+    amount: u64,
+    decimals: u8,
+    token_program: &Pubkey,
+    send_opts: SendOptions,
+) -> Result<[Signature; 3]> {
+    let signers = [Keypair::new(), Keypair::new(), Keypair::new()];
+    let multisig = Keypair::new();
+    let signer_pubkeys: Vec<_> = signers.iter().map(Keypair::pubkey).collect();
+    let created = create_multisig(
+        client,
+        payer,
+        &multisig,
+        &signer_pubkeys,
+        MULTISIG_THRESHOLD,
+        token_program,
+        send_opts,
+    )
+    .await?;
+
+    let set_authority_ix = set_authority(
+        token_program,
+        mint_pubkey,
+        Some(&multisig.pubkey()),
+        AuthorityType::MintTokens,
+        &payer.pubkey(),
+        &[],
+    )?;
+    let handed_over = send_instructions(client, payer, &[], &[set_authority_ix], send_opts).await?;
+    info!(
+        "Mint authority handed from {} to multisig {} with signature {}",
+        payer.pubkey(),
+        multisig.pubkey(),
+        handed_over
+    );
+    if !send_opts.dry_run {
+        let authority = fetch_mint_authority(client, mint_pubkey, send_opts).await?;
+        if authority != Some(multisig.pubkey()) {
+            bail!(
+                workload,
+                "Mint {} has authority {:?} after the handover, expected multisig {}",
+                mint_pubkey,
+                authority,
+                multisig.pubkey()
+            );
+        }
+    }
+
+    // The first two signers are enough, the third stays out of it
+    let cosigners = &signers[..MULTISIG_THRESHOLD as usize];
+    let cosigner_pubkeys: Vec<_> = signer_pubkeys[..cosigners.len()].iter().collect();
+    let mint_ix = mint_to_checked(
+        token_program,
+        mint_pubkey,
+        token_account_pubkey,
+        &multisig.pubkey(),
+        &cosigner_pubkeys,
+        amount,
+        decimals,
+    )?;
+    let cosigners: Vec<_> = cosigners.iter().collect();
+    let minted = send_instructions(client, payer, &cosigners, &[mint_ix], send_opts).await?;
+    info!(
+        "Minted {} tokens to account {} with {} of the multisig's {} signers, signature {}",
+        UiAmount::from_base_units(amount, decimals),
+        token_account_pubkey,
+        MULTISIG_THRESHOLD,
+        signers.len(),
+        minted
+    );
+    Ok([created, handed_over, minted])
+}
@@ -0,0 +1,111 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use async_trait::async_trait;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        ConnectInfo, State, WebSocketUpgrade,
+    },
+    response::Response,
+    routing::get,
+    Json, Router,
+};
+use color_eyre::Result;
+use solana_sdk::pubkey::Pubkey;
+use svmkit_vixen_demo::{json::update_record, mints::MintAggregator, subscriber::ParsedUpdate};
+use tokio::{net::TcpListener, sync::broadcast};
+use tracing::{debug, info, warn};
+
+use crate::sink::MessageHandler;
+
+// Updates a client can fall behind by before it starts missing them
+const CLIENT_BUFFER: usize = 1024;
+
+// Re-broadcasts every update as the same JSON record as --output json to the
+// connected WebSocket clients. Each client reads from its own position in the
+// channel, so a slow one misses updates rather than holding back the stream
+// or the other clients.
+#[derive(Clone)]
+pub struct WsBroadcast {
+    updates: broadcast::Sender<Arc<str>>,
+}
+
+impl Default for WsBroadcast {
+    fn default() -> Self {
+        Self {
+            updates: broadcast::channel(CLIENT_BUFFER).0,
+        }
+    }
+}
+
+#[async_trait]
+impl MessageHandler for WsBroadcast {
+    fn name(&self) -> &str {
+        "websocket"
+    }
+
+    async fn handle(&mut self, program: &Pubkey, update: &ParsedUpdate) -> Result<()> {
+        // Nobody connected isn't a failure
+        if self.updates.receiver_count() > 0 {
+            let record = serde_json::to_string(&update_record(update, program)?)?;
+            let _ = self.updates.send(record.into());
+        }
+        Ok(())
+    }
+}
+
+// The mints' aggregates are served here too, as a plain GET of /mints, for
+// clients of the stream that don't have the status endpoints
+pub async fn serve(addr: SocketAddr, broadcast: WsBroadcast, mints: MintAggregator) -> Result<()> {
+    let app = Router::new()
+        .route("/", get(upgrade))
+        .route("/mints", get(move || async move { Json(mints.snapshot()) }))
+        .with_state(broadcast);
+    let listener = TcpListener::bind(addr).await?;
+    info!("Serving parsed updates over WebSocket on ws://{}", addr);
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn upgrade(
+    ws: WebSocketUpgrade,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    State(broadcast): State<WsBroadcast>,
+) -> Response {
+    let updates = broadcast.updates.subscribe();
+    ws.on_upgrade(move |socket| forward(socket, peer, updates))
+}
+
+// Anything the client sends is ignored, apart from it closing the socket
+async fn forward(
+    mut socket: WebSocket,
+    peer: SocketAddr,
+    mut updates: broadcast::Receiver<Arc<str>>,
+) {
+    debug!("WebSocket client {} connected", peer);
+    loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(record) => {
+                    if socket.send(Message::Text(record.to_string())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => warn!(
+                    "WebSocket client {} fell behind and missed {} update(s)",
+                    peer, missed
+                ),
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    debug!("WebSocket client {} disconnected", peer);
+}
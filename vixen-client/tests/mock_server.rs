@@ -0,0 +1,614 @@
+// The subscription and decode path against an in-process ProgramStreams
+// server that sends canned updates, so a proto upgrade that changes what the
+// client decodes fails here rather than in front of a live stream. Needs
+// nothing running, unlike the validator tests.
+
+use std::{
+    ops::ControlFlow,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::{stream, Stream, StreamExt as _};
+use solana_sdk::pubkey::Pubkey;
+use svmkit_vixen_demo::{
+    json::update_record,
+    subscriber::{
+        DecoderRegistry, ParsedUpdate, ReconnectPolicy, SlotSource, SubscriptionFailure,
+        SubscriptionHooks, VixenSubscriber,
+    },
+};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tokio_util::sync::CancellationToken;
+use yellowstone_vixen_proto::{
+    parser::{
+        token_extension_state_proto::StateOneof, token_program_ix_proto::IxOneof as TokenIx,
+        ExtendedTokenAccountProto, PoolStateProto, TokenAccountProto, TokenExtensionStateProto,
+        TokenProgramIxProto, TransferCheckedAccountsProto, TransferCheckedDataProto,
+        TransferCheckedIxProto,
+    },
+    prost::{Message, Name},
+    prost_types::Any,
+    stream::{
+        program_streams_server::{ProgramStreams, ProgramStreamsServer},
+        SubscribeRequest, SubscribeUpdate,
+    },
+    tonic::{
+        codec::CompressionEncoding,
+        metadata::MetadataValue,
+        service::Interceptor,
+        transport::{Endpoint, Server},
+        Request, Response, Status,
+    },
+};
+
+const TRANSFER_AMOUNT: u64 = 1_500_000;
+const ACCOUNT_AMOUNT: u64 = 8_500_000;
+
+// What a subscriber asked for: the program and its x-token header
+type Seen = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+// Sends the same updates to every subscriber, then ends the stream, or
+// keeps it open without sending anything more
+struct MockStreams {
+    updates: Vec<SubscribeUpdate>,
+    hold_open: bool,
+    seen: Seen,
+}
+
+#[async_trait]
+impl ProgramStreams for MockStreams {
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<SubscribeUpdate, Status>> + Send>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let token = request
+            .metadata()
+            .get("x-token")
+            .and_then(|token| token.to_str().ok())
+            .map(str::to_string);
+        self.seen
+            .lock()
+            .unwrap()
+            .push((request.into_inner().program, token));
+        let updates = stream::iter(self.updates.clone().into_iter().map(Ok));
+        if self.hold_open {
+            return Ok(Response::new(Box::pin(updates.chain(stream::pending()))));
+        }
+        Ok(Response::new(Box::pin(updates)))
+    }
+}
+
+// Serves the updates on a free local port for as long as the test runs
+async fn serve(updates: Vec<SubscribeUpdate>) -> (Endpoint, Seen) {
+    serve_with(updates, false, None).await
+}
+
+// Compressing the stream with `compression` when the client accepts it
+async fn serve_with(
+    updates: Vec<SubscribeUpdate>,
+    hold_open: bool,
+    compression: Option<CompressionEncoding>,
+) -> (Endpoint, Seen) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let seen = Seen::default();
+    let mut service = ProgramStreamsServer::new(MockStreams {
+        updates,
+        hold_open,
+        seen: seen.clone(),
+    });
+    if let Some(encoding) = compression {
+        service = service
+            .accept_compressed(encoding)
+            .send_compressed(encoding);
+    }
+    tokio::spawn(
+        Server::builder()
+            .add_service(service)
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    let endpoint = Endpoint::from_shared(format!("http://{}", addr)).unwrap();
+    (endpoint, seen)
+}
+
+fn update<M: Message + Name>(message: &M) -> SubscribeUpdate {
+    raw_update(&M::type_url(), message.encode_to_vec())
+}
+
+fn raw_update(type_url: &str, value: Vec<u8>) -> SubscribeUpdate {
+    SubscribeUpdate {
+        parsed: Some(Any {
+            type_url: type_url.to_string(),
+            value,
+        }),
+    }
+}
+
+fn transfer(source: &Pubkey, destination: &Pubkey, mint: &Pubkey) -> TokenProgramIxProto {
+    TokenProgramIxProto {
+        ix_oneof: Some(TokenIx::TransferChecked(TransferCheckedIxProto {
+            accounts: Some(TransferCheckedAccountsProto {
+                source: source.to_string(),
+                mint: mint.to_string(),
+                destination: destination.to_string(),
+                owner: Pubkey::new_unique().to_string(),
+                multisig_signers: vec![],
+            }),
+            data: Some(TransferCheckedDataProto {
+                amount: TRANSFER_AMOUNT,
+                decimals: 6,
+            }),
+        })),
+    }
+}
+
+fn account(mint: &Pubkey, owner: &Pubkey) -> TokenExtensionStateProto {
+    TokenExtensionStateProto {
+        state_oneof: Some(StateOneof::ExtendedTokenAccount(
+            ExtendedTokenAccountProto {
+                base_account: Some(TokenAccountProto {
+                    mint: mint.to_string(),
+                    owner: owner.to_string(),
+                    amount: ACCOUNT_AMOUNT,
+                    ..Default::default()
+                }),
+                extension_data_vec: vec![],
+            },
+        )),
+    }
+}
+
+#[tokio::test]
+async fn decodes_canned_updates() {
+    let (source, destination, mint, owner) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let (endpoint, _) = serve(vec![
+        update(&transfer(&source, &destination, &mint)),
+        update(&account(&mint, &owner)),
+        raw_update(
+            "type.googleapis.com/vixen.parser.NotYetKnownProto",
+            vec![1, 2, 3],
+        ),
+        // Claims to be an instruction but isn't one, so it's skipped
+        raw_update(&TokenProgramIxProto::type_url(), vec![0xff; 4]),
+        SubscribeUpdate { parsed: None },
+    ])
+    .await;
+    let program = Pubkey::new_unique();
+    let updates: Vec<ParsedUpdate> = VixenSubscriber::new(endpoint)
+        .subscribe(&program)
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert_eq!(updates.len(), 3, "{:?}", updates);
+
+    let Some(TokenIx::TransferChecked(ix)) = updates[0].token_instruction() else {
+        panic!("Not a transfer: {:?}", updates[0]);
+    };
+    let accounts = ix.accounts.as_ref().unwrap();
+    assert_eq!(accounts.source, source.to_string());
+    assert_eq!(accounts.destination, destination.to_string());
+    assert_eq!(ix.data.as_ref().unwrap().amount, TRANSFER_AMOUNT);
+    let record = update_record(&updates[0], &program).unwrap();
+    assert_eq!(record.type_name, TokenProgramIxProto::full_name());
+    assert_eq!(record.program, program.to_string());
+    assert!(
+        record.message.to_string().contains(&source.to_string()),
+        "{}",
+        record.message
+    );
+
+    assert!(updates[1].is_state());
+    let state = updates[1].token_account().unwrap();
+    assert_eq!(state.owner, owner.to_string());
+    assert_eq!(state.amount, ACCOUNT_AMOUNT);
+
+    let ParsedUpdate::Unknown(any) = &updates[2] else {
+        panic!("Not passed on as unknown: {:?}", updates[2]);
+    };
+    assert_eq!(any.value, [1, 2, 3]);
+    assert_eq!(
+        updates[2].type_name(),
+        "vixen.parser.NotYetKnownProto".to_string()
+    );
+}
+
+#[tokio::test]
+async fn decodes_registered_types() {
+    let pool = PoolStateProto {
+        token_mint_0: Pubkey::new_unique().to_string(),
+        tick_spacing: 60,
+        ..Default::default()
+    };
+    let (endpoint, _) = serve(vec![update(&pool)]).await;
+    let decoders = DecoderRegistry::default().register::<PoolStateProto>();
+    let updates: Vec<ParsedUpdate> = VixenSubscriber::new(endpoint)
+        .with_decoders(decoders)
+        .subscribe(&Pubkey::new_unique())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+
+    let [ParsedUpdate::Custom(custom)] = updates.as_slice() else {
+        panic!("Not decoded as a registered type: {:?}", updates);
+    };
+    assert_eq!(custom.downcast_ref::<PoolStateProto>(), Some(&pool));
+    assert_eq!(updates[0].type_name(), PoolStateProto::full_name());
+    let message = updates[0].to_json().unwrap();
+    assert_eq!(message["tickSpacing"], 60, "{}", message);
+}
+
+// Payloads that compress well, each being compressed on its own, and what
+// they take uncompressed on the wire: each message behind its 5 byte gRPC
+// prefix
+fn repeated_updates() -> (Vec<SubscribeUpdate>, u64) {
+    let payload = raw_update(
+        "type.googleapis.com/vixen.parser.NotYetKnownProto",
+        vec![7; 4096],
+    );
+    let updates = vec![payload; 50];
+    let bytes = updates.iter().map(|u| u.encoded_len() as u64 + 5).sum();
+    (updates, bytes)
+}
+
+#[tokio::test]
+async fn negotiates_compression_and_counts_the_wire_bytes() {
+    let (updates, uncompressed) = repeated_updates();
+    let (endpoint, _) = serve_with(updates, false, Some(CompressionEncoding::Gzip)).await;
+    let subscriber =
+        VixenSubscriber::new(endpoint.clone()).with_compression(vec![CompressionEncoding::Gzip]);
+    let received = subscriber
+        .subscribe(&Pubkey::new_unique())
+        .await
+        .unwrap()
+        .count()
+        .await;
+    assert_eq!(received, 50);
+    let wire = subscriber.wire_stats();
+    assert_eq!(wire.encoding().as_deref(), Some("gzip"));
+    assert!(
+        wire.bytes() > 0 && wire.bytes() < uncompressed,
+        "{} bytes on the wire for {} uncompressed",
+        wire.bytes(),
+        uncompressed
+    );
+
+    // Requests in an encoding the server doesn't accept are refused
+    let subscriber = VixenSubscriber::new(endpoint)
+        .with_compression(vec![CompressionEncoding::Gzip])
+        .with_send_compression(Some(CompressionEncoding::Zstd));
+    assert!(subscriber.subscribe(&Pubkey::new_unique()).await.is_err());
+}
+
+#[tokio::test]
+async fn counts_every_byte_of_an_uncompressed_stream() {
+    let (updates, uncompressed) = repeated_updates();
+    let (endpoint, _) = serve(updates).await;
+    // Asked for, but the server doesn't compress
+    let subscriber =
+        VixenSubscriber::new(endpoint).with_compression(vec![CompressionEncoding::Gzip]);
+    subscriber
+        .subscribe(&Pubkey::new_unique())
+        .await
+        .unwrap()
+        .count()
+        .await;
+    assert_eq!(subscriber.wire_stats().bytes(), uncompressed);
+    assert_eq!(subscriber.wire_stats().encoding(), None);
+}
+
+struct Token(&'static str);
+
+impl Interceptor for Token {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        request
+            .metadata_mut()
+            .insert("x-token", MetadataValue::from_static(self.0));
+        Ok(request)
+    }
+}
+
+#[tokio::test]
+async fn sends_the_program_and_interceptor_headers() {
+    let (endpoint, seen) = serve(vec![]).await;
+    let program = Pubkey::new_unique();
+    let subscriber = VixenSubscriber::new(endpoint).with_interceptor(Token("secret"));
+    let updates = subscriber.subscribe(&program).await.unwrap().count().await;
+    assert_eq!(updates, 0);
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [(program.to_string(), Some("secret".to_string()))]
+    );
+}
+
+// Counts what `run` reports and stops after the given number of connections
+struct Counting {
+    connections: u32,
+    updates: usize,
+    failures: Vec<String>,
+    stop_after: u32,
+}
+
+#[async_trait]
+impl SubscriptionHooks for Counting {
+    async fn connected(&mut self, attempt: u32) -> ControlFlow<()> {
+        self.connections = attempt;
+        if attempt > self.stop_after {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+
+    async fn update(&mut self, _: SubscribeUpdate) -> ControlFlow<()> {
+        self.updates += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn failed(&mut self, _: u32, failure: &SubscriptionFailure, _: Option<Duration>) {
+        self.failures.push(failure.to_string());
+    }
+}
+
+#[tokio::test]
+async fn run_reconnects_when_the_stream_ends() {
+    let mint = Pubkey::new_unique();
+    let (endpoint, seen) = serve(vec![update(&account(&mint, &Pubkey::new_unique()))]).await;
+    let policy = ReconnectPolicy {
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(10),
+        max_failures: 5,
+        failover_after: 3,
+        drop_after: None,
+        stale_after: None,
+    };
+    let mut hooks = Counting {
+        connections: 0,
+        updates: 0,
+        failures: vec![],
+        stop_after: 2,
+    };
+    VixenSubscriber::new(endpoint)
+        .run(
+            &Pubkey::new_unique(),
+            policy,
+            &mut hooks,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(hooks.connections, 3);
+    assert_eq!(hooks.updates, 2);
+    assert_eq!(hooks.failures.len(), 2, "{:?}", hooks.failures);
+    assert_eq!(seen.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn run_drops_connections_after_drop_after() {
+    let mint = Pubkey::new_unique();
+    let (endpoint, seen) = serve_with(
+        vec![update(&account(&mint, &Pubkey::new_unique()))],
+        true,
+        None,
+    )
+    .await;
+    let policy = ReconnectPolicy {
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(10),
+        max_failures: 1,
+        failover_after: 3,
+        drop_after: Some(Duration::from_millis(50)),
+        stale_after: None,
+    };
+    let mut hooks = Counting {
+        connections: 0,
+        updates: 0,
+        failures: vec![],
+        stop_after: 2,
+    };
+    VixenSubscriber::new(endpoint)
+        .run(
+            &Pubkey::new_unique(),
+            policy,
+            &mut hooks,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+    // Dropped connections don't count as failures, so a single allowed
+    // failure is enough
+    assert_eq!(hooks.connections, 3);
+    assert_eq!(hooks.updates, 2);
+    assert_eq!(
+        hooks.failures,
+        ["stream dropped on purpose", "stream dropped on purpose"]
+    );
+    assert_eq!(seen.lock().unwrap().len(), 3);
+}
+
+// A chain a slot further on every time it's asked, or stuck at one
+struct Slots {
+    slot: AtomicU64,
+    advancing: bool,
+}
+
+#[async_trait]
+impl SlotSource for Slots {
+    async fn slot(&self) -> Option<u64> {
+        Some(
+            self.slot
+                .fetch_add(u64::from(self.advancing), Ordering::Relaxed),
+        )
+    }
+}
+
+#[tokio::test]
+async fn run_resubscribes_when_the_stream_goes_stale() {
+    let mint = Pubkey::new_unique();
+    let (endpoint, seen) = serve_with(
+        vec![update(&account(&mint, &Pubkey::new_unique()))],
+        true,
+        None,
+    )
+    .await;
+    let policy = ReconnectPolicy {
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(10),
+        max_failures: 1,
+        failover_after: 3,
+        drop_after: None,
+        stale_after: Some(Duration::from_millis(50)),
+    };
+    let mut hooks = Counting {
+        connections: 0,
+        updates: 0,
+        failures: vec![],
+        stop_after: 2,
+    };
+    let slots = Slots {
+        slot: AtomicU64::new(1),
+        advancing: true,
+    };
+    VixenSubscriber::new(endpoint.clone())
+        .with_slots(Arc::new(slots))
+        .run(
+            &Pubkey::new_unique(),
+            policy,
+            &mut hooks,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+    // Stale connections don't count as failures either
+    assert_eq!(hooks.connections, 3);
+    assert_eq!(hooks.updates, 2);
+    assert_eq!(hooks.failures.len(), 2);
+    assert!(
+        hooks.failures[0].contains("while the chain advanced"),
+        "{:?}",
+        hooks.failures
+    );
+    assert_eq!(seen.lock().unwrap().len(), 3);
+
+    // With the chain stuck too, the quiet stream is left alone
+    let mut hooks = Counting {
+        connections: 0,
+        updates: 0,
+        failures: vec![],
+        stop_after: 2,
+    };
+    let slots = Slots {
+        slot: AtomicU64::new(1),
+        advancing: false,
+    };
+    let shutdown = CancellationToken::new();
+    let stop = shutdown.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        stop.cancel();
+    });
+    VixenSubscriber::new(endpoint)
+        .with_slots(Arc::new(slots))
+        .run(&Pubkey::new_unique(), policy, &mut hooks, &shutdown)
+        .await
+        .unwrap();
+    assert_eq!(hooks.connections, 1);
+    assert!(hooks.failures.is_empty(), "{:?}", hooks.failures);
+}
+
+// Bound and dropped, so nothing is listening on it
+async fn dead_endpoint() -> Endpoint {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    Endpoint::from_shared(format!("http://{}", addr)).unwrap()
+}
+
+#[tokio::test]
+async fn run_fails_over_to_the_next_endpoint() {
+    let mint = Pubkey::new_unique();
+    let (endpoint, seen) = serve(vec![update(&account(&mint, &Pubkey::new_unique()))]).await;
+    let policy = ReconnectPolicy {
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(10),
+        max_failures: 2,
+        failover_after: 3,
+        drop_after: None,
+        stale_after: None,
+    };
+    let mut hooks = Counting {
+        connections: 0,
+        updates: 0,
+        failures: vec![],
+        stop_after: 2,
+    };
+    // Never connected, so the first failure moves on
+    VixenSubscriber::new(dead_endpoint().await)
+        .with_fallback_endpoints([endpoint])
+        .run(
+            &Pubkey::new_unique(),
+            policy,
+            &mut hooks,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(hooks.updates, 1);
+    // The dead endpoint, then the mock's stream ending, after which it
+    // stays on the mock until the hooks stop it
+    assert_eq!(hooks.failures.len(), 2, "{:?}", hooks.failures);
+    assert_eq!(seen.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn run_gives_up_after_max_failures() {
+    let endpoint = dead_endpoint().await;
+    let policy = ReconnectPolicy {
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(10),
+        max_failures: 2,
+        failover_after: 3,
+        drop_after: None,
+        stale_after: None,
+    };
+    let mut hooks = Counting {
+        connections: 0,
+        updates: 0,
+        failures: vec![],
+        stop_after: u32::MAX,
+    };
+    let err = VixenSubscriber::new(endpoint)
+        .run(
+            &Pubkey::new_unique(),
+            policy,
+            &mut hooks,
+            &CancellationToken::new(),
+        )
+        .await
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string().contains("2 consecutive failure(s)"),
+        "{}",
+        err
+    );
+    assert_eq!(hooks.connections, 0);
+    assert_eq!(hooks.failures.len(), 2);
+}
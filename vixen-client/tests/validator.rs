@@ -0,0 +1,363 @@
+// End-to-end checks of the token helpers against a running
+// solana-test-validator. They only run with RUN_INTEGRATION=1, and skip
+// rather than fail when the validator can't be reached, so `cargo test`
+// stays green on machines without one. SOLANA_RPC_URL overrides the default
+// local endpoint.
+
+use std::{path::Path, time::Duration};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+    signature::Keypair, signer::Signer,
+};
+use spl_token_2022::{
+    extension::StateWithExtensions,
+    state::{Account as TokenAccount, AccountState},
+};
+use svmkit_vixen_demo::{
+    bench::BenchConfig,
+    scenario::Scenario,
+    token::{
+        airdrop_new_address, create_mint, create_token_accounts, fetch_token_balance,
+        fetch_token_balance_at_slot, mint_to, send_instructions, SendOptions, TransferFeeParams,
+    },
+    workload::Workload,
+};
+
+const DEFAULT_RPC_URL: &str = "http://localhost:8899";
+const TOKEN_PROGRAM: Pubkey = spl_token_2022::ID;
+const DECIMALS: u8 = 6;
+const MINT_AMOUNT: u64 = 10_000_000_000;
+const TRANSFER_AMOUNT: u64 = 1_000_000_000;
+const BURN_AMOUNT: u64 = 100_000_000;
+
+const SEND_OPTS: SendOptions = SendOptions {
+    explain: false,
+    confirm_attempts: 60,
+    confirm_interval: Duration::from_millis(500),
+    read_attempts: 20,
+    airdrop_timeout: Duration::from_secs(60),
+    send_attempts: 3,
+    send_timeout: Duration::from_secs(180),
+    dry_run: false,
+    chaos: None,
+    airdrop_commitment: None,
+    tx_commitment: None,
+    cluster: None,
+};
+
+fn rpc_url() -> String {
+    std::env::var("SOLANA_RPC_URL").unwrap_or_else(|_| DEFAULT_RPC_URL.to_string())
+}
+
+async fn validator() -> Option<RpcClient> {
+    if std::env::var("RUN_INTEGRATION").as_deref() != Ok("1") {
+        eprintln!("Skipping, set RUN_INTEGRATION=1 to run against a validator");
+        return None;
+    }
+    let url = rpc_url();
+    let client = RpcClient::new_with_commitment(url.clone(), CommitmentConfig::confirmed());
+    if let Err(e) = client.get_version().await {
+        eprintln!("Skipping, no validator reachable at {}: {}", url, e);
+        return None;
+    }
+    Some(client)
+}
+
+// A funded payer with a fresh mint and two token accounts it owns
+struct Setup {
+    payer: Keypair,
+    mint: Keypair,
+    accounts: (Pubkey, Pubkey),
+}
+
+async fn setup(client: &RpcClient) -> Setup {
+    let payer = Keypair::new();
+    airdrop_new_address(payer.pubkey(), LAMPORTS_PER_SOL, client, SEND_OPTS)
+        .await
+        .unwrap();
+    let mint = Keypair::new();
+    create_mint(&mint, &payer, client, DECIMALS, &TOKEN_PROGRAM, SEND_OPTS)
+        .await
+        .unwrap();
+    let token_accounts = [Keypair::new(), Keypair::new()];
+    let accounts = create_token_accounts(
+        client,
+        &payer,
+        &mint.pubkey(),
+        &token_accounts,
+        &TOKEN_PROGRAM,
+        SEND_OPTS,
+    )
+    .await
+    .unwrap();
+    Setup {
+        payer,
+        mint,
+        accounts,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn create_token_accounts_returns_two_initialized_accounts() {
+    let Some(client) = validator().await else {
+        return;
+    };
+    let Setup {
+        payer,
+        mint,
+        accounts: (pk1, pk2),
+    } = setup(&client).await;
+
+    assert_ne!(pk1, pk2);
+    for pubkey in [pk1, pk2] {
+        let data = client.get_account_data(&pubkey).await.unwrap();
+        let account = StateWithExtensions::<TokenAccount>::unpack(&data)
+            .unwrap()
+            .base;
+        assert_eq!(account.state, AccountState::Initialized);
+        assert_eq!(account.mint, mint.pubkey());
+        assert_eq!(account.owner, payer.pubkey());
+        assert_eq!(account.amount, 0);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn transfer_moves_exactly_the_transfer_amount() {
+    let Some(client) = validator().await else {
+        return;
+    };
+    let Setup {
+        payer,
+        mint,
+        accounts: (pk1, pk2),
+    } = setup(&client).await;
+
+    mint_to(
+        &client,
+        &payer,
+        &payer,
+        &mint.pubkey(),
+        &pk1,
+        MINT_AMOUNT,
+        DECIMALS,
+        &TOKEN_PROGRAM,
+        SEND_OPTS,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        fetch_token_balance(&client, &pk1, SEND_OPTS).await.unwrap(),
+        MINT_AMOUNT
+    );
+
+    let transfer = spl_token_2022::instruction::transfer_checked(
+        &TOKEN_PROGRAM,
+        &pk1,
+        &mint.pubkey(),
+        &pk2,
+        &payer.pubkey(),
+        &[],
+        TRANSFER_AMOUNT,
+        DECIMALS,
+    )
+    .unwrap();
+    send_instructions(&client, &payer, &[], &[transfer], SEND_OPTS)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fetch_token_balance(&client, &pk1, SEND_OPTS).await.unwrap(),
+        MINT_AMOUNT - TRANSFER_AMOUNT
+    );
+    assert_eq!(
+        fetch_token_balance(&client, &pk2, SEND_OPTS).await.unwrap(),
+        TRANSFER_AMOUNT
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn workload_leaves_the_expected_balances() {
+    if validator().await.is_none() {
+        return;
+    }
+    let run = Workload::new(rpc_url())
+        .with_decimals(DECIMALS)
+        .with_mint_amount(MINT_AMOUNT)
+        .with_transfer_amount(TRANSFER_AMOUNT)
+        .with_burn_amount(BURN_AMOUNT)
+        .with_send_options(SEND_OPTS)
+        .run(std::future::ready(Ok(())))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        run.final_balances,
+        Some([MINT_AMOUNT - TRANSFER_AMOUNT - BURN_AMOUNT, TRANSFER_AMOUNT])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn faucet_keypair_funds_the_payer() {
+    let Some(client) = validator().await else {
+        return;
+    };
+    let faucet = Keypair::new();
+    airdrop_new_address(faucet.pubkey(), 5 * LAMPORTS_PER_SOL, &client, SEND_OPTS)
+        .await
+        .unwrap();
+    // Nothing beyond the shortfall, so the payer ends up with what the run
+    // needs and no more
+    let run = Workload::new(rpc_url())
+        .with_decimals(DECIMALS)
+        .with_mint_amount(MINT_AMOUNT)
+        .with_transfer_amount(TRANSFER_AMOUNT)
+        .with_burn_amount(BURN_AMOUNT)
+        .with_airdrop_lamports(0)
+        .with_faucet(Some(faucet.insecure_clone()))
+        .with_send_options(SEND_OPTS)
+        .run(std::future::ready(Ok(())))
+        .await
+        .unwrap();
+
+    assert!(run.final_balances.is_some());
+    let spent = 5 * LAMPORTS_PER_SOL - client.get_balance(&faucet.pubkey()).await.unwrap();
+    assert!(spent > 0 && spent < LAMPORTS_PER_SOL, "{}", spent);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multisig_and_delegate_flows_leave_the_expected_balances() {
+    if validator().await.is_none() {
+        return;
+    }
+    let run = Workload::new(rpc_url())
+        .with_decimals(DECIMALS)
+        .with_mint_amount(MINT_AMOUNT)
+        .with_transfer_amount(TRANSFER_AMOUNT)
+        .with_burn_amount(BURN_AMOUNT)
+        .with_multisig_authority(true)
+        .with_delegated_transfer(true)
+        .with_send_options(SEND_OPTS)
+        .run(std::future::ready(Ok(())))
+        .await
+        .unwrap();
+
+    // Minted again by the multisig, and transferred again by the delegate
+    assert_eq!(
+        run.final_balances,
+        Some([
+            2 * MINT_AMOUNT - 2 * TRANSFER_AMOUNT - BURN_AMOUNT,
+            2 * TRANSFER_AMOUNT
+        ])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn teardown_closes_the_demo_accounts() {
+    let Some(client) = validator().await else {
+        return;
+    };
+    // The transfer fee leaves withheld tokens to harvest before closing
+    let run = Workload::new(rpc_url())
+        .with_decimals(DECIMALS)
+        .with_mint_amount(MINT_AMOUNT)
+        .with_transfer_amount(TRANSFER_AMOUNT)
+        .with_burn_amount(BURN_AMOUNT)
+        .with_transfer_fee(Some(TransferFeeParams {
+            basis_points: 50,
+            maximum_fee: u64::MAX,
+        }))
+        .with_teardown(true)
+        .with_send_options(SEND_OPTS)
+        .run(std::future::ready(Ok(())))
+        .await
+        .unwrap();
+
+    for account in run.accounts {
+        assert_eq!(
+            fetch_token_balance_at_slot(&client, &account)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn workload_runs_with_every_extension() {
+    if validator().await.is_none() {
+        return;
+    }
+    let run = Workload::new(rpc_url())
+        .with_decimals(DECIMALS)
+        .with_mint_amount(MINT_AMOUNT)
+        .with_transfer_amount(TRANSFER_AMOUNT)
+        .with_burn_amount(BURN_AMOUNT)
+        .with_interest_rate(Some(500))
+        .with_metadata_pointer(true)
+        .with_memo_transfers(true)
+        // The transfer's id memo has to satisfy the memo requirement too
+        .with_transfer_ids(true)
+        .with_send_options(SEND_OPTS)
+        .run(std::future::ready(Ok(())))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        run.final_balances,
+        Some([MINT_AMOUNT - TRANSFER_AMOUNT - BURN_AMOUNT, TRANSFER_AMOUNT])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bench_sends_whole_batches_on_durable_nonces() {
+    if validator().await.is_none() {
+        return;
+    }
+    let config = BenchConfig {
+        mints: 1,
+        accounts: 3,
+        batch: 3,
+        nonces: 2,
+        duration: Duration::from_secs(5),
+    };
+    let bench = Workload::new(rpc_url())
+        .with_decimals(DECIMALS)
+        .with_mint_amount(MINT_AMOUNT)
+        .with_transfer_amount(TRANSFER_AMOUNT)
+        .with_send_options(SEND_OPTS)
+        .prepare_bench(config)
+        .await
+        .unwrap();
+    let run = bench.run().await.unwrap();
+
+    assert!(run.transactions > 0);
+    assert_eq!(run.failed, 0);
+    assert_eq!(run.transfers, run.transactions * 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn burn_and_close_scenario_closes_its_accounts() {
+    let Some(client) = validator().await else {
+        return;
+    };
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios/burn_and_close.yaml");
+    let scenario = Scenario::load(&path).unwrap();
+    let run = Workload::new(rpc_url())
+        .with_send_options(SEND_OPTS)
+        .run_scenario(&scenario, std::future::ready(Ok(())))
+        .await
+        .unwrap();
+
+    assert_eq!(run.accounts.len(), 2);
+    for account in run.accounts.values() {
+        let closed = client
+            .get_account_with_commitment(account, client.commitment())
+            .await
+            .unwrap()
+            .value
+            .is_none();
+        assert!(closed, "{} should have been closed", account);
+    }
+}